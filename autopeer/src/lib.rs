@@ -3,12 +3,15 @@ use worker::*;
 
 // TODO move to common
 const CONFIG_PATH: &str = "/config.json";
+const AUTOPEER_PATH: &str = "/autopeer";
 
 #[derive(Serialize)]
 struct ConfigResponse {
     backend_url: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    autopeer_url: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -26,9 +29,52 @@ fn json_response<T: Serialize>(body: &T, status: u16) -> Result<Response> {
     )
 }
 
+async fn proxy_autopeer(mut req: Request, target: &str) -> Result<Response> {
+    if req.method() != Method::Post {
+        return Response::error("Method Not Allowed", 405);
+    }
+
+    let headers = Headers::new();
+    headers.set("Content-Type", "application/json")?;
+    if let Some(auth) = req.headers().get("Authorization")? {
+        headers.set("Authorization", &auth)?;
+    }
+    let body = req.text().await?;
+
+    let mut init = RequestInit::new();
+    init.with_method(Method::Post)
+        .with_headers(headers)
+        .with_body(Some(body.into()));
+    let upstream = Request::new_with_init(target, &init)?;
+
+    match Fetch::Request(upstream).send().await {
+        Ok(response) => Ok(response),
+        Err(e) => {
+            console_error!("AutoPeer backend request failed: {:?}", e);
+            let error = ErrorResponse {
+                error: "AutoPeer backend is unavailable".to_string(),
+            };
+            json_response(&error, 502)
+        }
+    }
+}
+
 #[event(fetch)]
 async fn main(req: Request, env: Env, _ctx: Context) -> Result<Response> {
     let url = req.url()?;
+    let autopeer_url = env.var("AUTOPEER_URL").ok().map(|v| v.to_string());
+
+    if url.path() == AUTOPEER_PATH {
+        return match autopeer_url {
+            Some(target) => proxy_autopeer(req, &target).await,
+            None => {
+                let error = ErrorResponse {
+                    error: "AUTOPEER_URL is not configured".to_string(),
+                };
+                json_response(&error, 404)
+            }
+        };
+    }
 
     if url.path() == CONFIG_PATH {
         let backend_url = env.var("BACKEND_URL");
@@ -39,6 +85,7 @@ async fn main(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                 let config = ConfigResponse {
                     backend_url: backend_url.to_string(),
                     username,
+                    autopeer_url: autopeer_url.map(|_| AUTOPEER_PATH.to_string()),
                 };
                 return json_response(&config, 200);
             }
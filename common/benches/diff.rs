@@ -0,0 +1,81 @@
+//! Compares `calculate_diff` against the m·n LCS table the previous
+//! implementation built for every node on every poll.
+//!
+//! Run with `cargo bench -p common --bench diff`.
+
+use std::hint::black_box;
+
+use chrono::Utc;
+use common::{diff::calculate_diff, models::Protocol};
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+
+fn protocols(count: usize) -> Vec<Protocol> {
+    (0..count)
+        .map(|i| Protocol {
+            name: format!("dn42_{:05}", i),
+            proto: "BGP".to_string(),
+            table: "---".to_string(),
+            state: "up".to_string(),
+            since: Utc::now().format("%Y-%m-%d").to_string(),
+            info: "Established".to_string(),
+            reason: None,
+        })
+        .collect()
+}
+
+/// A typical poll: a few sessions changed state, one was added and one was
+/// removed.
+fn changed(old: &[Protocol]) -> Vec<Protocol> {
+    let mut new = old.to_vec();
+    for protocol in new.iter_mut().step_by(97) {
+        protocol.state = "start".to_string();
+        protocol.info = "Active".to_string();
+    }
+    new.remove(new.len() / 3);
+    let mut added = protocols(1).remove(0);
+    added.name = "dn42_new".to_string();
+    new.insert(new.len() / 2, added);
+    new
+}
+
+fn lcs_table(old: &[Protocol], new: &[Protocol]) -> Vec<Vec<usize>> {
+    let mut c = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in 1..=old.len() {
+        for j in 1..=new.len() {
+            c[i][j] = if old[i - 1].name == new[j - 1].name {
+                c[i - 1][j - 1] + 1
+            } else {
+                c[i - 1][j].max(c[i][j - 1])
+            };
+        }
+    }
+    c
+}
+
+fn bench_diff(c: &mut Criterion) {
+    let mut group = c.benchmark_group("diff");
+    for size in [100, 1_500, 5_000] {
+        let old = protocols(size);
+        let new = changed(&old);
+
+        group.bench_with_input(BenchmarkId::new("unchanged", size), &old, |b, old| {
+            b.iter(|| calculate_diff(black_box(old), black_box(old)))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("changed", size),
+            &(&old, &new),
+            |b, (old, new)| b.iter(|| calculate_diff(black_box(old), black_box(new))),
+        );
+        if size <= 1_500 {
+            group.bench_with_input(
+                BenchmarkId::new("lcs_table", size),
+                &(&old, &new),
+                |b, (old, new)| b.iter(|| lcs_table(black_box(old), black_box(new))),
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_diff);
+criterion_main!(benches);
@@ -2,7 +2,11 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    models::{NetworkInfo, NodeProtocol, NodeStatusDiff, NodeWireGuard},
+    models::{
+        NetworkInfo, NodeProtocol, NodeStatusDiff, NodeTunnels, NodeWireGuard, NodeWireGuardDiff,
+    },
+    protocol::ProtocolDetails,
+    route::RouteEntry,
     traceroute::TracerouteHop,
 };
 
@@ -42,6 +46,10 @@ pub enum AppResponse {
     NoChange { last_updated: DateTime<Utc> },
     #[serde(rename = "wg")]
     WireGuard { data: Vec<NodeWireGuard> },
+    #[serde(rename = "wgd")]
+    WireGuardDiff { data: Vec<NodeWireGuardDiff> },
+    #[serde(rename = "tn")]
+    Tunnels { data: Vec<NodeTunnels> },
     #[serde(rename = "tri")]
     TracerouteInit { node: String },
     #[serde(rename = "tru")]
@@ -55,6 +63,11 @@ pub enum AppResponse {
     RouteLookupInit { node: String },
     #[serde(rename = "rlu")]
     RouteLookupUpdate { node: String, lines: Vec<String> },
+    #[serde(rename = "rlr")]
+    RouteLookupRoutes {
+        node: String,
+        routes: Vec<RouteEntry>,
+    },
     #[serde(rename = "pdi")]
     ProtocolDetailsInit { node: String, protocol: String },
     #[serde(rename = "pdu")]
@@ -63,8 +76,14 @@ pub enum AppResponse {
         protocol: String,
         lines: Vec<String>,
     },
+    #[serde(rename = "pdp")]
+    ProtocolDetailsParsed {
+        node: String,
+        protocol: String,
+        details: Option<Box<ProtocolDetails>>,
+    },
     #[serde(rename = "ni")]
     NetworkInfo(NetworkInfo),
     #[serde(rename = "e")]
-    Error(String),
+    Error { error: String },
 }
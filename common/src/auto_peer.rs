@@ -26,6 +26,7 @@ pub enum AutoPeerRequest {
 pub enum AutoPeerResponse {
     #[serde(rename = "init_success")]
     InitSuccess {
+        token: String,
         challenge_methods: Vec<ChallengeMethod>,
     },
     #[serde(rename = "init_error")]
@@ -50,7 +51,7 @@ pub enum AutoPeerResponse {
     OperationError { error: String },
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum ChallengeMethod {
     Pgp,
@@ -61,10 +62,36 @@ pub enum ChallengeMethod {
 pub struct PeeringSession {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
+    #[serde(default)]
+    pub node: String,
     pub name: String,
     pub ipv4: Option<String>,
     pub ipv6: Option<String>,
     pub endpoint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wg_pubkey: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub comment: Option<String>,
 }
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ProvisionRequest {
+    pub asn: u32,
+    pub session: PeeringSession,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ProvisionResponse {
+    pub files: Vec<RenderedFile>,
+    pub applied: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bird_output: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct RenderedFile {
+    pub path: String,
+    pub content: String,
+}
@@ -0,0 +1,374 @@
+use std::{fmt, net::IpAddr, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// The BIRD commands the proxy runs on behalf of the server. Anything else is
+/// rejected before it reaches BIRD, so a leaked shared secret can't be used
+/// to dump the whole RIB.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum BirdCommand {
+    /// `show protocols`
+    ShowProtocols,
+    /// `show protocols all <name>`
+    ShowProtocolsAll { name: String },
+    /// `show route for <prefix> [all]`
+    ShowRoute {
+        prefix: String,
+        #[serde(default)]
+        all: bool,
+    },
+    /// `show route where <condition> [all]`
+    ShowRouteWhere {
+        condition: RouteCondition,
+        #[serde(default)]
+        all: bool,
+    },
+    /// `show status`
+    ShowStatus,
+    /// `show memory`
+    ShowMemory,
+}
+
+/// Filters allowed in `show route where`. Each of them matches a bounded set
+/// of routes, unlike arbitrary filter expressions.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RouteCondition {
+    /// Routes originated by an AS: `bgp_path.last = <asn>`.
+    OriginAsn { asn: u32 },
+}
+
+impl BirdCommand {
+    /// Checks the arguments, which end up verbatim in the command line.
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            BirdCommand::ShowProtocolsAll { name } => validate_symbol(name),
+            BirdCommand::ShowRoute { prefix, .. } => validate_prefix(prefix),
+            BirdCommand::ShowProtocols
+            | BirdCommand::ShowRouteWhere { .. }
+            | BirdCommand::ShowStatus
+            | BirdCommand::ShowMemory => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for BirdCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let all = |all: bool| if all { " all" } else { "" };
+        match self {
+            BirdCommand::ShowProtocols => write!(f, "show protocols"),
+            BirdCommand::ShowProtocolsAll { name } => write!(f, "show protocols all {}", name),
+            BirdCommand::ShowRoute { prefix, all: a } => {
+                write!(f, "show route for {}{}", prefix, all(*a))
+            }
+            BirdCommand::ShowRouteWhere {
+                condition: RouteCondition::OriginAsn { asn },
+                all: a,
+            } => write!(f, "show route where bgp_path.last = {}{}", asn, all(*a)),
+            BirdCommand::ShowStatus => write!(f, "show status"),
+            BirdCommand::ShowMemory => write!(f, "show memory"),
+        }
+    }
+}
+
+/// Parses the text form of an allowed command, as written by [`Display`].
+///
+/// [`Display`]: fmt::Display
+impl FromStr for BirdCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        // A trailing `all` only belongs to commands with arguments.
+        let (words, all) = match words.split_last() {
+            Some((&"all", rest)) if rest.len() > 2 => (rest, true),
+            _ => (words.as_slice(), false),
+        };
+
+        let command = match words {
+            ["show", "protocols"] => BirdCommand::ShowProtocols,
+            ["show", "protocols", "all", name] if !all => BirdCommand::ShowProtocolsAll {
+                name: name.to_string(),
+            },
+            ["show", "route", "for", prefix] => BirdCommand::ShowRoute {
+                prefix: prefix.to_string(),
+                all,
+            },
+            ["show", "route", "where", "bgp_path.last", "=", asn] => BirdCommand::ShowRouteWhere {
+                condition: RouteCondition::OriginAsn {
+                    asn: asn.parse().map_err(|_| format!("Invalid ASN '{}'", asn))?,
+                },
+                all,
+            },
+            ["show", "status"] => BirdCommand::ShowStatus,
+            ["show", "memory"] => BirdCommand::ShowMemory,
+            _ => return Err(format!("Command not allowed: {}", s.trim())),
+        };
+        command.validate()?;
+        Ok(command)
+    }
+}
+
+/// Starts the last line of a `/bird` body when BIRD failed after output had
+/// been sent and the status code could no longer say so. The JSON
+/// [`BirdError`] follows, as in a JSON text sequence (RFC 7464); BIRD output
+/// never contains the separator.
+const TRAILER_SEPARATOR: char = '\u{1e}';
+
+/// An error reply from BIRD: reply codes 8xxx for runtime errors and 9xxx for
+/// commands it couldn't parse.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct BirdError {
+    pub code: u16,
+    pub message: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BirdErrorKind {
+    /// No route or protocol matched (`8001`, `8003`).
+    NotFound,
+    /// The restricted session doesn't allow the command (`8007`).
+    AccessDenied,
+    /// BIRD couldn't parse the command (`9xxx`).
+    InvalidCommand,
+    /// Any other runtime error.
+    Failed,
+}
+
+impl BirdError {
+    /// Whether a reply code denotes an error.
+    pub fn is_error_code(code: u16) -> bool {
+        (8000..10000).contains(&code)
+    }
+
+    /// The line ending a reply whose output was already sent.
+    pub fn trailer(&self) -> String {
+        format!(
+            "{}{}\n",
+            TRAILER_SEPARATOR,
+            serde_json::to_string(self).unwrap_or_default()
+        )
+    }
+
+    /// The error of a line written by [`BirdError::trailer`], without its
+    /// newline.
+    pub fn from_trailer(line: &str) -> Option<Self> {
+        serde_json::from_str(line.strip_prefix(TRAILER_SEPARATOR)?).ok()
+    }
+
+    pub fn kind(&self) -> BirdErrorKind {
+        match self.code {
+            8001 | 8003 => BirdErrorKind::NotFound,
+            8007 => BirdErrorKind::AccessDenied,
+            9000..=9999 => BirdErrorKind::InvalidCommand,
+            _ => BirdErrorKind::Failed,
+        }
+    }
+}
+
+impl fmt::Display for BirdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (BIRD {})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for BirdError {}
+
+/// BIRD symbols, which protocol names are: a letter or `_`, then letters,
+/// digits and `_`.
+fn validate_symbol(name: &str) -> Result<(), String> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid protocol name '{}'", name))
+    }
+}
+
+/// An address or a prefix in CIDR notation.
+fn validate_prefix(prefix: &str) -> Result<(), String> {
+    let (addr, len) = match prefix.split_once('/') {
+        Some((addr, len)) => (addr, Some(len)),
+        None => (prefix, None),
+    };
+    let max_len = match addr.parse::<IpAddr>() {
+        Ok(IpAddr::V4(_)) => 32,
+        Ok(IpAddr::V6(_)) => 128,
+        Err(_) => return Err(format!("Invalid prefix '{}'", prefix)),
+    };
+    match len.map(str::parse::<u8>) {
+        None => Ok(()),
+        Some(Ok(len)) if len <= max_len => Ok(()),
+        Some(_) => Err(format!("Invalid prefix length in '{}'", prefix)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<BirdCommand, String> {
+        s.parse()
+    }
+
+    #[test]
+    fn round_trips() {
+        for command in [
+            "show protocols",
+            "show protocols all dn42_peer1",
+            "show route for 172.20.0.0/14",
+            "show route for fd00::1 all",
+            "show route where bgp_path.last = 4242420000",
+            "show route where bgp_path.last = 4242420000 all",
+            "show status",
+            "show memory",
+        ] {
+            assert_eq!(parse(command).unwrap().to_string(), command);
+        }
+        // Whitespace is normalised, so the command sent to BIRD is one line.
+        assert_eq!(
+            parse("  show\troute   for 10.0.0.0/8\nall ")
+                .unwrap()
+                .to_string(),
+            "show route for 10.0.0.0/8 all"
+        );
+    }
+
+    #[test]
+    fn trailing_all() {
+        assert_eq!(
+            parse("show route for 10.0.0.1 all").unwrap(),
+            BirdCommand::ShowRoute {
+                prefix: "10.0.0.1".to_string(),
+                all: true,
+            }
+        );
+        assert!(parse("show protocols all").is_err());
+        assert!(parse("show protocols all dn42_peer1 all").is_err());
+        assert!(parse("show status all").is_err());
+        assert!(parse("show route all").is_err());
+    }
+
+    #[test]
+    fn rejects_injection() {
+        for command in [
+            "show route for 10.0.0.1; show route",
+            "show route for 10.0.0.1 ; show route",
+            "show route for 10.0.0.1;",
+            "show status\nshow route",
+            "show protocols all peer;show",
+            "show route where bgp_path.last = 4242420000 || net ~ 0.0.0.0/0",
+            "show route where bgp_path.last = 4242420000; show route",
+            "show route where net ~ 0.0.0.0/0",
+            "show route where bgp_path.last = AS4242420000",
+            "show route all",
+            "show route export peer1",
+            "configure",
+            "",
+        ] {
+            assert!(parse(command).is_err(), "accepted {:?}", command);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_prefixes() {
+        for prefix in [
+            "300.1.1.1",
+            "10.0.0.0/33",
+            "fd00::/129",
+            "10.0.0.0/-1",
+            "10.0.0.0/",
+            "fd00::1%wg0",
+            "foo",
+        ] {
+            assert!(
+                parse(&format!("show route for {}", prefix)).is_err(),
+                "accepted {}",
+                prefix
+            );
+        }
+        assert!(parse("show route for 10.0.0.0/32").is_ok());
+        assert!(parse("show route for fd00::/128").is_ok());
+    }
+
+    #[test]
+    fn validates_json_commands() {
+        let command: BirdCommand =
+            serde_json::from_str(r#"{"command":"show_protocols_all","name":"peer1; show"}"#)
+                .unwrap();
+        assert!(command.validate().is_err());
+        let command: BirdCommand =
+            serde_json::from_str(r#"{"command":"show_route","prefix":"10.0.0.0/33"}"#).unwrap();
+        assert!(command.validate().is_err());
+        let command: BirdCommand = serde_json::from_str(
+            r#"{"command":"show_route_where","condition":{"type":"origin_asn","asn":4242420000},"all":true}"#,
+        )
+        .unwrap();
+        assert!(command.validate().is_ok());
+        assert!(serde_json::from_str::<BirdCommand>(r#"{"command":"configure"}"#).is_err());
+        assert!(
+            serde_json::from_str::<BirdCommand>(
+                r#"{"command":"show_route_where","condition":{"type":"filter","expr":"true"}}"#
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn symbols() {
+        assert!(validate_symbol("dn42_peer1").is_ok());
+        assert!(validate_symbol("_peer").is_ok());
+        for name in ["", "1peer", "peer-1", "peer.1", "\"peer\"", "peer*"] {
+            assert!(validate_symbol(name).is_err(), "accepted {:?}", name);
+        }
+    }
+
+    #[test]
+    fn error_kinds() {
+        let kind = |code| {
+            BirdError {
+                code,
+                message: String::new(),
+            }
+            .kind()
+        };
+        assert_eq!(kind(8001), BirdErrorKind::NotFound);
+        assert_eq!(kind(8003), BirdErrorKind::NotFound);
+        assert_eq!(kind(8007), BirdErrorKind::AccessDenied);
+        assert_eq!(kind(9001), BirdErrorKind::InvalidCommand);
+        assert_eq!(kind(8000), BirdErrorKind::Failed);
+        assert!(BirdError::is_error_code(8000));
+        assert!(BirdError::is_error_code(9999));
+        assert!(!BirdError::is_error_code(1002));
+        assert!(!BirdError::is_error_code(0));
+    }
+
+    #[test]
+    fn error_trailers() {
+        let error = BirdError {
+            code: 8001,
+            message: "Network not found".to_string(),
+        };
+        let trailer = error.trailer();
+        assert!(trailer.ends_with('\n'));
+        assert_eq!(trailer.lines().count(), 1);
+        assert_eq!(
+            BirdError::from_trailer(trailer.trim_end()),
+            Some(error.clone())
+        );
+
+        // Output that merely looks like an error isn't one.
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(BirdError::from_trailer(&json), None);
+        assert_eq!(
+            BirdError::from_trailer("Network not found (BIRD 8001)"),
+            None
+        );
+        assert_eq!(BirdError::from_trailer("\u{1e}not json"), None);
+    }
+}
@@ -0,0 +1,494 @@
+use std::collections::HashMap;
+
+use crate::models::{DiffOp, NodeWireGuard, NodeWireGuardDiff, Protocol};
+
+/// Computes the ops that turn `old` into `new`. Protocols are matched by
+/// name: unchanged leading and trailing runs are matched directly, and in
+/// between every name that occurs exactly once on both sides is paired up,
+/// keeping the longest set of pairs that is in the same order on both sides
+/// (patience diff). Matched protocols become `Equal` or `Replace`, everything
+/// else `Delete` or `Insert`. This is O((m + n) log n) instead of building an
+/// m·n table, which matters for route servers with thousands of sessions.
+pub fn calculate_diff(old: &[Protocol], new: &[Protocol]) -> Vec<DiffOp> {
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(a, b)| a.name == b.name)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a.name == b.name)
+        .count();
+
+    let mut path: Vec<(usize, usize)> = (0..prefix).map(|i| (i, i)).collect();
+    path.extend(
+        match_unique(
+            &old[prefix..old.len() - suffix],
+            &new[prefix..new.len() - suffix],
+        )
+        .into_iter()
+        .map(|(i, j)| (i + prefix, j + prefix)),
+    );
+    path.extend((0..suffix).map(|k| (old.len() - suffix + k, new.len() - suffix + k)));
+
+    let mut diff = Vec::new();
+
+    let mut old_idx = 0;
+    let mut new_idx = 0;
+
+    for (match_old, match_new) in path {
+        if match_old > old_idx {
+            diff.push(DiffOp::Delete {
+                c: match_old - old_idx,
+            });
+        }
+
+        if match_new > new_idx {
+            diff.push(DiffOp::Insert {
+                i: new[new_idx..match_new].to_vec(),
+            });
+        }
+
+        if old[match_old] == new[match_new] {
+            if let Some(DiffOp::Equal { c: count }) = diff.last_mut() {
+                *count += 1;
+            } else {
+                diff.push(DiffOp::Equal { c: 1 });
+            }
+        } else {
+            diff.push(DiffOp::Replace {
+                i: vec![new[match_new].clone()],
+            });
+        }
+
+        old_idx = match_old + 1;
+        new_idx = match_new + 1;
+    }
+
+    if old_idx < old.len() {
+        diff.push(DiffOp::Delete {
+            c: old.len() - old_idx,
+        });
+    }
+
+    if new_idx < new.len() {
+        diff.push(DiffOp::Insert {
+            i: new[new_idx..].to_vec(),
+        });
+    }
+
+    opt_fold(diff)
+}
+
+/// Pairs up the names that occur exactly once in both `old` and `new` and
+/// returns the longest subsequence of pairs whose indices increase on both
+/// sides.
+fn match_unique(old: &[Protocol], new: &[Protocol]) -> Vec<(usize, usize)> {
+    // Name -> (occurrences in old, occurrences in new, index in old).
+    let mut names: HashMap<&str, (usize, usize, usize)> = HashMap::with_capacity(old.len());
+    for (i, protocol) in old.iter().enumerate() {
+        names.entry(protocol.name.as_str()).or_insert((0, 0, i)).0 += 1;
+    }
+    for protocol in new {
+        if let Some(entry) = names.get_mut(protocol.name.as_str()) {
+            entry.1 += 1;
+        }
+    }
+
+    let pairs: Vec<(usize, usize)> = new
+        .iter()
+        .enumerate()
+        .filter_map(|(j, protocol)| match names.get(protocol.name.as_str()) {
+            Some(&(1, 1, i)) => Some((i, j)),
+            _ => None,
+        })
+        .collect();
+
+    longest_increasing(&pairs)
+}
+
+/// Longest subsequence of `pairs` (ordered by their second index) whose first
+/// indices increase, found by patience sorting in O(k log k).
+fn longest_increasing(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // tails[l] is the pair ending the best known subsequence of length l + 1.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = vec![None; pairs.len()];
+
+    for (k, &(i, _)) in pairs.iter().enumerate() {
+        let len = tails.partition_point(|&t| pairs[t].0 < i);
+        previous[k] = len.checked_sub(1).map(|l| tails[l]);
+        if len == tails.len() {
+            tails.push(k);
+        } else {
+            tails[len] = k;
+        }
+    }
+
+    let mut result = Vec::with_capacity(tails.len());
+    let mut current = tails.last().copied();
+    while let Some(k) = current {
+        result.push(pairs[k]);
+        current = previous[k];
+    }
+    result.reverse();
+    result
+}
+
+fn opt_fold(diff: Vec<DiffOp>) -> Vec<DiffOp> {
+    let mut result = Vec::new();
+
+    for op in diff {
+        match op {
+            DiffOp::Replace { i: items } => {
+                if let Some(DiffOp::Replace { i: last }) = result.last_mut() {
+                    last.extend(items);
+                } else {
+                    result.push(DiffOp::Replace { i: items });
+                }
+            }
+            DiffOp::Insert { i: items } => {
+                if let Some(DiffOp::Insert { i: last }) = result.last_mut() {
+                    last.extend(items);
+                } else {
+                    result.push(DiffOp::Insert { i: items });
+                }
+            }
+            DiffOp::Delete { c: count } => {
+                if let Some(DiffOp::Delete { c: last_count }) = result.last_mut() {
+                    *last_count += count;
+                } else {
+                    result.push(DiffOp::Delete { c: count });
+                }
+            }
+            DiffOp::Equal { c: count } => {
+                if let Some(DiffOp::Equal { c: last_count }) = result.last_mut() {
+                    *last_count += count;
+                } else {
+                    result.push(DiffOp::Equal { c: count });
+                }
+            }
+        }
+    }
+
+    result
+}
+
+/// Interfaces of `new` that were added or changed and the names of
+/// interfaces that disappeared, compared with `old` by interface name.
+pub fn calculate_wireguard_diff(
+    old: Option<&NodeWireGuard>,
+    new: &NodeWireGuard,
+) -> NodeWireGuardDiff {
+    let old_interfaces = old.map(|o| o.interfaces.as_slice()).unwrap_or_default();
+    let previous: HashMap<&str, _> = old_interfaces
+        .iter()
+        .map(|i| (i.name.as_str(), i))
+        .collect();
+    let current: HashMap<&str, _> = new
+        .interfaces
+        .iter()
+        .map(|i| (i.name.as_str(), i))
+        .collect();
+
+    NodeWireGuardDiff {
+        n: new.name.clone(),
+        i: new
+            .interfaces
+            .iter()
+            .filter(|iface| previous.get(iface.name.as_str()) != Some(iface))
+            .cloned()
+            .collect(),
+        r: old_interfaces
+            .iter()
+            .filter(|iface| !current.contains_key(iface.name.as_str()))
+            .map(|iface| iface.name.clone())
+            .collect(),
+        u: new.last_updated,
+        e: new.error.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::models::{WireGuardInterface, WireGuardPeer};
+
+    /// The LCS diff this module replaced, kept as a reference.
+    fn lcs_diff(old: &[Protocol], new: &[Protocol]) -> Vec<DiffOp> {
+        let (m, n) = (old.len(), new.len());
+        let mut table = vec![vec![0; n + 1]; m + 1];
+        for i in 1..=m {
+            for j in 1..=n {
+                table[i][j] = if old[i - 1].name == new[j - 1].name {
+                    table[i - 1][j - 1] + 1
+                } else {
+                    table[i - 1][j].max(table[i][j - 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (m, n);
+        let mut path = Vec::new();
+        while i > 0 && j > 0 {
+            if old[i - 1].name == new[j - 1].name {
+                path.push((i - 1, j - 1));
+                i -= 1;
+                j -= 1;
+            } else if table[i - 1][j] > table[i][j - 1] {
+                i -= 1;
+            } else {
+                j -= 1;
+            }
+        }
+        path.reverse();
+
+        let mut diff = Vec::new();
+        let (mut old_idx, mut new_idx) = (0, 0);
+        for (match_old, match_new) in path {
+            if match_old > old_idx {
+                diff.push(DiffOp::Delete {
+                    c: match_old - old_idx,
+                });
+            }
+            if match_new > new_idx {
+                diff.push(DiffOp::Insert {
+                    i: new[new_idx..match_new].to_vec(),
+                });
+            }
+            if old[match_old] == new[match_new] {
+                diff.push(DiffOp::Equal { c: 1 });
+            } else {
+                diff.push(DiffOp::Replace {
+                    i: vec![new[match_new].clone()],
+                });
+            }
+            old_idx = match_old + 1;
+            new_idx = match_new + 1;
+        }
+        if old_idx < m {
+            diff.push(DiffOp::Delete { c: m - old_idx });
+        }
+        if new_idx < n {
+            diff.push(DiffOp::Insert {
+                i: new[new_idx..].to_vec(),
+            });
+        }
+        opt_fold(diff)
+    }
+
+    /// Applies `diff` to `old` the way clients do.
+    fn apply(old: &[Protocol], diff: &[DiffOp]) -> Vec<Protocol> {
+        let mut result = Vec::new();
+        let mut idx = 0;
+        for op in diff {
+            match op {
+                DiffOp::Equal { c } => {
+                    result.extend_from_slice(&old[idx..idx + c]);
+                    idx += c;
+                }
+                DiffOp::Delete { c } => idx += c,
+                DiffOp::Insert { i } => result.extend_from_slice(i),
+                DiffOp::Replace { i } => {
+                    result.extend_from_slice(i);
+                    idx += i.len();
+                }
+            }
+        }
+        result.extend_from_slice(&old[idx..]);
+        result
+    }
+
+    fn protocol(name: &str, state: &str) -> Protocol {
+        Protocol {
+            name: name.to_string(),
+            proto: "BGP".to_string(),
+            table: "---".to_string(),
+            state: state.to_string(),
+            since: "12:00:00".to_string(),
+            info: String::new(),
+            reason: None,
+        }
+    }
+
+    fn protocols(names: &[&str]) -> Vec<Protocol> {
+        names.iter().map(|n| protocol(n, "up")).collect()
+    }
+
+    /// A small deterministic generator, so failures can be reproduced.
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((self.0 >> 33) as usize) % n
+        }
+    }
+
+    #[test]
+    fn examples() {
+        let old = protocols(&["a", "b", "c", "d"]);
+        assert_eq!(calculate_diff(&old, &old), [DiffOp::Equal { c: 4 }]);
+        assert_eq!(
+            calculate_diff(&[], &old),
+            [DiffOp::Insert { i: old.clone() }]
+        );
+        assert_eq!(calculate_diff(&old, &[]), [DiffOp::Delete { c: 4 }]);
+
+        let mut new = protocols(&["a", "c", "x", "d"]);
+        new[1].state = "start".to_string();
+        assert_eq!(
+            calculate_diff(&old, &new),
+            [
+                DiffOp::Equal { c: 1 },
+                DiffOp::Delete { c: 1 },
+                DiffOp::Replace {
+                    i: vec![new[1].clone()]
+                },
+                DiffOp::Insert {
+                    i: vec![new[2].clone()]
+                },
+                DiffOp::Equal { c: 1 },
+            ]
+        );
+    }
+
+    /// With unique names in a stable order, which is what BIRD prints, both
+    /// algorithms find the same matching.
+    #[test]
+    fn matches_lcs_on_ordered_changes() {
+        let mut rng = Lcg(42);
+        for round in 0..500 {
+            let len = rng.below(60);
+            let old: Vec<Protocol> = (0..len)
+                .map(|i| protocol(&format!("p{:03}", i * 2), "up"))
+                .collect();
+
+            let mut new = Vec::new();
+            for (i, p) in old.iter().enumerate() {
+                match rng.below(10) {
+                    0 => {}
+                    1 => new.push(protocol(&p.name, "start")),
+                    2 => {
+                        new.push(p.clone());
+                        new.push(protocol(&format!("p{:03}", i * 2 + 1), "up"));
+                    }
+                    _ => new.push(p.clone()),
+                }
+            }
+
+            let diff = calculate_diff(&old, &new);
+            assert_eq!(diff, lcs_diff(&old, &new), "round {}", round);
+            assert_eq!(apply(&old, &diff), new, "round {}", round);
+        }
+    }
+
+    /// Reordered and duplicate names may be matched differently from LCS,
+    /// but the diff must still turn `old` into `new`.
+    #[test]
+    fn applies_on_reordered_and_duplicate_names() {
+        let mut rng = Lcg(7);
+        for round in 0..500 {
+            let names = ["a", "b", "c", "d", "e", "f"];
+            let list = |rng: &mut Lcg| -> Vec<Protocol> {
+                (0..rng.below(12))
+                    .map(|_| protocol(names[rng.below(names.len())], ["up", "start"][rng.below(2)]))
+                    .collect()
+            };
+            let old = list(&mut rng);
+            let new = list(&mut rng);
+
+            let diff = calculate_diff(&old, &new);
+            assert_eq!(apply(&old, &diff), new, "round {}", round);
+            assert_eq!(apply(&old, &lcs_diff(&old, &new)), new, "round {}", round);
+        }
+    }
+
+    fn interface(name: &str, rx_bytes: u64) -> WireGuardInterface {
+        WireGuardInterface {
+            name: name.to_string(),
+            public_key: None,
+            listen_port: Some(51820),
+            peers: vec![WireGuardPeer {
+                public_key: None,
+                endpoint: None,
+                allowed_ips: Vec::new(),
+                latest_handshake: Some(1700000000),
+                rx_bytes,
+                tx_bytes: 0,
+                persistent_keepalive: None,
+            }],
+            protocols: Vec::new(),
+        }
+    }
+
+    fn wireguard(interfaces: Vec<WireGuardInterface>) -> NodeWireGuard {
+        NodeWireGuard {
+            name: "node1".to_string(),
+            interfaces,
+            last_updated: Utc::now(),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn wireguard_changes() {
+        let old = wireguard(vec![
+            interface("wg0", 1),
+            interface("wg1", 1),
+            interface("wg2", 1),
+        ]);
+        let new = wireguard(vec![
+            interface("wg0", 1),
+            interface("wg1", 2),
+            interface("wg3", 1),
+        ]);
+
+        let diff = calculate_wireguard_diff(Some(&old), &new);
+        assert_eq!(diff.n, "node1");
+        assert_eq!(diff.i, [interface("wg1", 2), interface("wg3", 1)]);
+        assert_eq!(diff.r, ["wg2"]);
+
+        let mut applied = old.clone();
+        diff.apply(&mut applied);
+        assert_eq!(applied, new);
+    }
+
+    #[test]
+    fn wireguard_unchanged_and_first_update() {
+        let node = wireguard(vec![interface("wg0", 1), interface("wg1", 1)]);
+        assert!(calculate_wireguard_diff(Some(&node), &node).is_empty());
+
+        // Without a previous update every interface is new.
+        let diff = calculate_wireguard_diff(None, &node);
+        assert_eq!(diff.i, node.interfaces);
+        assert!(diff.r.is_empty());
+
+        // Only the order differs, which `apply` restores.
+        let reordered = wireguard(vec![interface("wg1", 1), interface("wg0", 1)]);
+        assert!(calculate_wireguard_diff(Some(&node), &reordered).is_empty());
+    }
+
+    #[test]
+    fn wireguard_error_is_carried() {
+        let old = wireguard(vec![interface("wg0", 1)]);
+        let new = NodeWireGuard {
+            error: Some("Node is not reachable".to_string()),
+            ..old.clone()
+        };
+
+        let diff = calculate_wireguard_diff(Some(&old), &new);
+        assert!(diff.is_empty());
+        assert_eq!(diff.e.as_deref(), Some("Node is not reachable"));
+        let mut applied = old.clone();
+        diff.apply(&mut applied);
+        assert_eq!(applied.error, new.error);
+        assert_eq!(applied.interfaces, old.interfaces);
+    }
+}
@@ -0,0 +1,77 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::Protocol;
+
+/// Windows, in seconds, that flaps are counted over.
+pub const FLAP_WINDOWS: [i64; 3] = [3600, 6 * 3600, 24 * 3600];
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct StateTransition {
+    pub timestamp: DateTime<Utc>,
+    /// `None` for the first time a protocol was seen.
+    pub from: Option<String>,
+    pub to: String,
+    pub reason: Option<String>,
+}
+
+impl StateTransition {
+    /// A flap is a protocol dropping out of the `up` state.
+    pub fn is_flap(&self) -> bool {
+        self.from.as_deref().is_some_and(is_up) && !is_up(&self.to)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct FlapCount {
+    pub window_secs: i64,
+    pub flaps: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ProtocolHistory {
+    pub node: String,
+    pub protocol: String,
+    /// Oldest first.
+    pub transitions: Vec<StateTransition>,
+    pub flaps: Vec<FlapCount>,
+}
+
+impl ProtocolHistory {
+    pub fn new(node: String, protocol: String, transitions: Vec<StateTransition>) -> Self {
+        let now = Utc::now();
+        let flaps = FLAP_WINDOWS
+            .iter()
+            .map(|&window_secs| FlapCount {
+                window_secs,
+                flaps: count_flaps(&transitions, now, window_secs),
+            })
+            .collect();
+
+        Self {
+            node,
+            protocol,
+            transitions,
+            flaps,
+        }
+    }
+}
+
+/// The state a transition is tracked on. Only the protocol state counts: the
+/// info of a down BGP session keeps alternating between `Active` and
+/// `Connect`, which would flood the history.
+pub fn protocol_status(protocol: &Protocol) -> String {
+    protocol.state.clone()
+}
+
+pub fn count_flaps(transitions: &[StateTransition], now: DateTime<Utc>, window_secs: i64) -> usize {
+    let since = now - Duration::seconds(window_secs);
+    transitions
+        .iter()
+        .filter(|t| t.timestamp >= since && t.is_flap())
+        .count()
+}
+
+fn is_up(status: &str) -> bool {
+    status == "up"
+}
@@ -1,7 +1,13 @@
 pub mod api;
 pub mod auto_peer;
+pub mod command;
+pub mod diff;
+pub mod history;
 pub mod humanize;
 pub mod models;
+pub mod protocol;
+pub mod registry;
+pub mod route;
 pub mod traceroute;
 pub mod utils;
 pub mod wireguard;
@@ -11,6 +11,9 @@ pub struct Protocol {
     pub state: String,
     pub since: String,
     pub info: String,
+    /// Explanation following the state in `info`, e.g. the last error.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -54,23 +57,99 @@ pub struct NetworkInfo {
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
-pub struct WireGuardPeer {
+pub struct WireGuardInterface {
     pub name: String,
-    // pub public_key: String,
-    // pub endpoint: Option<String>,
-    pub latest_handshake: Option<String>,
-    pub transfer_rx: String,
-    pub transfer_tx: String,
+    pub public_key: Option<String>,
+    pub listen_port: Option<u16>,
+    pub peers: Vec<WireGuardPeer>,
+    /// BGP protocols whose sessions run over this interface.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub protocols: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct WireGuardPeer {
+    pub public_key: Option<String>,
+    pub endpoint: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_ips: Vec<String>,
+    /// Unix time of the latest handshake, `None` if there never was one.
+    pub latest_handshake: Option<i64>,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    /// Keepalive interval in seconds, `None` when disabled.
+    pub persistent_keepalive: Option<u32>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct NodeWireGuard {
     pub name: String,
-    pub peers: Vec<WireGuardPeer>,
+    pub interfaces: Vec<WireGuardInterface>,
     pub last_updated: DateTime<Utc>,
     pub error: Option<String>,
 }
 
+/// WireGuard interfaces of a node that changed since the previous update,
+/// keyed by interface name.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct NodeWireGuardDiff {
+    pub n: String,
+    /// Added or changed interfaces.
+    pub i: Vec<WireGuardInterface>,
+    /// Names of removed interfaces.
+    pub r: Vec<String>,
+    pub u: DateTime<Utc>,
+    pub e: Option<String>,
+}
+
+impl NodeWireGuardDiff {
+    pub fn is_empty(&self) -> bool {
+        self.i.is_empty() && self.r.is_empty()
+    }
+
+    /// Applies the diff to the node's interfaces, keeping them sorted by name.
+    pub fn apply(self, node: &mut NodeWireGuard) {
+        node.error = self.e;
+        node.last_updated = self.u;
+        node.interfaces
+            .retain(|iface| !self.r.contains(&iface.name));
+        for iface in self.i {
+            match node.interfaces.iter_mut().find(|i| i.name == iface.name) {
+                Some(existing) => *existing = iface,
+                None => node.interfaces.push(iface),
+            }
+        }
+        node.interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+}
+
+/// The WireGuard interface a BGP protocol runs over, with the handshake of
+/// its most recently active peer.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ProtocolTunnel {
+    pub protocol: String,
+    pub interface: String,
+    pub endpoint: Option<String>,
+    pub latest_handshake: Option<i64>,
+}
+
+impl ProtocolTunnel {
+    /// WireGuard drops a session 180 seconds after its last handshake, so an
+    /// older handshake means the tunnel itself is down.
+    pub const HANDSHAKE_TIMEOUT: i64 = 180;
+
+    pub fn is_up(&self, now: i64) -> bool {
+        self.latest_handshake
+            .is_some_and(|handshake| now - handshake <= Self::HANDSHAKE_TIMEOUT)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct NodeTunnels {
+    pub name: String,
+    pub tunnels: Vec<ProtocolTunnel>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "o")]
 pub enum DiffOp {
@@ -0,0 +1,512 @@
+use std::ops::AddAssign;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{models::Protocol, utils::strip_continuation};
+
+/// Parsed `show protocols all <name>` output for a single protocol.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ProtocolDetails {
+    pub summary: Protocol,
+    pub description: Option<String>,
+    pub bgp: Option<BgpSession>,
+    pub channels: Vec<ProtocolChannel>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct BgpSession {
+    pub state: String,
+    pub neighbor_address: Option<String>,
+    pub neighbor_as: Option<u32>,
+    pub local_as: Option<u32>,
+    pub neighbor_id: Option<String>,
+    pub session: Option<String>,
+    pub source_address: Option<String>,
+    pub hold_timer: Option<BgpTimer>,
+    pub keepalive_timer: Option<BgpTimer>,
+}
+
+/// A `remaining/configured` timer such as `Hold timer: 172.341/240`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct BgpTimer {
+    pub remaining: Option<f64>,
+    pub configured: Option<u32>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct ProtocolChannel {
+    pub name: String,
+    pub state: Option<String>,
+    pub table: Option<String>,
+    pub preference: Option<u32>,
+    pub input_filter: Option<String>,
+    pub output_filter: Option<String>,
+    pub routes: RouteStats,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct RouteStats {
+    pub imported: u32,
+    pub filtered: u32,
+    pub exported: u32,
+    pub preferred: u32,
+}
+
+impl AddAssign for RouteStats {
+    fn add_assign(&mut self, other: Self) {
+        self.imported += other.imported;
+        self.filtered += other.filtered;
+        self.exported += other.exported;
+        self.preferred += other.preferred;
+    }
+}
+
+impl ProtocolDetails {
+    /// Route counts summed over all channels.
+    pub fn route_stats(&self) -> RouteStats {
+        let mut total = RouteStats::default();
+        for channel in &self.channels {
+            total += channel.routes;
+        }
+        total
+    }
+}
+
+/// Parses `show protocols all` output. Each unindented line (other than the
+/// column header) starts a new protocol; the indented `Key: value` lines
+/// below it fill in the BGP session and channel details.
+pub fn parse_protocol_details(lines: &[String]) -> Vec<ProtocolDetails> {
+    let mut protocols: Vec<ProtocolDetails> = Vec::new();
+    let mut columns = ProtocolColumns::default();
+    let mut in_channel = false;
+
+    for line in lines {
+        let line = strip_continuation(line);
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        if !line.starts_with(char::is_whitespace) {
+            if let Some(header) = ProtocolColumns::from_header(line) {
+                columns = header;
+                continue;
+            }
+            if let Some(summary) = parse_protocol_line(line, columns) {
+                protocols.push(ProtocolDetails {
+                    summary,
+                    description: None,
+                    bgp: None,
+                    channels: Vec::new(),
+                    last_error: None,
+                });
+                in_channel = false;
+            }
+            continue;
+        }
+
+        let Some(protocol) = protocols.last_mut() else {
+            continue;
+        };
+
+        if let Some(name) = trimmed.strip_prefix("Channel ") {
+            protocol.channels.push(ProtocolChannel {
+                name: name.to_string(),
+                ..Default::default()
+            });
+            in_channel = true;
+            continue;
+        }
+
+        let Some((key, value)) = trimmed.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+
+        match key {
+            "Description" => protocol.description = Some(value.to_string()),
+            "Last error" => protocol.last_error = Some(value.to_string()),
+            "BGP state" => {
+                protocol.bgp = Some(BgpSession {
+                    state: value.to_string(),
+                    ..Default::default()
+                });
+                in_channel = false;
+            }
+            _ if in_channel => {
+                if let Some(channel) = protocol.channels.last_mut() {
+                    apply_channel_field(channel, key, value);
+                }
+            }
+            _ => {
+                if let Some(bgp) = protocol.bgp.as_mut() {
+                    apply_bgp_field(bgp, key, value);
+                }
+            }
+        }
+    }
+
+    protocols
+}
+
+/// Column offsets taken from the `Name Proto Table State Since Info` header
+/// that BIRD prints above protocol listings.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ProtocolColumns {
+    info: Option<usize>,
+}
+
+impl ProtocolColumns {
+    /// Returns the columns if `line` is the protocol table header.
+    pub fn from_header(line: &str) -> Option<Self> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words != ["Name", "Proto", "Table", "State", "Since", "Info"] {
+            return None;
+        }
+        Some(Self {
+            info: line.find("Info"),
+        })
+    }
+}
+
+/// Parses one row of `show protocols`. Name, protocol, table and state are
+/// always single words. `Since` is one word for most BIRD time formats but
+/// two for `iso long` (`2025-01-01 12:00:00`), so a second word is taken
+/// into it when it starts before the `Info` column or looks like a time of
+/// day. Everything after that is the info string, kept whole; whatever
+/// follows its first word (e.g. `Socket: Connection refused`) becomes the
+/// reason.
+pub fn parse_protocol_line(line: &str, columns: ProtocolColumns) -> Option<Protocol> {
+    let line = strip_continuation(line);
+    let words = words_with_offsets(line);
+    let [name, proto, table, state] = [0, 1, 2, 3].map(|i| words.get(i).map(|(_, w)| *w));
+    let (name, proto, table, state) = (name?, proto?, table?, state?);
+
+    let mut since = words.get(4).map(|(_, w)| w.to_string()).unwrap_or_default();
+    let mut next = 5;
+    if let Some(&(offset, word)) = words.get(5)
+        && (columns.info.is_some_and(|info| offset < info) || is_time_of_day(word))
+    {
+        since.push(' ');
+        since.push_str(word);
+        next = 6;
+    }
+
+    let rest: Vec<&str> = words.iter().skip(next).map(|(_, w)| *w).collect();
+    let info = rest.join(" ");
+    let reason =
+        Some(rest.iter().skip(1).copied().collect::<Vec<_>>().join(" ")).filter(|r| !r.is_empty());
+
+    Some(Protocol {
+        name: name.to_string(),
+        proto: proto.to_string(),
+        table: table.to_string(),
+        state: state.to_string(),
+        since,
+        info,
+        reason,
+    })
+}
+
+fn words_with_offsets(line: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in line.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                words.push((s, &line[s..i]));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        words.push((s, &line[s..]));
+    }
+    words
+}
+
+/// Matches `12:00:00` and `12:00:00.123`.
+fn is_time_of_day(word: &str) -> bool {
+    let time = word.split('.').next().unwrap_or(word);
+    let parts: Vec<&str> = time.split(':').collect();
+    (2..=3).contains(&parts.len())
+        && parts
+            .iter()
+            .all(|p| !p.is_empty() && p.bytes().all(|b| b.is_ascii_digit()))
+}
+
+fn apply_bgp_field(bgp: &mut BgpSession, key: &str, value: &str) {
+    match key {
+        "Neighbor address" => bgp.neighbor_address = Some(value.to_string()),
+        "Neighbor AS" => bgp.neighbor_as = value.parse().ok(),
+        "Local AS" => bgp.local_as = value.parse().ok(),
+        "Neighbor ID" => bgp.neighbor_id = Some(value.to_string()),
+        "Session" => bgp.session = Some(value.to_string()),
+        "Source address" => bgp.source_address = Some(value.to_string()),
+        "Hold timer" => bgp.hold_timer = parse_timer(value),
+        "Keepalive timer" => bgp.keepalive_timer = parse_timer(value),
+        _ => {}
+    }
+}
+
+fn apply_channel_field(channel: &mut ProtocolChannel, key: &str, value: &str) {
+    match key {
+        "State" => channel.state = Some(value.to_string()),
+        "Table" => channel.table = Some(value.to_string()),
+        "Preference" => channel.preference = value.parse().ok(),
+        "Input filter" => channel.input_filter = Some(value.to_string()),
+        "Output filter" => channel.output_filter = Some(value.to_string()),
+        "Routes" => channel.routes = parse_route_stats(value),
+        _ => {}
+    }
+}
+
+fn parse_timer(value: &str) -> Option<BgpTimer> {
+    let (remaining, configured) = value.split_once('/')?;
+    Some(BgpTimer {
+        remaining: remaining.trim().parse().ok(),
+        configured: configured.trim().parse().ok(),
+    })
+}
+
+/// Parses `10 imported, 2 filtered, 5 exported, 8 preferred`.
+fn parse_route_stats(value: &str) -> RouteStats {
+    let mut stats = RouteStats::default();
+    for part in value.split(',') {
+        let mut words = part.split_whitespace();
+        let (Some(count), Some(kind)) = (words.next(), words.next()) else {
+            continue;
+        };
+        let Ok(count) = count.parse() else {
+            continue;
+        };
+        match kind {
+            "imported" => stats.imported = count,
+            "filtered" => stats.filtered = count,
+            "exported" => stats.exported = count,
+            "preferred" => stats.preferred = count,
+            _ => {}
+        }
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `show protocols all` from BIRD 2.15 with `timeformat protocol iso long`,
+    /// as relayed by the proxy.
+    const PROTOCOLS_ALL: &str = "\
+Name       Proto      Table      State  Since         Info
+dn42_kioubit_v4 BGP        ---        up     2024-03-04 10:15:27  Established   
+  Description:    Kioubit
+  BGP state:          Established
+    Neighbor address: 172.20.53.97%dn42-kioubit
+    Neighbor AS:      4242423914
+    Local AS:         4242422601
+    Neighbor ID:      172.20.53.97
+    Local capabilities
+      Multiprotocol
+        AF announced: ipv4
+      Route refresh
+      4-octet AS numbers
+    Session:          external AS4
+    Source address:   172.20.0.81
+    Hold timer:       172.341/240
+    Keepalive timer:  41.123/80
+  Channel ipv4
+    State:          UP
+    Table:          master4
+    Preference:     100
+    Input filter:   dn42_import
+    Output filter:  dn42_export
+    Routes:         754 imported, 2 filtered, 1020 exported, 700 preferred
+    Route change stats:     received   rejected   filtered    ignored   accepted
+      Import updates:          12345          0         10        120      12215
+    BGP Next hop:   172.20.0.81
+  Channel ipv6
+    State:          UP
+    Table:          master6
+    Preference:     100
+    Input filter:   dn42_import
+    Output filter:  dn42_export
+    Routes:         600 imported, 0 filtered, 900 exported, 550 preferred
+
+dn42_lare_v4 BGP        ---        start  2024-03-05 08:01:12  Active        Socket: Connection refused
+  BGP state:          Active
+    Neighbor address: 172.20.0.82%dn42-lare
+    Neighbor AS:      4242423999
+    Local AS:         4242422601
+    Connect delay:    3.512/5
+    Last error:       Socket: Connection refused
+  Channel ipv4
+    State:          DOWN
+    Table:          master4
+    Preference:     100
+    Input filter:   dn42_import
+    Output filter:  dn42_export
+";
+
+    /// `show protocols` with the default `iso short` time format, where a
+    /// long name pushes the other columns to the right.
+    const PROTOCOLS: &str = "\
+Name       Proto      Table      State  Since         Info
+device1    Device     ---        up     12:00:03.281  
+dn42_lare_v4 BGP        ---        start  12:04:11.511  Connect       Socket: Connection refused
+dn42_foo   BGP        ---        up     2024-03-04    Established   ";
+
+    fn lines(output: &str) -> Vec<String> {
+        output.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn protocol_details() {
+        let protocols = parse_protocol_details(&lines(PROTOCOLS_ALL));
+        assert_eq!(protocols.len(), 2);
+
+        let up = &protocols[0];
+        assert_eq!(up.summary.name, "dn42_kioubit_v4");
+        assert_eq!(up.summary.state, "up");
+        assert_eq!(up.summary.since, "2024-03-04 10:15:27");
+        assert_eq!(up.summary.info, "Established");
+        assert_eq!(up.description.as_deref(), Some("Kioubit"));
+        assert_eq!(up.last_error, None);
+        assert_eq!(
+            up.bgp,
+            Some(BgpSession {
+                state: "Established".to_string(),
+                neighbor_address: Some("172.20.53.97%dn42-kioubit".to_string()),
+                neighbor_as: Some(4242423914),
+                local_as: Some(4242422601),
+                neighbor_id: Some("172.20.53.97".to_string()),
+                session: Some("external AS4".to_string()),
+                source_address: Some("172.20.0.81".to_string()),
+                hold_timer: Some(BgpTimer {
+                    remaining: Some(172.341),
+                    configured: Some(240),
+                }),
+                keepalive_timer: Some(BgpTimer {
+                    remaining: Some(41.123),
+                    configured: Some(80),
+                }),
+            })
+        );
+
+        assert_eq!(up.channels.len(), 2);
+        let ipv4 = &up.channels[0];
+        assert_eq!(ipv4.name, "ipv4");
+        assert_eq!(ipv4.state.as_deref(), Some("UP"));
+        assert_eq!(ipv4.table.as_deref(), Some("master4"));
+        assert_eq!(ipv4.preference, Some(100));
+        assert_eq!(ipv4.input_filter.as_deref(), Some("dn42_import"));
+        assert_eq!(ipv4.output_filter.as_deref(), Some("dn42_export"));
+        assert_eq!(
+            up.route_stats(),
+            RouteStats {
+                imported: 1354,
+                filtered: 2,
+                exported: 1920,
+                preferred: 1250,
+            }
+        );
+    }
+
+    #[test]
+    fn protocol_details_down() {
+        let protocols = parse_protocol_details(&lines(PROTOCOLS_ALL));
+        let down = &protocols[1];
+        assert_eq!(down.summary.state, "start");
+        assert_eq!(down.summary.info, "Active Socket: Connection refused");
+        assert_eq!(
+            down.summary.reason.as_deref(),
+            Some("Socket: Connection refused")
+        );
+        assert_eq!(
+            down.last_error.as_deref(),
+            Some("Socket: Connection refused")
+        );
+
+        let bgp = down.bgp.as_ref().unwrap();
+        assert_eq!(bgp.state, "Active");
+        assert_eq!(bgp.neighbor_as, Some(4242423999));
+        assert_eq!(bgp.hold_timer, None);
+        assert_eq!(down.channels.len(), 1);
+        assert_eq!(down.channels[0].state.as_deref(), Some("DOWN"));
+        assert_eq!(down.route_stats(), RouteStats::default());
+    }
+
+    fn parse_rows(output: &str) -> Vec<Protocol> {
+        let mut lines = output.lines();
+        let columns = ProtocolColumns::from_header(lines.next().unwrap()).unwrap();
+        lines
+            .map(|line| parse_protocol_line(line, columns).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn protocol_rows() {
+        let protocols = parse_rows(PROTOCOLS);
+        assert_eq!(protocols.len(), 3);
+
+        let device = &protocols[0];
+        assert_eq!(
+            (
+                device.name.as_str(),
+                device.proto.as_str(),
+                device.table.as_str()
+            ),
+            ("device1", "Device", "---")
+        );
+        assert_eq!(device.since, "12:00:03.281");
+        assert_eq!(device.info, "");
+        assert_eq!(device.reason, None);
+
+        let lare = &protocols[1];
+        assert_eq!(lare.name, "dn42_lare_v4");
+        assert_eq!(lare.state, "start");
+        assert_eq!(lare.since, "12:04:11.511");
+        assert_eq!(lare.info, "Connect Socket: Connection refused");
+        assert_eq!(lare.reason.as_deref(), Some("Socket: Connection refused"));
+
+        // A date on its own is followed by the info column, not a time.
+        let foo = &protocols[2];
+        assert_eq!(foo.since, "2024-03-04");
+        assert_eq!(foo.info, "Established");
+    }
+
+    #[test]
+    fn iso_long_since() {
+        let header = "Name       Proto      Table      State  Since                Info";
+        let columns = ProtocolColumns::from_header(header).unwrap();
+        let line = "dn42_kioubit_v4 BGP        ---        up     2024-03-04 10:15:27  Established";
+        let protocol = parse_protocol_line(line, columns).unwrap();
+        assert_eq!(protocol.since, "2024-03-04 10:15:27");
+        assert_eq!(protocol.info, "Established");
+
+        // Without a header, the time of day is still recognised.
+        let protocol = parse_protocol_line(line, ProtocolColumns::default()).unwrap();
+        assert_eq!(protocol.since, "2024-03-04 10:15:27");
+        assert_eq!(protocol.info, "Established");
+
+        let line = " static1    Static     master4    up     2024-03-04 10:15:27.004";
+        let protocol = parse_protocol_line(line, ProtocolColumns::default()).unwrap();
+        assert_eq!(protocol.name, "static1");
+        assert_eq!(protocol.since, "2024-03-04 10:15:27.004");
+        assert_eq!(protocol.info, "");
+    }
+
+    #[test]
+    fn time_of_day() {
+        assert!(is_time_of_day("12:00"));
+        assert!(is_time_of_day("12:00:03"));
+        assert!(is_time_of_day("12:00:03.281"));
+        assert!(!is_time_of_day("2024-03-04"));
+        assert!(!is_time_of_day("Established"));
+        assert!(!is_time_of_day("Socket:"));
+        assert!(!is_time_of_day(":00"));
+    }
+}
@@ -0,0 +1,429 @@
+use serde::{Deserialize, Serialize};
+
+use crate::utils::strip_continuation;
+
+/// A single path from `show route [for <target>] [all]` output.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct RouteEntry {
+    pub table: Option<String>,
+    pub prefix: String,
+    pub route_type: Option<String>,
+    pub via: Option<String>,
+    pub interface: Option<String>,
+    pub protocol: String,
+    pub since: String,
+    pub from: Option<String>,
+    pub primary: bool,
+    pub preference: Option<u32>,
+    pub metric: Option<u32>,
+    pub origin_asn: Option<u32>,
+    pub source: Option<String>,
+    pub bgp: Option<BgpAttributes>,
+    /// Attributes that have no dedicated field, in output order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attributes: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct BgpAttributes {
+    pub origin: Option<String>,
+    pub as_path: Vec<AsPathSegment>,
+    pub next_hop: Vec<String>,
+    pub local_pref: Option<u32>,
+    pub med: Option<u32>,
+    pub communities: Vec<(u32, u32)>,
+    pub large_communities: Vec<(u32, u32, u32)>,
+}
+
+/// Part of an AS path: ASNs in the order they were traversed, or an
+/// unordered `{...}` set left behind by aggregation.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum AsPathSegment {
+    Sequence(Vec<u32>),
+    Set(Vec<u32>),
+}
+
+impl std::fmt::Display for AsPathSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let join = |asns: &[u32]| {
+            asns.iter()
+                .map(u32::to_string)
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        match self {
+            AsPathSegment::Sequence(asns) => write!(f, "{}", join(asns)),
+            AsPathSegment::Set(asns) => write!(f, "{{{}}}", join(asns)),
+        }
+    }
+}
+
+/// Parses BIRD route output into one entry per path. Lines that start a new
+/// path are recognised by their `[protocol since]` block; everything up to
+/// the next such line (`via`, `Type:`, `BGP.*` ...) belongs to that path.
+/// Continuation paths without a prefix inherit the one above them.
+pub fn parse_routes(lines: &[String]) -> Vec<RouteEntry> {
+    let mut routes: Vec<RouteEntry> = Vec::new();
+    let mut table = None;
+    let mut prefix = String::new();
+
+    for line in lines {
+        let line = strip_continuation(line);
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        if let Some(name) = trimmed
+            .strip_prefix("Table ")
+            .and_then(|t| t.strip_suffix(':'))
+        {
+            table = Some(name.to_string());
+            continue;
+        }
+
+        let indented = line.starts_with(char::is_whitespace);
+        if let Some(mut entry) = parse_header(trimmed, indented) {
+            if entry.prefix.is_empty() {
+                entry.prefix = prefix.clone();
+            } else {
+                prefix = entry.prefix.clone();
+            }
+            entry.table = table.clone();
+            routes.push(entry);
+            continue;
+        }
+
+        let Some(entry) = routes.last_mut() else {
+            continue;
+        };
+
+        if let Some(rest) = trimmed
+            .strip_prefix("via ")
+            .or_else(|| trimmed.strip_prefix("dev "))
+        {
+            apply_next_hop(entry, trimmed.starts_with("via "), rest);
+        } else if let Some((key, value)) = trimmed.split_once(':')
+            && !key.contains(char::is_whitespace)
+        {
+            apply_attribute(entry, key, value.trim());
+        }
+    }
+
+    routes
+}
+
+fn parse_header(line: &str, indented: bool) -> Option<RouteEntry> {
+    let open = line.find(" [")?;
+    let close = open + line[open..].find(']')?;
+    let (head, bracket, tail) = (&line[..open], &line[open + 2..close], &line[close + 1..]);
+
+    let mut bracket_parts = bracket.split_whitespace();
+    let protocol = bracket_parts.next()?.to_string();
+    let rest: Vec<&str> = bracket_parts.collect();
+    let (since, from) = match rest.iter().position(|p| *p == "from") {
+        Some(i) => (rest[..i].join(" "), rest.get(i + 1).map(|s| s.to_string())),
+        None => (rest.join(" "), None),
+    };
+
+    let mut entry = RouteEntry {
+        protocol,
+        since,
+        from,
+        ..Default::default()
+    };
+
+    let mut words = head.split_whitespace();
+    if !indented {
+        entry.prefix = words.next()?.to_string();
+    }
+    let words: Vec<&str> = words.collect();
+    let mut i = 0;
+    while i < words.len() {
+        match (words[i], words.get(i + 1)) {
+            ("via", Some(addr)) => {
+                entry.via = Some(addr.to_string());
+                i += 1;
+            }
+            ("on" | "dev", Some(iface)) => {
+                entry.interface = Some(iface.to_string());
+                i += 1;
+            }
+            (word, _) if entry.route_type.is_none() && entry.via.is_none() => {
+                entry.route_type = Some(word.to_string());
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    for token in tail.split_whitespace() {
+        if token == "*" {
+            entry.primary = true;
+        } else if let Some(inner) = token.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+            let mut values = inner.split('/');
+            entry.preference = values.next().and_then(|v| v.parse().ok());
+            entry.metric = values.next().and_then(|v| v.parse().ok());
+        } else if let Some(inner) = token.strip_prefix("[AS").and_then(|t| t.strip_suffix(']')) {
+            let digits = inner.trim_end_matches(|c: char| !c.is_ascii_digit());
+            entry.origin_asn = digits.parse().ok();
+        }
+    }
+
+    Some(entry)
+}
+
+fn apply_next_hop(entry: &mut RouteEntry, is_via: bool, rest: &str) {
+    let mut words = rest.split_whitespace();
+    if is_via {
+        // Multipath routes list several next hops, keep the first one.
+        if entry.via.is_some() {
+            return;
+        }
+        entry.via = words.next().map(str::to_string);
+        if words.next() == Some("on") {
+            entry.interface = words.next().map(str::to_string);
+        }
+    } else if entry.interface.is_none() {
+        entry.interface = words.next().map(str::to_string);
+    }
+}
+
+fn apply_attribute(entry: &mut RouteEntry, key: &str, value: &str) {
+    if key == "Type" {
+        entry.source = Some(value.to_string());
+        return;
+    }
+
+    let Some(attr) = key.strip_prefix("BGP.") else {
+        entry.attributes.push((key.to_string(), value.to_string()));
+        return;
+    };
+    let bgp = entry.bgp.get_or_insert_with(BgpAttributes::default);
+    match attr {
+        "origin" => bgp.origin = Some(value.to_string()),
+        "as_path" => bgp.as_path = parse_as_path(value),
+        "next_hop" => bgp.next_hop = value.split_whitespace().map(str::to_string).collect(),
+        "local_pref" => bgp.local_pref = value.parse().ok(),
+        "med" => bgp.med = value.parse().ok(),
+        "community" => {
+            bgp.communities = tuples(value)
+                .filter_map(|t| match t[..] {
+                    [a, b] => Some((a, b)),
+                    _ => None,
+                })
+                .collect()
+        }
+        "large_community" => {
+            bgp.large_communities = tuples(value)
+                .filter_map(|t| match t[..] {
+                    [a, b, c] => Some((a, b, c)),
+                    _ => None,
+                })
+                .collect()
+        }
+        _ => entry.attributes.push((key.to_string(), value.to_string())),
+    }
+}
+
+/// Splits `(a, b) (c, d)` style lists into their numeric members.
+/// Splits `4242422601 {4242423914, 4242420000}` into its sequences and
+/// sets. BIRD separates the ASNs of a set with spaces or commas.
+fn parse_as_path(value: &str) -> Vec<AsPathSegment> {
+    let spaced = value
+        .replace('{', " { ")
+        .replace('}', " } ")
+        .replace(',', " ");
+    let mut segments = Vec::new();
+    let mut set: Option<Vec<u32>> = None;
+    for token in spaced.split_whitespace() {
+        match token {
+            "{" => set = Some(Vec::new()),
+            "}" => segments.extend(set.take().map(AsPathSegment::Set)),
+            asn => {
+                let Ok(asn) = asn.parse() else {
+                    continue;
+                };
+                match (&mut set, segments.last_mut()) {
+                    (Some(set), _) => set.push(asn),
+                    (None, Some(AsPathSegment::Sequence(asns))) => asns.push(asn),
+                    (None, _) => segments.push(AsPathSegment::Sequence(vec![asn])),
+                }
+            }
+        }
+    }
+    segments.extend(set.map(AsPathSegment::Set));
+    segments
+}
+
+fn tuples(value: &str) -> impl Iterator<Item = Vec<u32>> + '_ {
+    value.split(')').filter_map(|group| {
+        let inner = group.trim().strip_prefix('(')?;
+        inner
+            .split(',')
+            .map(|n| n.trim().parse::<u32>().ok())
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `show route for 172.23.0.80 all` from BIRD 2.15, as relayed by the
+    /// proxy: continuation lines keep the leading space of the reply.
+    const ROUTE_ALL: &str = "Table master4:
+172.23.0.0/24        unicast [dn42_kioubit_v4 2024-03-04 10:15:27] * (100) [AS4242423914i]
+ \tvia 172.20.53.97 on dn42-kioubit
+ \tType: BGP univ
+ \tBGP.origin: IGP
+ \tBGP.as_path: 4242423914
+ \tBGP.next_hop: 172.20.53.97
+ \tBGP.local_pref: 100
+ \tBGP.community: (64511,3) (64511,24) (64511,33)
+ \tBGP.large_community: (4242423914, 101, 44) (4242423914, 102, 1)
+                      unicast [dn42_lare_v4 2024-03-05 08:01:12 from 172.20.0.81] (100) [AS4242423914i]
+ \tvia 172.20.0.81 on dn42-lare
+ \tType: BGP univ
+ \tBGP.origin: IGP
+ \tBGP.as_path: 4242422601 {4242423914, 4242420000}
+ \tBGP.next_hop: 172.20.0.81
+ \tBGP.med: 50
+ \tBGP.local_pref: 90
+ \tBGP.otc: 4242422601";
+
+    const ROUTE_STATIC: &str = "Table master6:
+fd42:4242:2601::/48  unreachable [static6 2024-03-01 00:00:05] * (200)
+ \tType: static univ
+fd42:4242:2601:ac12::/64 unicast [ospf6 2024-03-01 00:00:09] * I (150/20) [0.0.0.1]
+ \tvia fe80::1 on eth0
+ \tType: OSPF univ
+ \tOSPF.metric1: 20
+ \tOSPF.router_id: 0.0.0.1";
+
+    fn lines(output: &str) -> Vec<String> {
+        output.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn bgp_paths() {
+        let routes = parse_routes(&lines(ROUTE_ALL));
+        assert_eq!(routes.len(), 2);
+
+        let best = &routes[0];
+        assert_eq!(best.table.as_deref(), Some("master4"));
+        assert_eq!(best.prefix, "172.23.0.0/24");
+        assert_eq!(best.route_type.as_deref(), Some("unicast"));
+        assert_eq!(best.protocol, "dn42_kioubit_v4");
+        assert_eq!(best.since, "2024-03-04 10:15:27");
+        assert_eq!(best.from, None);
+        assert!(best.primary);
+        assert_eq!(best.preference, Some(100));
+        assert_eq!(best.origin_asn, Some(4242423914));
+        assert_eq!(best.via.as_deref(), Some("172.20.53.97"));
+        assert_eq!(best.interface.as_deref(), Some("dn42-kioubit"));
+        assert_eq!(best.source.as_deref(), Some("BGP univ"));
+        assert_eq!(
+            best.bgp,
+            Some(BgpAttributes {
+                origin: Some("IGP".to_string()),
+                as_path: vec![AsPathSegment::Sequence(vec![4242423914])],
+                next_hop: vec!["172.20.53.97".to_string()],
+                local_pref: Some(100),
+                med: None,
+                communities: vec![(64511, 3), (64511, 24), (64511, 33)],
+                large_communities: vec![(4242423914, 101, 44), (4242423914, 102, 1)],
+            })
+        );
+        assert!(best.attributes.is_empty());
+
+        // The second path has no prefix of its own.
+        let backup = &routes[1];
+        assert_eq!(backup.prefix, "172.23.0.0/24");
+        assert_eq!(backup.protocol, "dn42_lare_v4");
+        assert_eq!(backup.since, "2024-03-05 08:01:12");
+        assert_eq!(backup.from.as_deref(), Some("172.20.0.81"));
+        assert!(!backup.primary);
+        let bgp = backup.bgp.as_ref().unwrap();
+        assert_eq!(
+            bgp.as_path,
+            [
+                AsPathSegment::Sequence(vec![4242422601]),
+                AsPathSegment::Set(vec![4242423914, 4242420000]),
+            ]
+        );
+        assert_eq!(bgp.med, Some(50));
+        assert_eq!(bgp.local_pref, Some(90));
+        assert_eq!(
+            backup.attributes,
+            [("BGP.otc".to_string(), "4242422601".to_string())]
+        );
+    }
+
+    #[test]
+    fn static_and_ospf_routes() {
+        let routes = parse_routes(&lines(ROUTE_STATIC));
+        assert_eq!(routes.len(), 2);
+
+        let unreachable = &routes[0];
+        assert_eq!(unreachable.table.as_deref(), Some("master6"));
+        assert_eq!(unreachable.prefix, "fd42:4242:2601::/48");
+        assert_eq!(unreachable.route_type.as_deref(), Some("unreachable"));
+        assert_eq!(unreachable.via, None);
+        assert_eq!(unreachable.preference, Some(200));
+        assert_eq!(unreachable.source.as_deref(), Some("static univ"));
+        assert_eq!(unreachable.bgp, None);
+
+        let ospf = &routes[1];
+        assert_eq!(ospf.prefix, "fd42:4242:2601:ac12::/64");
+        assert_eq!(ospf.protocol, "ospf6");
+        assert_eq!((ospf.preference, ospf.metric), (Some(150), Some(20)));
+        assert_eq!(ospf.origin_asn, None);
+        assert_eq!(ospf.via.as_deref(), Some("fe80::1"));
+        assert_eq!(ospf.interface.as_deref(), Some("eth0"));
+        assert_eq!(ospf.attributes.len(), 2);
+    }
+
+    #[test]
+    fn multipath_keeps_first_next_hop() {
+        let output = "10.0.0.0/8           unicast [kernel4 12:00:00.123] * (10)
+ \tvia 192.168.1.1 on eth0 weight 1
+ \tvia 192.168.1.2 on eth1 weight 1";
+        let routes = parse_routes(&lines(output));
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].since, "12:00:00.123");
+        assert_eq!(routes[0].table, None);
+        assert_eq!(routes[0].via.as_deref(), Some("192.168.1.1"));
+        assert_eq!(routes[0].interface.as_deref(), Some("eth0"));
+    }
+
+    #[test]
+    fn as_path_sets() {
+        use AsPathSegment::{Sequence, Set};
+
+        assert_eq!(parse_as_path(""), []);
+        assert_eq!(
+            parse_as_path("4242422601 4242423914"),
+            [Sequence(vec![4242422601, 4242423914])]
+        );
+        let path = parse_as_path("4242422601 4242421080 {4242423914 4242420000} 4242420001");
+        assert_eq!(
+            path,
+            [
+                Sequence(vec![4242422601, 4242421080]),
+                Set(vec![4242423914, 4242420000]),
+                Sequence(vec![4242420001]),
+            ]
+        );
+        let shown: Vec<String> = path.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            shown.join(" "),
+            "4242422601 4242421080 {4242423914 4242420000} 4242420001"
+        );
+        // A set right at the start, with the spacing of older BIRD versions.
+        assert_eq!(
+            parse_as_path("{ 4242423914, 4242420000 }"),
+            [Set(vec![4242423914, 4242420000])]
+        );
+    }
+}
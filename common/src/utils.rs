@@ -6,7 +6,14 @@ pub fn filter_protocol_details(raw: &str) -> String {
         .join("\n")
 }
 
-use std::net::IpAddr;
+/// BIRD marks continuation lines of a multi-line reply with a single leading
+/// space in place of the reply code. Removing it lines them up with the first
+/// line again so indentation and column offsets can be relied upon.
+pub fn strip_continuation(line: &str) -> &str {
+    line.strip_prefix(' ').unwrap_or(line)
+}
+
+use std::net::{IpAddr, SocketAddr};
 
 use serde::{Deserialize, Deserializer};
 
@@ -43,6 +50,39 @@ pub fn validate_target(target: &str) -> Result<(), String> {
     Ok(())
 }
 
+pub fn validate_endpoint(endpoint: &str) -> Result<(), String> {
+    let endpoint = endpoint.trim();
+    if endpoint.parse::<SocketAddr>().is_ok() {
+        return Ok(());
+    }
+
+    let (host, port) = endpoint
+        .rsplit_once(':')
+        .ok_or_else(|| "Endpoint must be in host:port format".to_string())?;
+    if host.contains(':') {
+        return Err("IPv6 endpoints must be written as [address]:port".to_string());
+    }
+    match port.parse::<u16>() {
+        Ok(port) if port > 0 => {}
+        _ => return Err(format!("Endpoint port '{}' is invalid", port)),
+    }
+    validate_target(host)
+}
+
+pub fn validate_wg_pubkey(key: &str) -> Result<(), String> {
+    let key = key.trim();
+    if key.len() == 44
+        && key.ends_with('=')
+        && key
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'+' || b == b'/' || b == b'=')
+    {
+        Ok(())
+    } else {
+        Err("WireGuard public key must be 44 base64 characters".to_string())
+    }
+}
+
 pub fn deserialize_listen_address<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
 where
     D: Deserializer<'de>,
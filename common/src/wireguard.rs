@@ -1,39 +1,172 @@
 use crate::{
-    humanize::{humanize_bytes, humanize_duration},
-    models::WireGuardPeer,
+    models::{WireGuardInterface, WireGuardPeer},
+    protocol::ProtocolDetails,
 };
 
-pub fn parse_wireguard_dump(output: &str) -> Vec<WireGuardPeer> {
-    let mut peers = Vec::new();
-
-    for line in output.lines() {
-        let fields: Vec<&str> = line.split('\t').collect();
-
-        // let public_key = fields[1].to_string();
-        // let endpoint = if fields[3] != "(none)" {
-        //     Some(fields[3].to_string())
-        // } else {
-        //     None
-        // };
-
-        let name = fields[0].to_string();
-        let latest_handshake_ts: i64 = fields[5].parse().unwrap_or(0);
-        let latest_handshake = humanize_duration(latest_handshake_ts);
-
-        let rx_bytes: u64 = fields[6].parse().unwrap_or(0);
-        let tx_bytes: u64 = fields[7].parse().unwrap_or(0);
-
-        peers.push(WireGuardPeer {
-            name,
-            // public_key,
-            // endpoint,
-            latest_handshake,
-            transfer_rx: humanize_bytes(rx_bytes),
-            transfer_tx: humanize_bytes(tx_bytes),
+/// Parses `wg show all dump` or `wg show <interface> dump` output into
+/// interfaces and their peers. The former prefixes every line with the
+/// interface name, the latter leaves it out, so its lines are attributed to
+/// `interface`. Interface lines carry 4 further fields (the private key is
+/// dropped), peer lines 8. Peers whose interface line is missing still get an
+/// interface entry. Fields the proxy redacted or WireGuard reports as
+/// `(none)` / `off` become `None`.
+pub fn parse_wireguard_dump(
+    output: &str,
+    interface: Option<&str>,
+) -> Result<Vec<WireGuardInterface>, String> {
+    let mut interfaces: Vec<WireGuardInterface> = Vec::new();
+
+    for (number, line) in output.lines().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let mut fields: Vec<&str> = line.split('\t').collect();
+        let name = match (fields.len(), interface) {
+            (5 | 9, _) => fields.remove(0),
+            (4 | 8, Some(name)) => name,
+            (4 | 8, None) => {
+                return Err(format!(
+                    "WireGuard dump line {} has no interface name",
+                    number + 1
+                ));
+            }
+            (len, _) => {
+                return Err(format!(
+                    "WireGuard dump line {} has {} fields, expected 4, 5, 8 or 9",
+                    number + 1,
+                    len
+                ));
+            }
+        };
+
+        let index = match interfaces.iter().position(|i| i.name == name) {
+            Some(index) => index,
+            None => {
+                interfaces.push(WireGuardInterface {
+                    name: name.to_string(),
+                    public_key: None,
+                    listen_port: None,
+                    peers: Vec::new(),
+                    protocols: Vec::new(),
+                });
+                interfaces.len() - 1
+            }
+        };
+        let interface = &mut interfaces[index];
+
+        if fields.len() == 4 {
+            interface.public_key = value(fields[1]).map(str::to_string);
+            interface.listen_port = fields[2].parse().ok();
+            continue;
+        }
+
+        interface.peers.push(WireGuardPeer {
+            public_key: value(fields[0]).map(str::to_string),
+            endpoint: value(fields[2]).map(str::to_string),
+            allowed_ips: value(fields[3])
+                .map(|ips| ips.split(',').map(str::to_string).collect())
+                .unwrap_or_default(),
+            latest_handshake: fields[4].parse().ok().filter(|&ts: &i64| ts > 0),
+            rx_bytes: fields[5].parse().unwrap_or(0),
+            tx_bytes: fields[6].parse().unwrap_or(0),
+            persistent_keepalive: value(fields[7]).and_then(|k| k.parse().ok()),
         });
     }
 
-    peers.sort_by(|a, b| a.name.cmp(&b.name));
+    interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(interfaces)
+}
+
+/// Names of the BGP protocols that run over `interface`: those named after
+/// it and those whose neighbor address is scoped to it (`fe80::1%wg0`).
+pub fn protocols_for_interface(interface: &str, protocols: &[ProtocolDetails]) -> Vec<String> {
+    protocols
+        .iter()
+        .filter(|p| p.summary.proto == "BGP")
+        .filter(|p| {
+            p.summary.name == interface
+                || p.bgp
+                    .as_ref()
+                    .and_then(|bgp| bgp.neighbor_address.as_deref())
+                    .and_then(|addr| addr.split_once('%'))
+                    .is_some_and(|(_, scope)| scope == interface)
+        })
+        .map(|p| p.summary.name.clone())
+        .collect()
+}
+
+fn value(field: &str) -> Option<&str> {
+    match field {
+        "" | "(none)" | "(redacted)" | "off" => None,
+        _ => Some(field),
+    }
+}
 
-    peers
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_DUMP: &str = "\
+wg0\tcHJpdmF0ZQ==\tcHVibGljMA==\t51820\toff
+wg0\tcGVlcjA=\t(none)\t192.0.2.1:51820\tfd00::/64,10.0.0.0/8\t1700000000\t1024\t2048\t25
+wg1\tcHJpdmF0ZQ==\tcHVibGljMQ==\t51821\toff
+wg1\tcGVlcjE=\t(none)\t(none)\t(none)\t0\t0\t0\toff
+";
+
+    const SINGLE_DUMP: &str = "\
+cHJpdmF0ZQ==\tcHVibGljMA==\t51820\toff
+cGVlcjA=\t(none)\t192.0.2.1:51820\tfd00::/64,10.0.0.0/8\t1700000000\t1024\t2048\t25
+";
+
+    fn wg0() -> WireGuardInterface {
+        WireGuardInterface {
+            name: "wg0".to_string(),
+            public_key: Some("cHVibGljMA==".to_string()),
+            listen_port: Some(51820),
+            peers: vec![WireGuardPeer {
+                public_key: Some("cGVlcjA=".to_string()),
+                endpoint: Some("192.0.2.1:51820".to_string()),
+                allowed_ips: vec!["fd00::/64".to_string(), "10.0.0.0/8".to_string()],
+                latest_handshake: Some(1700000000),
+                rx_bytes: 1024,
+                tx_bytes: 2048,
+                persistent_keepalive: Some(25),
+            }],
+            protocols: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn all_interfaces() {
+        let interfaces = parse_wireguard_dump(ALL_DUMP, None).unwrap();
+        assert_eq!(interfaces.len(), 2);
+        assert_eq!(interfaces[0], wg0());
+        let peer = &interfaces[1].peers[0];
+        assert_eq!(peer.endpoint, None);
+        assert!(peer.allowed_ips.is_empty());
+        assert_eq!(peer.latest_handshake, None);
+        assert_eq!(peer.persistent_keepalive, None);
+    }
+
+    #[test]
+    fn single_interface() {
+        assert_eq!(
+            parse_wireguard_dump(SINGLE_DUMP, Some("wg0")).unwrap(),
+            vec![wg0()]
+        );
+        // Lines of `wg show all dump` name their interface themselves.
+        assert_eq!(
+            parse_wireguard_dump(ALL_DUMP, Some("wg0")).unwrap().len(),
+            2
+        );
+        assert!(parse_wireguard_dump(SINGLE_DUMP, None).is_err());
+    }
+
+    #[test]
+    fn rejects_unrecognised_lines() {
+        assert!(parse_wireguard_dump("wg0\tkey\t51820", None).is_err());
+        assert!(parse_wireguard_dump("interface: wg0", Some("wg0")).is_err());
+        assert_eq!(parse_wireguard_dump("", None).unwrap(), Vec::new());
+    }
 }
@@ -3,9 +3,11 @@ pub mod content_modal;
 pub mod data_table;
 pub mod header;
 pub mod main_view;
+pub mod protocol_history;
 pub mod protocols;
 pub mod route_dropdown;
 pub mod route_lookup;
+pub mod route_table;
 pub mod shell;
 pub mod status_banner;
 pub mod traceroute;
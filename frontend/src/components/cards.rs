@@ -1,6 +1,10 @@
-use common::models::PeeringInfo;
+use common::{
+    models::PeeringInfo,
+    protocol::{BgpTimer, ProtocolDetails},
+};
 use yew::prelude::*;
 
+use super::data_table::{DataTable, TableRow};
 use crate::utils::select_text;
 
 #[derive(Properties, PartialEq)]
@@ -113,3 +117,85 @@ pub fn peering_field(props: &PeeringFieldProps) -> Html {
         html! {}
     }
 }
+
+#[derive(Properties, PartialEq)]
+pub struct ProtocolDetailsCardProps {
+    pub details: ProtocolDetails,
+}
+
+#[function_component(ProtocolDetailsCard)]
+pub fn protocol_details_card(props: &ProtocolDetailsCardProps) -> Html {
+    let details = &props.details;
+    let summary = &details.summary;
+    let bgp = details.bgp.clone().unwrap_or_default();
+    let timer = |timer: &Option<BgpTimer>| {
+        timer.as_ref().map(|t| {
+            format!(
+                "{} / {}",
+                t.remaining
+                    .map(|r| format!("{:.1}s", r))
+                    .unwrap_or("-".into()),
+                t.configured
+                    .map(|c| format!("{}s", c))
+                    .unwrap_or("-".into()),
+            )
+        })
+    };
+
+    html! {
+        <article class="peering-card">
+            <div class="peering-node-header">
+                <h4 class="peering-node-title">{ &summary.name }</h4>
+                <span class="peering-node-meta">
+                    { format!("{} {} since {}", summary.proto, summary.state, summary.since) }
+                </span>
+            </div>
+            <dl class="peering-grid">
+                <PeeringField label="Description" value={details.description.clone()} />
+                <PeeringField label="BGP State" value={details.bgp.as_ref().map(|b| b.state.clone())} />
+                <PeeringField label="Neighbor Address" value={bgp.neighbor_address.clone()} />
+                <PeeringField label="Neighbor AS" value={bgp.neighbor_as.map(|a| format!("AS{}", a))} />
+                <PeeringField label="Local AS" value={bgp.local_as.map(|a| format!("AS{}", a))} />
+                <PeeringField label="Neighbor ID" value={bgp.neighbor_id.clone()} />
+                <PeeringField label="Session" value={bgp.session.clone()} />
+                <PeeringField label="Source Address" value={bgp.source_address.clone()} />
+                <PeeringField label="Hold Timer" value={timer(&bgp.hold_timer)} />
+                <PeeringField label="Keepalive Timer" value={timer(&bgp.keepalive_timer)} />
+                <PeeringField label="Last Error" value={details.last_error.clone()} />
+            </dl>
+            if !details.channels.is_empty() {
+                <DataTable
+                    headers={
+                        [
+                            "Channel",
+                            "State",
+                            "Table",
+                            "Imported",
+                            "Filtered",
+                            "Exported",
+                            "Preferred",
+                        ]
+                        .map(AttrValue::from)
+                        .to_vec()
+                    }
+                    rows={
+                        details.channels.iter().map(|channel| TableRow {
+                            cells: vec![
+                                html! { &channel.name },
+                                html! { channel.state.clone().unwrap_or_default() },
+                                html! { channel.table.clone().unwrap_or_default() },
+                                html! { channel.routes.imported },
+                                html! { channel.routes.filtered },
+                                html! { channel.routes.exported },
+                                html! { channel.routes.preferred },
+                            ],
+                            on_click: None,
+                            highlighted: false,
+                        })
+                        .collect::<Vec<_>>()
+                    }
+                />
+            }
+        </article>
+    }
+}
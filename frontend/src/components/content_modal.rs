@@ -10,6 +10,10 @@ pub struct ContentModalProps {
     pub content: AttrValue,
     pub command: Option<AttrValue>,
     pub on_close: Callback<()>,
+    /// Structured view of the content. When present, the raw text is
+    /// collapsed below it.
+    #[prop_or_default]
+    pub children: Children,
 }
 
 #[function_component(ContentModal)]
@@ -112,17 +116,33 @@ pub fn content_modal(props: &ContentModalProps) -> Html {
                         html! {}
                     }
                 }
-                <pre
-                    style={
-                        if props.command.is_some() {
-                            "margin: 0; border: 1px solid var(--border); border-top: none; border-radius: 0 0 4px 4px;"
-                        } else {
-                            ""
+                {
+                    if props.children.is_empty() {
+                        html! {
+                            <pre
+                                style={
+                                    if props.command.is_some() {
+                                        "margin: 0; border: 1px solid var(--border); border-top: none; border-radius: 0 0 4px 4px;"
+                                    } else {
+                                        ""
+                                    }
+                                }
+                            >
+                                { &props.content }
+                            </pre>
+                        }
+                    } else {
+                        html! {
+                            <>
+                                { for props.children.iter() }
+                                <details class="expandable-item">
+                                    <summary class="summary-header">{ "Raw output" }</summary>
+                                    <pre>{ &props.content }</pre>
+                                </details>
+                            </>
                         }
                     }
-                >
-                    { &props.content }
-                </pre>
+                }
             </div>
         </div>
     }
@@ -4,6 +4,7 @@ use yew::prelude::*;
 pub struct TableRow {
     pub cells: Vec<Html>,
     pub on_click: Option<Callback<MouseEvent>>,
+    pub highlighted: bool,
 }
 
 #[derive(Properties, PartialEq)]
@@ -25,13 +26,10 @@ pub fn data_table(props: &DataTableProps) -> Html {
                 { for props.rows.iter().map(|row| {
                     let on_click = row.on_click.clone().unwrap_or_default();
                     html! {
-                        <tr class={
-                            if row.on_click.is_some() {
-                                "clickable-row"
-                            } else {
-                                ""
-                            }
-                        } onclick={on_click}>
+                        <tr class={classes!(
+                            row.on_click.is_some().then_some("clickable-row"),
+                            row.highlighted.then_some("highlighted-row"),
+                        )} onclick={on_click}>
                             { for row.cells.iter().map(|cell| html! { <td>{ cell.clone() }</td> }) }
                         </tr>
                     }
@@ -1,8 +1,14 @@
 use yew::prelude::*;
 
 use crate::{
-    components::{content_modal::ContentModal, header::Header, status_banner::StatusBanner},
-    store::{Action, LgStateHandle, modal::ModalAction},
+    components::{
+        cards::ProtocolDetailsCard, content_modal::ContentModal, header::Header,
+        route_table::RouteTable, status_banner::StatusBanner,
+    },
+    store::{
+        Action, LgStateHandle,
+        modal::{ModalAction, ModalView},
+    },
 };
 
 #[derive(Properties, PartialEq)]
@@ -37,7 +43,17 @@ pub fn main_view(props: &MainViewProps) -> Html {
                             state.dispatch(Action::Modal(ModalAction::Close));
                         })
                     }
-                />
+                >
+                    { for state.modal.view.iter().filter_map(|view| match view {
+                        ModalView::Routes(routes) if routes.is_empty() => None,
+                        ModalView::Routes(routes) => Some(html! {
+                            <RouteTable routes={routes.clone()} />
+                        }),
+                        ModalView::Protocol(details) => Some(html! {
+                            <ProtocolDetailsCard details={(**details).clone()} />
+                        }),
+                    }) }
+                </ContentModal>
                 {
                     if state.config_ready {
                         html! { { for props.children.iter() } }
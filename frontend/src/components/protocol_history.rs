@@ -0,0 +1,147 @@
+use chrono::Local;
+use common::history::ProtocolHistory;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlSelectElement;
+use yew::prelude::*;
+
+use super::{
+    data_table::{DataTable, TableRow},
+    shell::{ShellLine, ShellSelect},
+};
+use crate::{
+    services::api::get_protocol_history,
+    store::{LgStateHandle, route_info::RouteInfoHandle},
+};
+
+#[function_component(ProtocolTimeline)]
+pub fn protocol_timeline() -> Html {
+    let state = use_context::<LgStateHandle>().expect("no app state found");
+    let route_info = use_context::<RouteInfoHandle>().expect("no route info found");
+    let selected = use_state(String::new);
+    let history = use_state(|| None::<Result<ProtocolHistory, String>>);
+
+    let Some(node) = route_info.node_info.clone() else {
+        return html! {};
+    };
+
+    let load = {
+        let backend_url = state.backend_url.clone();
+        let node_name = node.name.clone();
+        let history = history.clone();
+        Callback::from(move |protocol: String| {
+            let backend_url = backend_url.clone();
+            let node_name = node_name.clone();
+            let history = history.clone();
+            spawn_local(async move {
+                history.set(Some(
+                    get_protocol_history(&backend_url, &node_name, &protocol).await,
+                ));
+            });
+        })
+    };
+
+    let on_select = {
+        let selected = selected.clone();
+        let history = history.clone();
+        let load = load.clone();
+        Callback::from(move |e: Event| {
+            let protocol = e.target_unchecked_into::<HtmlSelectElement>().value();
+            selected.set(protocol.clone());
+            history.set(None);
+            if !protocol.is_empty() {
+                load.emit(protocol);
+            }
+        })
+    };
+
+    let on_refresh = {
+        let selected = selected.clone();
+        Callback::from(move |e: MouseEvent| {
+            e.prevent_default();
+            if !selected.is_empty() {
+                load.emit((*selected).clone());
+            }
+        })
+    };
+
+    html! {
+        <section>
+            <h3>
+                {"History"}
+                <button
+                    class="refresh-button"
+                    onclick={on_refresh}
+                    title="Refresh protocol history"
+                >
+                    {"↻"}
+                </button>
+            </h3>
+            <div class="shell-line">
+                <ShellSelect value={(*selected).clone()} on_change={on_select}>
+                    <option value="" selected={selected.is_empty()}>{"(select protocol)"}</option>
+                    { for node.protocols.iter().map(|p| html! {
+                        <option value={p.name.clone()} selected={*selected == p.name}>{ &p.name }</option>
+                    }) }
+                </ShellSelect>
+            </div>
+            {
+                match &*history {
+                    None => html! {},
+                    Some(Err(err)) => html! {
+                        <pre class="status-message--error">{ err }</pre>
+                    },
+                    Some(Ok(history)) => render_history(&state.username, history),
+                }
+            }
+        </section>
+    }
+}
+
+fn render_history(username: &str, history: &ProtocolHistory) -> Html {
+    let flaps = history
+        .flaps
+        .iter()
+        .map(|f| format!("{}h: {}", f.window_secs / 3600, f.flaps))
+        .collect::<Vec<_>>()
+        .join(" · ");
+
+    html! {
+        <>
+            <ShellLine
+                prompt={format!("{}@{}$ ", username, history.node)}
+                command={format!("history {}", history.protocol)}
+                style={"font-size: 0.9em;".to_string()}
+            />
+            <p class="status-message">{ format!("Flaps ({})", flaps) }</p>
+            if history.transitions.is_empty() {
+                <p class="status-message">{"No state changes recorded yet"}</p>
+            } else {
+                <DataTable
+                    headers={
+                        ["Time", "From", "To", "Reason"]
+                            .map(AttrValue::from)
+                            .to_vec()
+                    }
+                    rows={
+                        history.transitions.iter().rev().map(|t| TableRow {
+                            cells: vec![
+                                html! {
+                                    t.timestamp
+                                        .with_timezone(&Local)
+                                        .format("%Y-%m-%d %H:%M:%S")
+                                        .to_string()
+                                },
+                                html! { t.from.clone().unwrap_or_else(|| "-".to_string()) },
+                                html! { &t.to },
+                                html! { t.reason.clone().unwrap_or_default() },
+                            ],
+                            on_click: None,
+                            highlighted: t.is_flap(),
+                        })
+                        .collect::<Vec<_>>()
+                    }
+                />
+            }
+        </>
+    }
+}
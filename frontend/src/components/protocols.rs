@@ -1,4 +1,8 @@
-use chrono::Local;
+use chrono::{Local, Utc};
+use common::{
+    humanize::humanize_duration,
+    models::{Protocol, ProtocolTunnel},
+};
 use yew::prelude::*;
 
 use super::{
@@ -30,6 +34,12 @@ pub fn protocols() -> Html {
             <h3>{"Protocols"}</h3>
             { for nodes.iter().map(|node| {
                 let node_name = node.name.clone();
+                let tunnels = state
+                    .tunnels
+                    .iter()
+                    .find(|n| n.name == node.name)
+                    .map(|n| n.tunnels.as_slice())
+                    .unwrap_or_default();
                 let on_protocol_click = on_protocol_click.clone();
                 html! {
                     <>
@@ -74,6 +84,7 @@ pub fn protocols() -> Html {
                                         "State",
                                         "Since",
                                         "Info",
+                                        "Tunnel",
                                     ]
                                     .map(AttrValue::from)
                                     .to_vec()
@@ -91,6 +102,12 @@ pub fn protocols() -> Html {
                                                 html! { &p.state },
                                                 html! { &p.since },
                                                 html! { &p.info },
+                                                html! {
+                                                    tunnel_status(
+                                                        p,
+                                                        tunnels.iter().find(|t| t.protocol == p.name),
+                                                    )
+                                                },
                                             ],
                                             on_click: Some(Callback::from(move |_| {
                                                 on_row_click.emit((
@@ -98,6 +115,7 @@ pub fn protocols() -> Html {
                                                     proto_name.clone(),
                                                 ));
                                             })),
+                                            highlighted: false,
                                         }
                                     })
                                     .collect::<Vec<_>>()
@@ -110,3 +128,21 @@ pub fn protocols() -> Html {
         </div>
     }
 }
+
+/// Describes the WireGuard tunnel under a session, so a session that is down
+/// over a working tunnel can be told apart from a broken tunnel.
+fn tunnel_status(protocol: &Protocol, tunnel: Option<&ProtocolTunnel>) -> String {
+    let Some(tunnel) = tunnel else {
+        return "-".to_string();
+    };
+    let handshake = match tunnel.latest_handshake.and_then(humanize_duration) {
+        Some(ago) => format!("last handshake {}", ago),
+        None => "no handshake yet".to_string(),
+    };
+
+    match (protocol.state == "up", tunnel.is_up(Utc::now().timestamp())) {
+        (true, true) => format!("{}, {}", tunnel.interface, handshake),
+        (false, true) => format!("session down, but {}", handshake),
+        (_, false) => format!("{} down, {}", tunnel.interface, handshake),
+    }
+}
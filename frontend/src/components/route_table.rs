@@ -0,0 +1,80 @@
+use common::route::RouteEntry;
+use yew::prelude::*;
+
+use super::data_table::{DataTable, TableRow};
+
+#[derive(Properties, PartialEq)]
+pub struct RouteTableProps {
+    pub routes: Vec<RouteEntry>,
+}
+
+#[function_component(RouteTable)]
+pub fn route_table(props: &RouteTableProps) -> Html {
+    let rows = props
+        .routes
+        .iter()
+        .map(|route| {
+            let bgp = route.bgp.clone().unwrap_or_default();
+            let next_hop = match (&route.via, &route.interface) {
+                (Some(via), Some(iface)) => format!("{} on {}", via, iface),
+                (Some(via), None) => via.clone(),
+                (None, Some(iface)) => format!("dev {}", iface),
+                (None, None) => route.route_type.clone().unwrap_or_default(),
+            };
+            let as_path = bgp
+                .as_path
+                .iter()
+                .map(|segment| segment.to_string())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let communities = bgp
+                .communities
+                .iter()
+                .map(|(a, b)| format!("({},{})", a, b))
+                .chain(
+                    bgp.large_communities
+                        .iter()
+                        .map(|(a, b, c)| format!("({},{},{})", a, b, c)),
+                )
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            TableRow {
+                cells: vec![
+                    html! { { if route.primary { "*" } else { "" } } },
+                    html! { &route.prefix },
+                    html! { &route.protocol },
+                    html! { next_hop },
+                    html! { as_path },
+                    html! { bgp.local_pref.map(|p| p.to_string()).unwrap_or_default() },
+                    html! { bgp.med.map(|m| m.to_string()).unwrap_or_default() },
+                    html! { communities },
+                    html! { &route.since },
+                ],
+                on_click: None,
+                highlighted: route.primary,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    html! {
+        <DataTable
+            headers={
+                [
+                    "",
+                    "Prefix",
+                    "Protocol",
+                    "Next Hop",
+                    "AS Path",
+                    "Local Pref",
+                    "MED",
+                    "Communities",
+                    "Since",
+                ]
+                .map(AttrValue::from)
+                .to_vec()
+            }
+            rows={rows}
+        />
+    }
+}
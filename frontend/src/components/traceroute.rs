@@ -216,6 +216,7 @@ pub fn traceroute_section() -> Html {
                                                             },
                                                         ],
                                                         on_click: None,
+                                                        highlighted: false,
                                                     }
                                                 })
                                                 .collect::<Vec<_>>()
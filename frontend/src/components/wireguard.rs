@@ -1,3 +1,4 @@
+use common::humanize::{humanize_bytes, humanize_duration};
 use yew::prelude::*;
 
 use super::{
@@ -50,20 +51,22 @@ pub fn wireguard_section() -> Html {
                            {
                                 if let Some(err) = &node_wg.error {
                                     html! { <pre class="status-message--error">{ err }</pre> }
-                                } else if node_wg.peers.is_empty() {
+                                } else if node_wg.interfaces.iter().all(|i| i.peers.is_empty()) {
                                     html! { <p class="status-message">{"No WireGuard peers found"}</p> }
                                 } else {
                                     html! {
                                         <>
                                             <ShellLine
                                                 prompt={format!("{}@{}$ ", state.username, &node_wg.name)}
-                                                command={"wg show".to_string()}
+                                                command={"wg show all".to_string()}
                                                 style={"font-size: 0.9em;".to_string()}
                                             />
                                             <DataTable
                                                 headers={
                                                     [
-                                                        "Peer",
+                                                        "Interface",
+                                                        "Protocols",
+                                                        "Endpoint",
                                                         "Latest Handshake",
                                                         "Transfer RX",
                                                         "Transfer TX",
@@ -72,16 +75,31 @@ pub fn wireguard_section() -> Html {
                                                     .to_vec()
                                                 }
                                                 rows={
-                                                    node_wg.peers.iter().map(|peer| {
+                                                    node_wg.interfaces.iter().flat_map(|interface| {
+                                                        interface.peers.iter().map(move |peer| (interface, peer))
+                                                    })
+                                                    .map(|(interface, peer)| {
                                                         TableRow {
                                                             cells: vec![
-                                                                html! { &peer.name },
-                                                                // html! { peer.endpoint.as_deref().unwrap_or("-") },
-                                                                html! { peer.latest_handshake.as_deref().unwrap_or("never") },
-                                                                html! { &peer.transfer_rx },
-                                                                html! { &peer.transfer_tx },
+                                                                html! { &interface.name },
+                                                                html! {
+                                                                    if interface.protocols.is_empty() {
+                                                                        {"-"}
+                                                                    } else {
+                                                                        { interface.protocols.join(", ") }
+                                                                    }
+                                                                },
+                                                                html! { peer.endpoint.as_deref().unwrap_or("-") },
+                                                                html! {
+                                                                    peer.latest_handshake
+                                                                        .and_then(humanize_duration)
+                                                                        .unwrap_or_else(|| "never".to_string())
+                                                                },
+                                                                html! { humanize_bytes(peer.rx_bytes) },
+                                                                html! { humanize_bytes(peer.tx_bytes) },
                                                             ],
                                                             on_click: None,
+                                                            highlighted: false,
                                                         }
                                                     })
                                                     .collect::<Vec<_>>()
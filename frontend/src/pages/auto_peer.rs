@@ -1,15 +1,37 @@
-use common::auto_peer::{ChallengeMethod, PeeringSession};
+use common::auto_peer::{AutoPeerRequest, ChallengeMethod, PeeringSession};
+use web_sys::HtmlSelectElement;
 use yew::prelude::*;
 
 use crate::{
-    components::shell::{ShellButton, ShellInput, ShellLine, ShellPrompt},
-    store::auto_peer::{AutoPeerAction, AutoPeerState, AutoPeerStep},
+    components::shell::{ShellButton, ShellInput, ShellLine, ShellPrompt, ShellSelect},
+    services::autopeer::dispatch_request,
+    store::{
+        LgStateHandle,
+        auto_peer::{AutoPeerAction, AutoPeerState, AutoPeerStep},
+    },
 };
 
 #[function_component(AutoPeerPage)]
 pub fn auto_peer_page() -> Html {
+    let lg_state = use_context::<LgStateHandle>().expect("no app state found");
     let state = use_reducer(AutoPeerState::default);
 
+    {
+        let state = state.clone();
+        let api_url = lg_state
+            .autopeer_url
+            .clone()
+            .unwrap_or_else(|| format!("{}/autopeer", lg_state.backend_url.trim_end_matches('/')));
+        let nodes: Vec<String> = lg_state.nodes.iter().map(|n| n.name.clone()).collect();
+        use_effect_with((api_url, nodes), move |(api_url, nodes)| {
+            state.dispatch(AutoPeerAction::Configure {
+                api_url: api_url.clone(),
+                nodes: nodes.clone(),
+            });
+            || ()
+        });
+    }
+
     let content = match state.step {
         AutoPeerStep::EnterAsn => render_asn_input(&state),
         AutoPeerStep::SelectChallenge => render_challenge_selection(&state),
@@ -37,15 +59,14 @@ fn render_asn_input(state: &UseReducerHandle<AutoPeerState>) -> Html {
 
     let state_clone = state.clone();
     let on_submit = Callback::from(move |_| {
-        // TODO: Send request to backend
-        // For now, simulate response for testing
-        state_clone.dispatch(AutoPeerAction::SetLoading(true));
-
-        // Mock response - in real implementation, this would come from API
-        let mock_response = common::auto_peer::AutoPeerResponse::InitSuccess {
-            challenge_methods: vec![ChallengeMethod::Pgp, ChallengeMethod::Email],
-        };
-        state_clone.dispatch(AutoPeerAction::HandleInitResponse(mock_response));
+        dispatch_request(
+            &state_clone,
+            None,
+            AutoPeerRequest::InitSession {
+                asn: state_clone.asn.trim().to_string(),
+            },
+            AutoPeerAction::HandleInitResponse,
+        );
     });
 
     html! {
@@ -84,41 +105,26 @@ fn render_asn_input(state: &UseReducerHandle<AutoPeerState>) -> Html {
 }
 
 fn render_challenge_selection(state: &UseReducerHandle<AutoPeerState>) -> Html {
-    let state_clone = state.clone();
-
-    let on_select_pgp = Callback::from(move |_| {
-        let state = state_clone.clone();
-        state.dispatch(AutoPeerAction::SelectMethod(ChallengeMethod::Pgp));
-        state.dispatch(AutoPeerAction::SetLoading(true));
-
-        // Mock response
-        let mock_response = common::auto_peer::AutoPeerResponse::ChallengeSelected {
-            challenge_text: Some(
-                "-----BEGIN PGP MESSAGE-----\nTest challenge text\n-----END PGP MESSAGE-----"
-                    .to_string(),
-            ),
-        };
-        state.dispatch(AutoPeerAction::HandleChallengeResponse(mock_response));
-    });
-
-    let state_clone = state.clone();
-    let on_select_email = Callback::from(move |_| {
-        let state = state_clone.clone();
-        state.dispatch(AutoPeerAction::SelectMethod(ChallengeMethod::Email));
-        state.dispatch(AutoPeerAction::SetLoading(true));
-
-        // Mock response
-        let mock_response = common::auto_peer::AutoPeerResponse::ChallengeSelected {
-            challenge_text: None,
-        };
-        state.dispatch(AutoPeerAction::HandleChallengeResponse(mock_response));
-    });
+    let select_method = |method: ChallengeMethod| {
+        let state = state.clone();
+        Callback::from(move |_| {
+            state.dispatch(AutoPeerAction::SelectMethod(method));
+            dispatch_request(
+                &state,
+                state.token.clone(),
+                AutoPeerRequest::SelectChallenge { method },
+                AutoPeerAction::HandleChallengeResponse,
+            );
+        })
+    };
+    let on_select_pgp = select_method(ChallengeMethod::Pgp);
+    let on_select_email = select_method(ChallengeMethod::Email);
 
     html! {
         <div class="autopeer-step">
             <ShellLine>
                 <ShellPrompt>{"autopeer"}</ShellPrompt>
-                {format!(" ASN {} verified. Select verification method:", state.asn)}
+                {format!(" Select a verification method for AS{}:", state.asn.trim_start_matches("AS"))}
             </ShellLine>
 
             <div class="autopeer-challenge-list">
@@ -175,23 +181,17 @@ fn pgp_verification(props: &PgpVerificationProps) -> Html {
     });
 
     let state_clone = props.state.clone();
+    let (pubkey_clone, signature_clone) = (pubkey.clone(), signature.clone());
     let on_submit = Callback::from(move |_| {
-        let state = state_clone.clone();
-        state.dispatch(AutoPeerAction::SetLoading(true));
-
-        // Mock response
-        let mock_response = common::auto_peer::AutoPeerResponse::VerifySuccess {
-            credential: "mock_credential_token".to_string(),
-            sessions: vec![PeeringSession {
-                id: Some("session1".to_string()),
-                name: "Example Peer 1".to_string(),
-                ipv4: Some("172.20.0.1".to_string()),
-                ipv6: Some("fd00::1".to_string()),
-                endpoint: "peer1.example.com:51820".to_string(),
-                comment: Some("Primary peering".to_string()),
-            }],
-        };
-        state.dispatch(AutoPeerAction::HandleVerifyResponse(mock_response));
+        dispatch_request(
+            &state_clone,
+            state_clone.token.clone(),
+            AutoPeerRequest::VerifyPgp {
+                pubkey: (*pubkey_clone).clone(),
+                signature: (*signature_clone).clone(),
+            },
+            AutoPeerAction::HandleVerifyResponse,
+        );
     });
 
     html! {
@@ -268,15 +268,16 @@ fn email_verification(props: &EmailVerificationProps) -> Html {
     });
 
     let state_clone = props.state.clone();
+    let code_clone = code.clone();
     let on_submit = Callback::from(move |_| {
-        let state = state_clone.clone();
-        state.dispatch(AutoPeerAction::SetLoading(true));
-
-        let mock_response = common::auto_peer::AutoPeerResponse::VerifySuccess {
-            credential: "mock_credential_token".to_string(),
-            sessions: vec![],
-        };
-        state.dispatch(AutoPeerAction::HandleVerifyResponse(mock_response));
+        dispatch_request(
+            &state_clone,
+            state_clone.token.clone(),
+            AutoPeerRequest::VerifyEmail {
+                code: (*code_clone).clone(),
+            },
+            AutoPeerAction::HandleVerifyResponse,
+        );
     });
 
     html! {
@@ -286,6 +287,12 @@ fn email_verification(props: &EmailVerificationProps) -> Html {
                 {" A verification code has been sent to your registered email address."}
             </ShellLine>
 
+            if let Some(sent_to) = &props.state.challenge_text {
+                <ShellLine>
+                    <span class="text-secondary">{sent_to}</span>
+                </ShellLine>
+            }
+
             <ShellLine>
                 <ShellPrompt>{"code"}</ShellPrompt>
                 {" "}
@@ -327,26 +334,98 @@ fn render_email_verification(state: &UseReducerHandle<AutoPeerState>) -> Html {
 
 #[function_component(SessionManagement)]
 fn session_management(props: &SessionManagementProps) -> Html {
-    let editing = use_state(|| None::<usize>);
+    let editing = use_state(|| None::<String>);
+    let state = &props.state;
+
+    {
+        let editing = editing.clone();
+        use_effect_with(state.sessions.clone(), move |_| {
+            editing.set(None);
+            || ()
+        });
+    }
+
+    let state_clone = state.clone();
+    let on_refresh = Callback::from(move |_| {
+        dispatch_request(
+            &state_clone,
+            state_clone.credential.clone(),
+            AutoPeerRequest::GetSessions,
+            AutoPeerAction::HandleSessionsResponse,
+        );
+    });
+
+    let state_clone = state.clone();
+    let on_create = Callback::from(move |session: PeeringSession| {
+        dispatch_request(
+            &state_clone,
+            state_clone.credential.clone(),
+            AutoPeerRequest::CreateSession { session },
+            AutoPeerAction::HandleSessionsResponse,
+        );
+    });
+
+    let mut nodes = state.nodes.clone();
+    for session in &state.sessions {
+        if !nodes.contains(&session.node) {
+            nodes.push(session.node.clone());
+        }
+    }
+    let free_nodes: Vec<String> = state
+        .nodes
+        .iter()
+        .filter(|n| !state.sessions.iter().any(|s| &s.node == *n))
+        .cloned()
+        .collect();
+
+    let new_session = PeeringSession {
+        id: None,
+        node: free_nodes.first().cloned().unwrap_or_default(),
+        name: String::new(),
+        ipv4: None,
+        ipv6: None,
+        endpoint: String::new(),
+        wg_pubkey: None,
+        comment: None,
+    };
 
     html! {
         <div class="autopeer-step">
             <ShellLine>
                 <ShellPrompt>{"sessions"}</ShellPrompt>
-                {" Manage your peering sessions"}
+                {" Manage your peering sessions "}
+                <ShellButton text="Refresh" onclick={on_refresh} disabled={state.loading} />
             </ShellLine>
 
+            if let Some(error) = &state.error {
+                <ShellLine>
+                    <span class="error-message">{error}</span>
+                </ShellLine>
+            }
+
             <div class="autopeer-sessions">
-                if props.state.sessions.is_empty() {
+                if state.sessions.is_empty() {
                     <ShellLine>
                         <span class="text-secondary">{"No peering sessions configured yet."}</span>
                     </ShellLine>
                 } else {
-                    <div class="autopeer-sessions-list">
-                        {for props.state.sessions.iter().enumerate().map(|(idx, session)| {
-                            render_session_item(session, idx, &props.state, &editing)
-                        })}
-                    </div>
+                    {for nodes.iter().map(|node| {
+                        let sessions: Vec<&PeeringSession> =
+                            state.sessions.iter().filter(|s| &s.node == node).collect();
+                        if sessions.is_empty() {
+                            return html! {};
+                        }
+                        html! {
+                            <div class="autopeer-sessions-list">
+                                <ShellLine>
+                                    <ShellPrompt>{node.clone()}</ShellPrompt>
+                                </ShellLine>
+                                {for sessions.into_iter().map(|session| {
+                                    render_session_item(session, state, &editing)
+                                })}
+                            </div>
+                        }
+                    })}
                 }
 
                 <div class="autopeer-new-session">
@@ -354,9 +433,18 @@ fn session_management(props: &SessionManagementProps) -> Html {
                         <ShellPrompt>{"new"}</ShellPrompt>
                         {" Create new peering session"}
                     </ShellLine>
-                    <div class="autopeer-form">
-                        <p class="text-secondary">{"Session creation form - to be implemented"}</p>
-                    </div>
+                    if free_nodes.is_empty() {
+                        <p class="text-secondary">{"You already have a session on every node."}</p>
+                    } else {
+                        <SessionForm
+                            key={state.sessions.len()}
+                            session={new_session}
+                            nodes={free_nodes}
+                            submit_text="Create"
+                            disabled={state.loading}
+                            on_submit={on_create}
+                        />
+                    }
                 </div>
             </div>
         </div>
@@ -376,28 +464,54 @@ fn render_session_management(state: &UseReducerHandle<AutoPeerState>) -> Html {
 
 fn render_session_item(
     session: &PeeringSession,
-    idx: usize,
     state: &UseReducerHandle<AutoPeerState>,
-    editing: &UseStateHandle<Option<usize>>,
+    editing: &UseStateHandle<Option<String>>,
 ) -> Html {
-    let is_editing = **editing == Some(idx);
+    let id = session.id.clone().unwrap_or_default();
+    let is_editing = editing.as_deref() == Some(id.as_str());
 
     let editing_clone = editing.clone();
+    let id_clone = id.clone();
     let on_edit = Callback::from(move |_| {
-        editing_clone.set(Some(idx));
+        editing_clone.set(Some(id_clone.clone()));
     });
 
     let editing_clone = editing.clone();
-    let on_cancel = Callback::from(move |_| {
+    let on_cancel = Callback::from(move |_: MouseEvent| {
         editing_clone.set(None);
     });
 
+    let state_clone = state.clone();
+    let id_clone = id.clone();
+    let on_update = Callback::from(move |session: PeeringSession| {
+        dispatch_request(
+            &state_clone,
+            state_clone.credential.clone(),
+            AutoPeerRequest::UpdateSession {
+                id: id_clone.clone(),
+                session,
+            },
+            AutoPeerAction::HandleSessionsResponse,
+        );
+    });
+
     let state_clone = state.clone();
     let on_delete = Callback::from(move |_| {
-        // TODO: Send delete request to backend
-        state_clone.dispatch(AutoPeerAction::SetLoading(true));
+        dispatch_request(
+            &state_clone,
+            state_clone.credential.clone(),
+            AutoPeerRequest::DeleteSession { id: id.clone() },
+            AutoPeerAction::HandleSessionsResponse,
+        );
     });
 
+    let nodes: Vec<String> = state
+        .nodes
+        .iter()
+        .filter(|n| **n == session.node || !state.sessions.iter().any(|s| &s.node == *n))
+        .cloned()
+        .collect();
+
     html! {
         <article class="peering-card peering-node">
             <div class="peering-node-header">
@@ -426,19 +540,140 @@ fn render_session_item(
                             <dt class="peering-label">{"Endpoint"}</dt>
                             <dd class="peering-value">{&session.endpoint}</dd>
                         </>
+                        if let Some(wg_pubkey) = &session.wg_pubkey {
+                            <>
+                                <dt class="peering-label">{"WireGuard"}</dt>
+                                <dd class="peering-value">{wg_pubkey}</dd>
+                            </>
+                        }
                     </dl>
 
                     <div class="autopeer-session-actions">
-                        <ShellButton text="Edit" onclick={on_edit} />
-                        <ShellButton text="Delete" onclick={on_delete} />
+                        <ShellButton text="Edit" onclick={on_edit} disabled={state.loading} />
+                        <ShellButton text="Delete" onclick={on_delete} disabled={state.loading} />
                     </div>
                 </>
             } else {
                 <div class="autopeer-session-edit">
-                    <p class="text-secondary">{"Edit form would go here"}</p>
-                    <ShellButton text="Cancel" onclick={on_cancel} />
+                    <SessionForm
+                        session={session.clone()}
+                        nodes={nodes}
+                        submit_text="Save"
+                        disabled={state.loading}
+                        on_submit={on_update}
+                        on_cancel={on_cancel}
+                    />
                 </div>
             }
         </article>
     }
 }
+
+#[derive(Properties, PartialEq)]
+struct SessionFormProps {
+    session: PeeringSession,
+    nodes: Vec<String>,
+    submit_text: AttrValue,
+    disabled: bool,
+    on_submit: Callback<PeeringSession>,
+    #[prop_or_default]
+    on_cancel: Option<Callback<MouseEvent>>,
+}
+
+#[function_component(SessionForm)]
+fn session_form(props: &SessionFormProps) -> Html {
+    let session = &props.session;
+    let node = use_state(|| session.node.clone());
+    let name = use_state(|| session.name.clone());
+    let ipv4 = use_state(|| session.ipv4.clone().unwrap_or_default());
+    let ipv6 = use_state(|| session.ipv6.clone().unwrap_or_default());
+    let endpoint = use_state(|| session.endpoint.clone());
+    let wg_pubkey = use_state(|| session.wg_pubkey.clone().unwrap_or_default());
+    let comment = use_state(|| session.comment.clone().unwrap_or_default());
+
+    let setter = |field: &UseStateHandle<String>| {
+        let field = field.clone();
+        Callback::from(move |value: String| field.set(value))
+    };
+
+    let node_clone = node.clone();
+    let on_node_change = Callback::from(move |e: Event| {
+        let select: HtmlSelectElement = e.target_unchecked_into();
+        node_clone.set(select.value());
+    });
+
+    let on_submit = {
+        let on_submit = props.on_submit.clone();
+        let id = session.id.clone();
+        let (node, name, ipv4, ipv6) = (node.clone(), name.clone(), ipv4.clone(), ipv6.clone());
+        let (endpoint, wg_pubkey, comment) = (endpoint.clone(), wg_pubkey.clone(), comment.clone());
+        let optional = |value: &str| Some(value.trim().to_string()).filter(|v| !v.is_empty());
+        Callback::from(move |_| {
+            on_submit.emit(PeeringSession {
+                id: id.clone(),
+                node: (*node).clone(),
+                name: name.trim().to_string(),
+                ipv4: optional(ipv4.as_str()),
+                ipv6: optional(ipv6.as_str()),
+                endpoint: endpoint.trim().to_string(),
+                wg_pubkey: optional(wg_pubkey.as_str()),
+                comment: optional(comment.as_str()),
+            });
+        })
+    };
+
+    html! {
+        <div class="autopeer-form">
+            <ShellLine>
+                <ShellPrompt>{"node"}</ShellPrompt>
+                {" "}
+                <ShellSelect value={(*node).clone()} on_change={on_node_change}>
+                    { for props.nodes.iter().map(|n| html! {
+                        <option value={n.clone()} selected={*n == *node}>{ n }</option>
+                    }) }
+                </ShellSelect>
+            </ShellLine>
+            <ShellLine>
+                <ShellPrompt>{"name"}</ShellPrompt>
+                {" "}
+                <ShellInput value={(*name).clone()} on_change={setter(&name)} placeholder="My peering" disabled={props.disabled} />
+            </ShellLine>
+            <ShellLine>
+                <ShellPrompt>{"ipv4"}</ShellPrompt>
+                {" "}
+                <ShellInput value={(*ipv4).clone()} on_change={setter(&ipv4)} placeholder="172.2x.x.x" disabled={props.disabled} />
+            </ShellLine>
+            <ShellLine>
+                <ShellPrompt>{"ipv6"}</ShellPrompt>
+                {" "}
+                <ShellInput value={(*ipv6).clone()} on_change={setter(&ipv6)} placeholder="fe80::xxxx" disabled={props.disabled} />
+            </ShellLine>
+            <ShellLine>
+                <ShellPrompt>{"endpoint"}</ShellPrompt>
+                {" "}
+                <ShellInput value={(*endpoint).clone()} on_change={setter(&endpoint)} placeholder="host:port" disabled={props.disabled} />
+            </ShellLine>
+            <ShellLine>
+                <ShellPrompt>{"wg_pubkey"}</ShellPrompt>
+                {" "}
+                <ShellInput value={(*wg_pubkey).clone()} on_change={setter(&wg_pubkey)} placeholder="(optional)" disabled={props.disabled} />
+            </ShellLine>
+            <ShellLine>
+                <ShellPrompt>{"comment"}</ShellPrompt>
+                {" "}
+                <ShellInput value={(*comment).clone()} on_change={setter(&comment)} placeholder="(optional)" disabled={props.disabled} />
+            </ShellLine>
+
+            <div class="autopeer-session-actions">
+                if let Some(on_cancel) = &props.on_cancel {
+                    <ShellButton text="Cancel" onclick={on_cancel.clone()} />
+                }
+                <ShellButton
+                    text={props.submit_text.clone()}
+                    onclick={on_submit}
+                    disabled={props.disabled || name.trim().is_empty() || node.is_empty()}
+                />
+            </div>
+        </div>
+    }
+}
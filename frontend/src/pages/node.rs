@@ -1,7 +1,8 @@
 use yew::prelude::*;
 
 use crate::components::{
-    protocols::Protocols, route_lookup::RouteLookup, traceroute::Traceroute, wireguard::WireGuard,
+    protocol_history::ProtocolTimeline, protocols::Protocols, route_lookup::RouteLookup,
+    traceroute::Traceroute, wireguard::WireGuard,
 };
 
 #[function_component(NodePage)]
@@ -10,6 +11,8 @@ pub fn node_page() -> Html {
         <>
             <Protocols/>
 
+            <ProtocolTimeline/>
+
             <WireGuard/>
 
             <Traceroute/>
@@ -1,3 +1,4 @@
 pub mod api;
+pub mod autopeer;
 pub mod response_handler;
 pub mod websocket;
@@ -1,4 +1,7 @@
-use common::api::{AppRequest, AppResponse};
+use common::{
+    api::{AppRequest, AppResponse},
+    history::ProtocolHistory,
+};
 use wasm_bindgen_futures::spawn_local;
 use yew::prelude::*;
 
@@ -44,7 +47,7 @@ pub fn perform_traceroute(
             spawn_local(async move {
                 match fetch_json::<AppResponse>(&url).await {
                     Ok(response) => {
-                        if let AppResponse::Error(err) = &response {
+                        if let AppResponse::Error { error: err } = &response {
                             state_clone.dispatch(Action::Traceroute(
                                 TracerouteAction::UpdateResult(
                                     node_name,
@@ -104,7 +107,7 @@ pub fn perform_route_lookup(
 
             match fetch_json::<AppResponse>(&url).await {
                 Ok(response) => {
-                    if let AppResponse::Error(err) = &response {
+                    if let AppResponse::Error { error: err } = &response {
                         state.dispatch(Action::Modal(ModalAction::UpdateContent(format!(
                             "Error: {}",
                             err
@@ -128,7 +131,7 @@ pub async fn get_protocols(state: &UseReducerHandle<crate::store::LgState>) -> R
     let url = format!("{}/api/protocols", state.backend_url.trim_end_matches('/'));
     match fetch_json::<AppResponse>(&url).await {
         Ok(response) => {
-            if let AppResponse::Error(e) = response {
+            if let AppResponse::Error { error: e } = response {
                 Err(e)
             } else {
                 crate::services::response_handler::handle_app_response(response, state);
@@ -145,7 +148,7 @@ pub async fn get_network_info(
     let url = format!("{}/api/info", state.backend_url.trim_end_matches('/'));
     match fetch_json::<AppResponse>(&url).await {
         Ok(response) => {
-            if let AppResponse::Error(e) = response {
+            if let AppResponse::Error { error: e } = response {
                 Err(e)
             } else {
                 crate::services::response_handler::handle_app_response(response, state);
@@ -156,6 +159,20 @@ pub async fn get_network_info(
     }
 }
 
+pub async fn get_protocol_history(
+    backend_url: &str,
+    node: &str,
+    protocol: &str,
+) -> Result<ProtocolHistory, String> {
+    let url = format!(
+        "{}/api/history/{}/{}",
+        backend_url.trim_end_matches('/'),
+        node,
+        protocol
+    );
+    fetch_json::<ProtocolHistory>(&url).await
+}
+
 pub fn get_protocol_details(
     state: &UseReducerHandle<crate::store::LgState>,
     node: String,
@@ -182,7 +199,7 @@ pub fn get_protocol_details(
 
             match fetch_json::<AppResponse>(&url).await {
                 Ok(response) => {
-                    if let AppResponse::Error(err) = &response {
+                    if let AppResponse::Error { error: err } = &response {
                         state.dispatch(Action::Modal(ModalAction::UpdateContent(format!(
                             "Error: {}",
                             err
@@ -0,0 +1,49 @@
+use common::auto_peer::{AutoPeerRequest, AutoPeerResponse};
+use reqwasm::http::Request;
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+
+use crate::store::auto_peer::{AutoPeerAction, AutoPeerState};
+
+pub async fn send_request(
+    url: &str,
+    token: Option<&str>,
+    request: &AutoPeerRequest,
+) -> Result<AutoPeerResponse, String> {
+    let body = serde_json::to_string(request).map_err(|e| e.to_string())?;
+
+    let mut req = Request::post(url)
+        .header("Content-Type", "application/json")
+        .body(body);
+    if let Some(token) = token {
+        req = req.header("Authorization", &format!("Bearer {}", token));
+    }
+
+    match req.send().await {
+        Ok(resp) if resp.ok() => resp
+            .json::<AutoPeerResponse>()
+            .await
+            .map_err(|e| format!("Failed to parse response: {}", e)),
+        Ok(resp) => Err(format!("HTTP request failed with status {}", resp.status())),
+        Err(e) => Err(format!("Request error: {}", e)),
+    }
+}
+
+/// Sends `request` in the background and feeds the response back into the
+/// AutoPeer reducer through `handler`.
+pub fn dispatch_request(
+    state: &UseReducerHandle<AutoPeerState>,
+    token: Option<String>,
+    request: AutoPeerRequest,
+    handler: fn(AutoPeerResponse) -> AutoPeerAction,
+) {
+    let state = state.clone();
+    state.dispatch(AutoPeerAction::SetLoading(true));
+
+    spawn_local(async move {
+        match send_request(&state.api_url, token.as_deref(), &request).await {
+            Ok(response) => state.dispatch(handler(response)),
+            Err(e) => state.dispatch(AutoPeerAction::SetError(Some(e))),
+        }
+    });
+}
@@ -1,6 +1,8 @@
 use common::api::AppResponse;
 
-use crate::store::{Action, LgStateHandle, TracerouteResult, traceroute::TracerouteAction};
+use crate::store::{
+    Action, LgStateHandle, TracerouteResult, modal::ModalAction, traceroute::TracerouteAction,
+};
 
 pub fn handle_app_response(response: AppResponse, state: &LgStateHandle) {
     match response {
@@ -34,6 +36,9 @@ pub fn handle_app_response(response: AppResponse, state: &LgStateHandle) {
         AppResponse::RouteLookupUpdate { node: _, lines } => {
             state.dispatch(Action::RouteLookupUpdate(lines));
         }
+        AppResponse::RouteLookupRoutes { node: _, routes } => {
+            state.dispatch(Action::RouteLookupRoutes(routes));
+        }
         AppResponse::ProtocolDetailsInit {
             node: _,
             protocol: _,
@@ -47,14 +52,36 @@ pub fn handle_app_response(response: AppResponse, state: &LgStateHandle) {
         } => {
             state.dispatch(Action::ProtocolDetailsUpdate(lines));
         }
+        AppResponse::ProtocolDetailsParsed {
+            node: _,
+            protocol: _,
+            details,
+        } => {
+            if let Some(details) = details {
+                state.dispatch(Action::ProtocolDetailsParsed(details));
+            }
+        }
         AppResponse::WireGuard { data } => {
             state.dispatch(Action::SetWireGuard(data));
         }
+        AppResponse::WireGuardDiff { data } => {
+            state.dispatch(Action::ApplyWireGuardDiff(data));
+        }
+        AppResponse::Tunnels { data } => {
+            state.dispatch(Action::SetTunnels(data));
+        }
         AppResponse::NetworkInfo(info) => {
             state.dispatch(Action::SetNetworkInfo(info));
         }
-        AppResponse::Error(e) => {
+        AppResponse::Error { error: e } => {
             tracing::error!("AppResponse Error: {}", e);
+            // Route lookups and protocol details report failures in their modal.
+            if state.modal.active {
+                state.dispatch(Action::Modal(ModalAction::UpdateContent(format!(
+                    "Error: {}",
+                    e
+                ))));
+            }
         }
     }
 }
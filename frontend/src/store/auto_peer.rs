@@ -15,11 +15,14 @@ pub enum AutoPeerStep {
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct AutoPeerState {
+    pub api_url: String,
+    pub nodes: Vec<String>,
     pub step: AutoPeerStep,
     pub asn: String,
     pub challenge_methods: Vec<ChallengeMethod>,
     pub selected_method: Option<ChallengeMethod>,
     pub challenge_text: Option<String>,
+    pub token: Option<String>,
     pub credential: Option<String>,
     pub sessions: Vec<PeeringSession>,
     pub error: Option<String>,
@@ -29,11 +32,14 @@ pub struct AutoPeerState {
 impl Default for AutoPeerState {
     fn default() -> Self {
         Self {
+            api_url: String::new(),
+            nodes: Vec::new(),
             step: AutoPeerStep::EnterAsn,
             asn: String::new(),
             challenge_methods: Vec::new(),
             selected_method: None,
             challenge_text: None,
+            token: None,
             credential: None,
             sessions: Vec::new(),
             error: None,
@@ -44,6 +50,7 @@ impl Default for AutoPeerState {
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum AutoPeerAction {
+    Configure { api_url: String, nodes: Vec<String> },
     SetAsn(String),
     SetLoading(bool),
     SetError(Option<String>),
@@ -55,6 +62,7 @@ pub enum AutoPeerAction {
     SetEmailCode(String),
     HandleVerifyResponse(AutoPeerResponse),
     HandleSessionsUpdate(Vec<PeeringSession>),
+    HandleSessionsResponse(AutoPeerResponse),
     Reset,
 }
 
@@ -65,6 +73,10 @@ impl Reducible for AutoPeerState {
         let mut state = (*self).clone();
 
         match action {
+            AutoPeerAction::Configure { api_url, nodes } => {
+                state.api_url = api_url;
+                state.nodes = nodes;
+            }
             AutoPeerAction::SetAsn(asn) => {
                 state.asn = asn;
             }
@@ -78,7 +90,11 @@ impl Reducible for AutoPeerState {
             AutoPeerAction::HandleInitResponse(response) => {
                 state.loading = false;
                 match response {
-                    AutoPeerResponse::InitSuccess { challenge_methods } => {
+                    AutoPeerResponse::InitSuccess {
+                        token,
+                        challenge_methods,
+                    } => {
+                        state.token = Some(token);
                         state.challenge_methods = challenge_methods;
                         state.step = AutoPeerStep::SelectChallenge;
                         state.error = None;
@@ -132,8 +148,28 @@ impl Reducible for AutoPeerState {
                 state.sessions = sessions;
                 state.loading = false;
             }
+            AutoPeerAction::HandleSessionsResponse(response) => {
+                state.loading = false;
+                match response {
+                    AutoPeerResponse::SessionsUpdate { sessions } => {
+                        state.sessions = sessions;
+                        state.error = None;
+                    }
+                    AutoPeerResponse::OperationSuccess { .. } => {
+                        state.error = None;
+                    }
+                    AutoPeerResponse::OperationError { error } => {
+                        state.error = Some(error);
+                    }
+                    _ => {}
+                }
+            }
             AutoPeerAction::Reset => {
-                state = Self::default();
+                state = Self {
+                    api_url: state.api_url,
+                    nodes: state.nodes,
+                    ..Self::default()
+                };
             }
             _ => {}
         }
@@ -3,12 +3,17 @@ use std::{collections::HashMap, rc::Rc};
 use chrono::{DateTime, Utc};
 use common::{
     api::AppRequest,
-    models::{DiffOp, NetworkInfo, NodeProtocol, NodeStatusDiff, NodeWireGuard, PeeringInfo},
+    models::{
+        DiffOp, NetworkInfo, NodeProtocol, NodeStatusDiff, NodeTunnels, NodeWireGuard,
+        NodeWireGuardDiff, PeeringInfo,
+    },
+    protocol::ProtocolDetails,
+    route::RouteEntry,
 };
 use yew::prelude::*;
 
 use super::{
-    modal::{ModalAction, ModalState},
+    modal::{ModalAction, ModalState, ModalView},
     traceroute::{TracerouteAction, TracerouteState},
 };
 
@@ -18,6 +23,7 @@ pub type LgStateHandle = UseReducerHandle<LgState>;
 pub struct LgState {
     pub nodes: Vec<NodeProtocol>,
     pub wireguard: Vec<NodeWireGuard>,
+    pub tunnels: Vec<NodeTunnels>,
     pub peering: HashMap<String, PeeringInfo>,
     pub modal: ModalState,
     pub error: Option<String>,
@@ -27,12 +33,15 @@ pub struct LgState {
     pub network_info: Option<NetworkInfo>,
     pub username: String,
     pub backend_url: String,
+    pub autopeer_url: Option<String>,
     pub ws_sender: Option<Callback<AppRequest>>,
 }
 
 pub enum Action {
     SetNodes(Vec<NodeProtocol>),
     SetWireGuard(Vec<NodeWireGuard>),
+    ApplyWireGuardDiff(Vec<NodeWireGuardDiff>),
+    SetTunnels(Vec<NodeTunnels>),
     SetError(String),
     Modal(ModalAction),
     Traceroute(TracerouteAction),
@@ -40,6 +49,7 @@ pub enum Action {
     SetConfig {
         username: String,
         backend_url: String,
+        autopeer_url: Option<String>,
     },
     SetWsSender(Callback<AppRequest>),
     ClearWsSender,
@@ -47,8 +57,10 @@ pub enum Action {
     ApplyDiff(Vec<NodeStatusDiff>),
     RouteLookupInit(String),
     RouteLookupUpdate(Vec<String>),
+    RouteLookupRoutes(Vec<RouteEntry>),
     ProtocolDetailsInit(String),
     ProtocolDetailsUpdate(Vec<String>),
+    ProtocolDetailsParsed(Box<ProtocolDetails>),
 }
 
 impl Reducible for LgState {
@@ -66,6 +78,26 @@ impl Reducible for LgState {
             Action::SetWireGuard(wireguard) => {
                 next_state.wireguard = wireguard;
             }
+            Action::ApplyWireGuardDiff(diffs) => {
+                for diff in diffs {
+                    let index = match next_state.wireguard.iter().position(|n| n.name == diff.n) {
+                        Some(index) => index,
+                        None => {
+                            next_state.wireguard.push(NodeWireGuard {
+                                name: diff.n.clone(),
+                                interfaces: Vec::new(),
+                                last_updated: diff.u,
+                                error: None,
+                            });
+                            next_state.wireguard.len() - 1
+                        }
+                    };
+                    diff.apply(&mut next_state.wireguard[index]);
+                }
+            }
+            Action::SetTunnels(tunnels) => {
+                next_state.tunnels = tunnels;
+            }
             Action::SetError(err) => {
                 next_state.error = Some(err);
             }
@@ -81,9 +113,11 @@ impl Reducible for LgState {
             Action::SetConfig {
                 username,
                 backend_url,
+                autopeer_url,
             } => {
                 next_state.username = username;
                 next_state.backend_url = backend_url;
+                next_state.autopeer_url = autopeer_url;
                 next_state.config_ready = true;
             }
             Action::SetWsSender(sender) => {
@@ -137,16 +171,24 @@ impl Reducible for LgState {
             }
             Action::RouteLookupInit(result) => {
                 next_state.modal.content = result;
+                next_state.modal.view = None;
             }
             Action::RouteLookupUpdate(lines) => {
                 next_state.modal.content = self.modal.content.clone() + "\n" + &lines.join("\n");
             }
+            Action::RouteLookupRoutes(routes) => {
+                next_state.modal.view = Some(ModalView::Routes(routes));
+            }
             Action::ProtocolDetailsInit(result) => {
                 next_state.modal.content = result;
+                next_state.modal.view = None;
             }
             Action::ProtocolDetailsUpdate(lines) => {
                 next_state.modal.content = self.modal.content.clone() + "\n" + &lines.join("\n");
             }
+            Action::ProtocolDetailsParsed(details) => {
+                next_state.modal.view = Some(ModalView::Protocol(details));
+            }
         }
 
         Rc::new(next_state)
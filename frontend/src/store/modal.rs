@@ -1,8 +1,18 @@
+use common::{protocol::ProtocolDetails, route::RouteEntry};
+
 #[derive(Clone, Debug, PartialEq, Default)]
 pub struct ModalState {
     pub active: bool,
     pub content: String,
     pub command: Option<String>,
+    pub view: Option<ModalView>,
+}
+
+/// Structured rendering of the modal content, shown above the raw output.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ModalView {
+    Routes(Vec<RouteEntry>),
+    Protocol(Box<ProtocolDetails>),
 }
 
 pub enum ModalAction {
@@ -21,6 +31,7 @@ impl ModalState {
                 self.active = true;
                 self.content = content;
                 self.command = command;
+                self.view = None;
             }
             ModalAction::UpdateContent(content) => {
                 self.content = content;
@@ -29,6 +40,7 @@ impl ModalState {
                 self.active = false;
                 self.content = String::new();
                 self.command = None;
+                self.view = None;
             }
         }
     }
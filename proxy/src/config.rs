@@ -1,10 +1,11 @@
 use std::{
+    collections::HashMap,
     net::{IpAddr, SocketAddr},
     path::Path,
 };
 
 use anyhow::{Context, anyhow};
-use common::utils::deserialize_listen_address;
+use common::{models::WireGuardPeer, utils::deserialize_listen_address};
 use serde::{Deserialize, Deserializer, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -29,6 +30,14 @@ pub struct PeeringInfo {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
     pub bind_socket: String,
+    /// Further bird instances (e.g. one per VRF), selected per request by
+    /// name. Like `bind_socket`, endpoints are Unix socket paths or
+    /// `address:port` for TCP.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub bird_instances: HashMap<String, String>,
+    /// Limits that apply to the pool of each bird instance.
+    #[serde(default)]
+    pub bird_pool: BirdPoolConfig,
     #[serde(deserialize_with = "deserialize_listen_address")]
     pub listen: Vec<String>,
     allowed_ips: Vec<String>,
@@ -42,6 +51,80 @@ pub struct Config {
     pub peering: Option<PeeringInfo>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub wireguard_command: Option<String>,
+    /// Peer fields hidden from `/wireguard`, endpoints and public keys unless
+    /// set. `[]` exposes them all. Private and preshared keys are never
+    /// exposed.
+    #[serde(default = "default_wireguard_redact")]
+    pub wireguard_redact: Vec<WireGuardField>,
+    /// WireGuard interface of each BGP protocol whose session can't be
+    /// matched by name or neighbor scope, keyed by protocol name.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub wireguard_protocols: HashMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provision: Option<ProvisionConfig>,
+}
+
+/// Limits of the pool of restricted bird sessions shared by all requests.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct BirdPoolConfig {
+    /// Most sessions in use at once; further requests queue for a free one.
+    pub max_size: usize,
+    /// Seconds a queued request waits for a session before failing.
+    pub acquire_timeout: u64,
+    /// Seconds an unused session is kept open.
+    pub idle_timeout: u64,
+}
+
+impl Default for BirdPoolConfig {
+    fn default() -> Self {
+        Self {
+            max_size: 8,
+            acquire_timeout: 10,
+            idle_timeout: 300,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WireGuardField {
+    PublicKey,
+    Endpoint,
+    AllowedIps,
+}
+
+impl WireGuardField {
+    pub fn redact(self, peer: &mut WireGuardPeer) {
+        match self {
+            WireGuardField::PublicKey => peer.public_key = None,
+            WireGuardField::Endpoint => peer.endpoint = None,
+            WireGuardField::AllowedIps => peer.allowed_ips.clear(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProvisionConfig {
+    /// Expected in the `x-provision-secret` header of `/provision`. The
+    /// `shared_secret` only grants the read-only endpoints, so it must differ.
+    pub secret: String,
+    pub bird_dir: String,
+    pub wireguard_dir: String,
+    #[serde(deserialize_with = "deserialize_template")]
+    pub bird_template: String,
+    #[serde(deserialize_with = "deserialize_template")]
+    pub wireguard_template: String,
+    #[serde(default = "default_interface_prefix")]
+    pub interface_prefix: String,
+}
+
+fn default_wireguard_redact() -> Vec<WireGuardField> {
+    vec![WireGuardField::Endpoint, WireGuardField::PublicKey]
+}
+
+fn default_interface_prefix() -> String {
+    "dn42_".to_string()
 }
 
 impl Config {
@@ -65,10 +148,27 @@ impl Config {
     pub fn validated(mut self) -> anyhow::Result<Self> {
         let mut errors: Vec<String> = Vec::new();
 
-        self.validate_endpoint("bind_socket", &mut errors);
+        self.validate_endpoint("bind_socket", &self.bind_socket, &mut errors);
+        for (name, endpoint) in &self.bird_instances {
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                errors.push(format!(
+                    "bird_instances name '{}' must be non-empty [A-Za-z0-9_-]",
+                    name
+                ));
+            }
+            self.validate_endpoint(&format!("bird_instances.{}", name), endpoint, &mut errors);
+        }
+        if self.bird_pool.max_size == 0 {
+            errors.push("bird_pool.max_size must be at least 1".to_string());
+        }
         self.validate_listen(&mut errors);
         self.validate_allowed_ips(&mut errors);
         self.validate_traceroute_bin(&mut errors);
+        self.validate_provision(&mut errors);
 
         if errors.is_empty() {
             Ok(self)
@@ -80,8 +180,7 @@ impl Config {
         }
     }
 
-    fn validate_endpoint(&self, name: &str, errors: &mut Vec<String>) {
-        let val = self.bind_socket.as_str();
+    fn validate_endpoint(&self, name: &str, val: &str, errors: &mut Vec<String>) {
         if val.parse::<SocketAddr>().is_ok() {
             return;
         }
@@ -171,6 +270,40 @@ impl Config {
             errors.push("traceroute_args is set but traceroute_bin isn't".to_string());
         }
     }
+
+    fn validate_provision(&self, errors: &mut Vec<String>) {
+        let Some(provision) = &self.provision else {
+            return;
+        };
+
+        for (name, dir) in [
+            ("provision.bird_dir", &provision.bird_dir),
+            ("provision.wireguard_dir", &provision.wireguard_dir),
+        ] {
+            if !Path::new(dir).is_dir() {
+                errors.push(format!("{} '{}' is not a directory", name, dir));
+            }
+        }
+
+        if provision.secret.is_empty() {
+            errors.push("provision.secret must not be empty".to_string());
+        } else if self.shared_secret.as_ref() == Some(&provision.secret) {
+            errors.push("provision.secret must differ from shared_secret".to_string());
+        }
+
+        let prefix = &provision.interface_prefix;
+        if prefix.is_empty()
+            || prefix.len() > 11
+            || !prefix
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            errors.push(format!(
+                "provision.interface_prefix '{}' must be 1-11 characters of [A-Za-z0-9_-]",
+                prefix
+            ));
+        }
+    }
 }
 
 pub fn deserialize_wg_pubkey<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
@@ -198,6 +331,21 @@ where
     }
 }
 
+pub fn deserialize_template<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+
+    let s = String::deserialize(deserializer)?;
+    if s.starts_with('/') || s.starts_with("./") || s.starts_with("../") {
+        std::fs::read_to_string(&s)
+            .map_err(|e| Error::custom(format!("Failed to read template from '{}': {}", s, e)))
+    } else {
+        Ok(s)
+    }
+}
+
 // FIXME maybe called split something
 pub fn deserialize_traceroute_args<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
 where
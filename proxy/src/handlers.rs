@@ -1,4 +1,6 @@
 pub mod bird;
+pub mod metrics;
 pub mod peering;
+pub mod provision;
 pub mod traceroute;
 pub mod wireguard;
@@ -1,38 +1,183 @@
-use std::sync::Arc;
-
-use axum::{body::Body, extract::Extension, response::IntoResponse};
-use tokio::io::AsyncWriteExt;
-use tokio_util::codec::Framed;
-use tracing::{error, info};
+use axum::{
+    Json,
+    body::Body,
+    extract::{Extension, Query},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use common::command::{BirdCommand, BirdError, BirdErrorKind};
+use serde::Deserialize;
+use tokio_stream::{self as stream, StreamExt};
+use tracing::{error, info, warn};
 
 use crate::{
-    config::Config,
-    services::bird::{BirdDecoder, BirdStream, connect},
+    metrics::Metrics,
+    services::bird::{BirdInstances, BirdStream},
 };
 
-pub async fn handler(Extension(config): Extension<Arc<Config>>, body: String) -> impl IntoResponse {
-    let mut stream = match connect(&config.bind_socket).await {
-        Ok(s) => s,
+#[derive(Deserialize)]
+pub struct BirdQuery {
+    /// One of `bird_instances`, the instance at `bind_socket` if not set.
+    instance: Option<String>,
+}
+
+pub async fn handler(
+    Extension(instances): Extension<BirdInstances>,
+    Extension(metrics): Extension<Metrics>,
+    Query(params): Query<BirdQuery>,
+    body: String,
+) -> Response {
+    let Some(pool) = instances.get(params.instance.as_deref()) else {
+        warn!(instance = ?params.instance, "Unknown bird instance");
+        return (StatusCode::BAD_REQUEST, "Unknown bird instance").into_response();
+    };
+
+    let command = match parse_command(&body) {
+        Ok(command) => command,
         Err(e) => {
-            error!(error = %e, "Failed to connect to bird socket");
-            return Body::from(e.to_string());
+            warn!(error = %e, "Rejected bird command");
+            metrics.bird_command_rejected();
+            return (StatusCode::BAD_REQUEST, e).into_response();
         }
     };
 
-    let body = if body.ends_with('\n') {
-        body
-    } else {
-        format!("{}\n", body)
+    let mut connection = match pool.get().await {
+        Ok(c) => c,
+        Err(e) => {
+            error!(error = %e, "Failed to get bird session");
+            return (StatusCode::SERVICE_UNAVAILABLE, e.to_string()).into_response();
+        }
     };
-    info!("Proxying bird request: {}", body.trim_end());
 
-    if let Err(e) = stream.write_all(body.as_bytes()).await {
+    let command = command.to_string();
+    info!("Proxying bird request: {}", command);
+    let timer = metrics.bird_command(&command);
+
+    if let Err(e) = connection.send(&command).await {
         error!(error = %e, "Failed to write bird request");
-        return Body::from(e.to_string());
+        return (StatusCode::BAD_GATEWAY, e.to_string()).into_response();
     }
 
-    Body::from_stream(BirdStream {
-        inner: Framed::new(stream, BirdDecoder::default()),
-        done: false,
-    })
+    // Bird usually replies with an error right away, so the first chunk
+    // decides the status code. Errors after some output can't change it
+    // anymore and end the output with a trailer line instead.
+    let mut reply = BirdStream::new(connection);
+    let first = match reply.next().await {
+        Some(Err(e)) => return error_response(e),
+        first => first,
+    };
+
+    // The timer records the duration once the body is fully sent and dropped.
+    Body::from_stream(stream::iter(first).chain(reply).map(move |line| {
+        let _ = &timer;
+        line.or_else(
+            |e| match e.get_ref().and_then(|e| e.downcast_ref::<BirdError>()) {
+                Some(error) => {
+                    warn!(
+                        code = error.code,
+                        "bird reply ended with an error: {}", error.message
+                    );
+                    Ok(error.trailer())
+                }
+                None => Err(e),
+            },
+        )
+    }))
+    .into_response()
+}
+
+fn error_response(e: std::io::Error) -> Response {
+    let Some(error) = e.get_ref().and_then(|e| e.downcast_ref::<BirdError>()) else {
+        error!(error = %e, "Failed to read bird reply");
+        return (StatusCode::BAD_GATEWAY, e.to_string()).into_response();
+    };
+
+    info!(
+        code = error.code,
+        "bird replied with an error: {}", error.message
+    );
+    let status = match error.kind() {
+        BirdErrorKind::NotFound => StatusCode::NOT_FOUND,
+        BirdErrorKind::AccessDenied => StatusCode::FORBIDDEN,
+        BirdErrorKind::InvalidCommand => StatusCode::BAD_REQUEST,
+        BirdErrorKind::Failed => StatusCode::BAD_GATEWAY,
+    };
+    (status, Json(error.clone())).into_response()
+}
+
+/// Reads a JSON [`BirdCommand`], or for older servers the text form of one.
+fn parse_command(body: &str) -> Result<BirdCommand, String> {
+    if !body.trim_start().starts_with('{') {
+        return body.parse();
+    }
+    let command: BirdCommand =
+        serde_json::from_str(body).map_err(|e| format!("Invalid command: {}", e))?;
+    command.validate()?;
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::to_bytes;
+
+    use super::*;
+    use crate::services::{bird::tests::fake_bird, provision::tests::test_dir};
+
+    async fn run(name: &str, reply: fn(&str) -> &'static str, body: &str) -> (StatusCode, String) {
+        let path = fake_bird(name, reply).await;
+        let dir = test_dir(name);
+        let config = crate::handlers::provision::tests::config(&dir, &path);
+        let metrics = Metrics::new();
+        let instances = BirdInstances::new(&config, &metrics);
+
+        let response = handler(
+            Extension(instances),
+            Extension(metrics),
+            Query(BirdQuery { instance: None }),
+            body.to_string(),
+        )
+        .await;
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn error_before_output_sets_the_status() {
+        let (status, body) = run(
+            "handler-error",
+            |_| "8001 Network not found\n",
+            r#"{"command":"show_route","prefix":"10.0.0.0/8","all":false}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let error: BirdError = serde_json::from_str(&body).unwrap();
+        assert_eq!(error.code, 8001);
+    }
+
+    #[tokio::test]
+    async fn error_after_output_ends_with_a_trailer() {
+        let (status, body) = run(
+            "handler-trailer",
+            |_| "1007-Table master4:\n 10.0.0.0/8 unicast [peer1]\n8001 Network not found\n",
+            r#"{"command":"show_route","prefix":"10.0.0.0/8","all":false}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let lines: Vec<&str> = body.lines().collect();
+        assert_eq!(
+            lines[..2],
+            ["Table master4:", " 10.0.0.0/8 unicast [peer1]"]
+        );
+        assert_eq!(
+            BirdError::from_trailer(lines[2]),
+            Some(BirdError {
+                code: 8001,
+                message: "Network not found".to_string(),
+            })
+        );
+        assert_eq!(lines.len(), 3);
+    }
 }
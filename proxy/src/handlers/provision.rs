@@ -0,0 +1,171 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::Extension,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use common::auto_peer::{ProvisionRequest, ProvisionResponse};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::Config,
+    services::{bird, provision},
+};
+
+pub async fn provision(
+    Extension(config): Extension<Arc<Config>>,
+    Json(req): Json<ProvisionRequest>,
+) -> Response {
+    let Some(provision_config) = &config.provision else {
+        return (StatusCode::NOT_FOUND, "Provisioning is not configured").into_response();
+    };
+
+    let files = match provision::render(provision_config, req.asn, &req.session) {
+        Ok(files) => files,
+        Err(e) => {
+            warn!(asn = req.asn, "Rejected provisioning request: {}", e);
+            return (StatusCode::BAD_REQUEST, e).into_response();
+        }
+    };
+
+    if req.dry_run {
+        return Json(ProvisionResponse {
+            files,
+            applied: false,
+            bird_output: None,
+        })
+        .into_response();
+    }
+
+    info!(
+        asn = req.asn,
+        "Provisioning peering session {}", req.session.name
+    );
+    let previous = match provision::write(&files) {
+        Ok(previous) => previous,
+        Err(e) => {
+            error!(asn = req.asn, "Failed to write provisioned files: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+        }
+    };
+
+    match bird::configure(&config.bind_socket).await {
+        Ok(output) => Json(ProvisionResponse {
+            files,
+            applied: true,
+            bird_output: Some(output),
+        })
+        .into_response(),
+        Err(e) => {
+            error!(asn = req.asn, error = %e, "Failed to reconfigure bird, restoring previous files");
+            provision::restore(&previous);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{fs, path::Path};
+
+    use axum::body::to_bytes;
+    use serde_json::json;
+
+    use super::*;
+    use crate::services::{
+        bird::tests::fake_bird,
+        provision::tests::{BIRD_TEMPLATE, WIREGUARD_TEMPLATE, session, test_dir},
+    };
+
+    pub(crate) fn config(dir: &Path, bind_socket: &str) -> Arc<Config> {
+        let config: Config = serde_json::from_value(json!({
+            "bind_socket": bind_socket,
+            "listen": "127.0.0.1:8000",
+            "allowed_ips": ["127.0.0.1"],
+            "shared_secret": "shared-secret",
+            "traceroute_bin": null,
+            "provision": {
+                "secret": "provision-secret",
+                "bird_dir": dir.join("bird"),
+                "wireguard_dir": dir.join("wireguard"),
+                "bird_template": BIRD_TEMPLATE,
+                "wireguard_template": WIREGUARD_TEMPLATE,
+            },
+        }))
+        .unwrap();
+        Arc::new(config.validated().unwrap())
+    }
+
+    async fn provision_with(config: Arc<Config>, dry_run: bool) -> (StatusCode, String) {
+        let request = ProvisionRequest {
+            asn: 4242420001,
+            session: session(),
+            dry_run,
+        };
+        let response = provision(Extension(config), Json(request)).await;
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn dry_run_writes_nothing() {
+        let dir = test_dir("dry-run");
+        let socket = fake_bird("provision-dry-run", |_| panic!("bird was reloaded")).await;
+
+        let (status, body) = provision_with(config(&dir, &socket), true).await;
+        assert_eq!(status, StatusCode::OK);
+        let response: ProvisionResponse = serde_json::from_str(&body).unwrap();
+        assert!(!response.applied);
+        assert_eq!(response.files.len(), 2);
+        assert!(response.files.iter().all(|f| !Path::new(&f.path).exists()));
+
+        fs::remove_dir_all(dir).unwrap();
+        fs::remove_file(socket).unwrap();
+    }
+
+    #[tokio::test]
+    async fn applies_and_reloads() {
+        let dir = test_dir("apply");
+        let socket = fake_bird("provision-apply", |command| match command {
+            "configure" => "0003 Reconfigured\n",
+            _ => "9001 syntax error\n",
+        })
+        .await;
+
+        let (status, body) = provision_with(config(&dir, &socket), false).await;
+        assert_eq!(status, StatusCode::OK);
+        let response: ProvisionResponse = serde_json::from_str(&body).unwrap();
+        assert!(response.applied);
+        assert_eq!(response.bird_output.as_deref(), Some("Reconfigured\n"));
+        for file in &response.files {
+            assert_eq!(fs::read_to_string(&file.path).unwrap(), file.content);
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+        fs::remove_file(socket).unwrap();
+    }
+
+    #[tokio::test]
+    async fn restores_files_when_reload_fails() {
+        let dir = test_dir("reload-fails");
+        let socket = fake_bird(
+            "provision-reload-fails",
+            |_| "8002 /etc/bird/peers/dn42_0001.conf:2:5 syntax error\n",
+        )
+        .await;
+        let existing = dir.join("bird/dn42_0001.conf");
+        fs::write(&existing, "old bird config\n").unwrap();
+
+        let (status, body) = provision_with(config(&dir, &socket), false).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(body.contains("syntax error"), "{}", body);
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "old bird config\n");
+        assert!(!dir.join("wireguard/dn42_0001.conf").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+        fs::remove_file(socket).unwrap();
+    }
+}
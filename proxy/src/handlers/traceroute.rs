@@ -15,6 +15,7 @@ use tracing::{error, info, warn};
 
 use crate::{
     config::Config,
+    metrics::Metrics,
     services::traceroute::{IpVersion, build_traceroute_command},
 };
 
@@ -25,27 +26,31 @@ pub struct TracerouteQuery {
 
 pub async fn traceroute(
     Extension(config): Extension<Arc<Config>>,
+    Extension(metrics): Extension<Metrics>,
     Query(params): Query<TracerouteQuery>,
 ) -> Response {
-    run_traceroute(config, params, IpVersion::Any).await
+    run_traceroute(config, metrics, params, IpVersion::Any).await
 }
 
 pub async fn traceroute4(
     Extension(config): Extension<Arc<Config>>,
+    Extension(metrics): Extension<Metrics>,
     Query(params): Query<TracerouteQuery>,
 ) -> Response {
-    run_traceroute(config, params, IpVersion::V4).await
+    run_traceroute(config, metrics, params, IpVersion::V4).await
 }
 
 pub async fn traceroute6(
     Extension(config): Extension<Arc<Config>>,
+    Extension(metrics): Extension<Metrics>,
     Query(params): Query<TracerouteQuery>,
 ) -> Response {
-    run_traceroute(config, params, IpVersion::V6).await
+    run_traceroute(config, metrics, params, IpVersion::V6).await
 }
 
 async fn run_traceroute(
     config: Arc<Config>,
+    metrics: Metrics,
     params: TracerouteQuery,
     version: IpVersion,
 ) -> Response {
@@ -72,6 +77,7 @@ async fn run_traceroute(
     };
 
     info!(%target, version = ?version, "Executing traceroute");
+    let timer = metrics.traceroute(version.as_str());
     match cmd.spawn() {
         Ok(mut child) => {
             let mut stderr = child.stderr.take();
@@ -84,7 +90,9 @@ async fn run_traceroute(
                         let combined_stream = tokio_stream::iter(vec![first_line]).chain(lines);
                         let stream_target = target.clone();
 
-                        let text_stream = combined_stream.map(move |line| match line {
+                        let text_stream = combined_stream.map(move |line| {
+                            let _ = &timer;
+                            match line {
                             Ok(mut raw_line) => {
                                 if !raw_line.ends_with('\n') {
                                     raw_line.push('\n');
@@ -95,6 +103,7 @@ async fn run_traceroute(
                                 error!(error = %e, %stream_target, "Failed to read traceroute output");
                                 Ok(String::new())
                             }
+                            }
                         });
 
                         Body::from_stream(text_stream).into_response()
@@ -1,16 +1,43 @@
 use std::sync::Arc;
 
 use axum::{
-    extract::Extension,
+    Json,
+    extract::{Extension, Query},
     http::StatusCode,
     response::{IntoResponse, Response},
 };
+use common::{
+    models::WireGuardInterface,
+    wireguard::{parse_wireguard_dump, protocols_for_interface},
+};
+use serde::Deserialize;
 use tokio::process::Command;
 use tracing::{error, info};
 
-use crate::config::Config;
+use crate::{config::Config, metrics::Metrics, services::bird::BirdInstances};
+
+#[derive(Deserialize)]
+pub struct WireGuardQuery {
+    #[serde(default)]
+    format: WireGuardFormat,
+}
+
+#[derive(Deserialize, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum WireGuardFormat {
+    /// Peer lines of `wg show all dump`, which servers before the JSON
+    /// format expect.
+    #[default]
+    Text,
+    Json,
+}
 
-pub async fn get_wireguard(Extension(config): Extension<Arc<Config>>) -> Response {
+pub async fn get_wireguard(
+    Extension(config): Extension<Arc<Config>>,
+    Extension(metrics): Extension<Metrics>,
+    Extension(instances): Extension<BirdInstances>,
+    Query(params): Query<WireGuardQuery>,
+) -> Response {
     info!("Getting WireGuard status");
 
     let (program, args) = if let Some(cmd) = &config.wireguard_command {
@@ -19,13 +46,14 @@ pub async fn get_wireguard(Extension(config): Extension<Arc<Config>>) -> Respons
         let args: Vec<&str> = parts.collect();
         (program.to_string(), args)
     } else {
-        ("wg".to_string(), vec!["show", "dump"])
+        ("wg".to_string(), vec!["show", "all", "dump"])
     };
 
     let output = match Command::new(&program).args(&args).output().await {
         Ok(output) => output,
         Err(e) => {
             error!(error = %e, "Failed to execute wireguard command: {} {:?}", program, args);
+            metrics.wireguard_failed("spawn");
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 format!("Failed to execute wireguard command: {}", e),
@@ -37,6 +65,7 @@ pub async fn get_wireguard(Extension(config): Extension<Arc<Config>>) -> Respons
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
         error!("WireGuard command failed: {}", stderr);
+        metrics.wireguard_failed("exit_status");
         return (
             StatusCode::INTERNAL_SERVER_ERROR,
             format!("WireGuard command failed: {}", stderr),
@@ -44,20 +73,76 @@ pub async fn get_wireguard(Extension(config): Extension<Arc<Config>>) -> Respons
             .into_response();
     }
 
-    String::from_utf8_lossy(&output.stdout)
-        .lines()
-        .skip(1)
-        .step_by(2)
-        .filter_map(|line| {
-            let mut fields: Vec<&str> = line.split('\t').collect();
-            if fields.len() == 9 {
-                fields[2] = "(redacted)";
-                Some(fields.join("\t"))
-            } else {
-                None
+    // `wg show <interface> dump` leaves the interface name out of its lines.
+    let interface = match args.as_slice() {
+        [.., "show", name, "dump"] if *name != "all" => Some(*name),
+        _ => None,
+    };
+    let mut interfaces =
+        match parse_wireguard_dump(&String::from_utf8_lossy(&output.stdout), interface) {
+            Ok(interfaces) => interfaces,
+            Err(e) => {
+                error!(error = %e, "Failed to parse WireGuard dump");
+                metrics.wireguard_failed("parse");
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to parse WireGuard dump: {}", e),
+                )
+                    .into_response();
+            }
+        };
+    for peer in interfaces.iter_mut().flat_map(|i| i.peers.iter_mut()) {
+        for field in &config.wireguard_redact {
+            field.redact(peer);
+        }
+    }
+
+    if params.format == WireGuardFormat::Text {
+        return peer_lines(&interfaces).into_response();
+    }
+
+    // The mapping is best effort, WireGuard data is still useful without it.
+    let protocols = instances.bgp_protocols().await;
+    for interface in &mut interfaces {
+        interface.protocols = protocols_for_interface(&interface.name, &protocols);
+    }
+    // Explicit mappings take precedence over the ones found above.
+    for (protocol, interface_name) in &config.wireguard_protocols {
+        for interface in &mut interfaces {
+            interface.protocols.retain(|p| p != protocol);
+            if &interface.name == interface_name {
+                interface.protocols.push(protocol.clone());
             }
+        }
+    }
+
+    Json(interfaces).into_response()
+}
+
+fn peer_lines(interfaces: &[WireGuardInterface]) -> String {
+    let or_none = |value: &Option<String>| value.clone().unwrap_or_else(|| "(none)".to_string());
+    interfaces
+        .iter()
+        .flat_map(|i| i.peers.iter().map(move |p| (i, p)))
+        .map(|(interface, peer)| {
+            [
+                interface.name.clone(),
+                or_none(&peer.public_key),
+                "(redacted)".to_string(),
+                or_none(&peer.endpoint),
+                if peer.allowed_ips.is_empty() {
+                    "(none)".to_string()
+                } else {
+                    peer.allowed_ips.join(",")
+                },
+                peer.latest_handshake.unwrap_or(0).to_string(),
+                peer.rx_bytes.to_string(),
+                peer.tx_bytes.to_string(),
+                peer.persistent_keepalive
+                    .map_or_else(|| "off".to_string(), |k| k.to_string()),
+            ]
+            .join("\t")
         })
         .collect::<Vec<_>>()
         .join("\n")
-        .into_response()
 }
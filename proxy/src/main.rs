@@ -10,11 +10,17 @@ use tokio::net::TcpListener;
 use tower_http::cors::CorsLayer;
 use tracing::info;
 
-use crate::{cli::Cli, middleware::auth::auth_middleware};
+use crate::{
+    cli::Cli,
+    metrics::Metrics,
+    middleware::auth::{auth_middleware, provision_auth_middleware},
+    services::bird::BirdInstances,
+};
 
 mod cli;
 mod config;
 mod handlers;
+mod metrics;
 mod middleware;
 mod services;
 
@@ -27,17 +33,10 @@ async fn main() -> anyhow::Result<()> {
     let config_path = &cli.config;
     info!("Using config file: {}", config_path);
     let config = Arc::new(Config::new(config_path)?);
+    let metrics = Metrics::new();
+    let instances = BirdInstances::new(&config, &metrics);
 
-    let app = Router::new()
-        .route("/bird", post(handlers::bird::handler))
-        .route("/wireguard", get(handlers::wireguard::get_wireguard))
-        .route("/traceroute", get(handlers::traceroute::traceroute))
-        .route("/traceroute4", get(handlers::traceroute::traceroute4))
-        .route("/traceroute6", get(handlers::traceroute::traceroute6))
-        .route("/peering", get(handlers::peering::get_peering_info))
-        .layer(CorsLayer::permissive())
-        .layer(axum::middleware::from_fn(auth_middleware))
-        .layer(Extension(config.clone()));
+    let app = router(config.clone(), metrics, instances);
 
     let mut handles = Vec::new();
     for listen_addr in &config.listen {
@@ -75,3 +74,114 @@ async fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+fn router(config: Arc<Config>, metrics: Metrics, instances: BirdInstances) -> Router {
+    let mut app = Router::new()
+        .route("/bird", post(handlers::bird::handler))
+        .route("/wireguard", get(handlers::wireguard::get_wireguard))
+        .route("/traceroute", get(handlers::traceroute::traceroute))
+        .route("/traceroute4", get(handlers::traceroute::traceroute4))
+        .route("/traceroute6", get(handlers::traceroute::traceroute6))
+        .route("/peering", get(handlers::peering::get_peering_info))
+        .route("/metrics", get(handlers::metrics::get_metrics))
+        .layer(CorsLayer::permissive())
+        .layer(axum::middleware::from_fn(auth_middleware));
+
+    if config.provision.is_some() {
+        app = app.merge(
+            Router::new()
+                .route("/provision", post(handlers::provision::provision))
+                .layer(axum::middleware::from_fn(provision_auth_middleware)),
+        );
+    }
+
+    app.layer(Extension(instances))
+        .layer(Extension(config))
+        .layer(Extension(metrics))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use common::auto_peer::ProvisionRequest;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::services::provision::tests::{session, test_dir};
+
+    async fn provision(app: Router, header: Option<(&str, &str)>) -> StatusCode {
+        let body = serde_json::to_string(&ProvisionRequest {
+            asn: 4242420001,
+            session: session(),
+            dry_run: true,
+        })
+        .unwrap();
+        let mut request = Request::post("/provision")
+            .header("content-type", "application/json")
+            .header("x-forwarded-for", "127.0.0.1");
+        if let Some((name, value)) = header {
+            request = request.header(name, value);
+        }
+        app.oneshot(request.body(Body::from(body)).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn provision_needs_its_own_secret() {
+        let dir = test_dir("router");
+        let config = handlers::provision::tests::config(&dir, "/tmp/bird-lg-test-unused.ctl");
+        let metrics = Metrics::new();
+        let app = router(
+            config.clone(),
+            metrics.clone(),
+            BirdInstances::new(&config, &metrics),
+        );
+
+        assert_eq!(provision(app.clone(), None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            provision(app.clone(), Some(("x-shared-secret", "shared-secret"))).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            provision(app.clone(), Some(("x-provision-secret", "shared-secret"))).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            provision(app, Some(("x-provision-secret", "provision-secret"))).await,
+            StatusCode::OK
+        );
+
+        let mut config = (*config).clone();
+        config.provision = None;
+        let config = Arc::new(config);
+        let app = router(
+            config.clone(),
+            metrics.clone(),
+            BirdInstances::new(&config, &metrics),
+        );
+        assert_eq!(
+            provision(app, Some(("x-shared-secret", "shared-secret"))).await,
+            StatusCode::NOT_FOUND
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn provision_secret_differs_from_shared_secret() {
+        let dir = test_dir("same-secret");
+        let mut config = (*handlers::provision::tests::config(&dir, "/tmp/bird.ctl")).clone();
+        config.shared_secret = Some("provision-secret".to_string());
+        let error = config.validated().unwrap_err().to_string();
+        assert!(
+            error.contains("provision.secret must differ from shared_secret"),
+            "{}",
+            error
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
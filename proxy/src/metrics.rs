@@ -0,0 +1,188 @@
+use prometheus::{
+    Encoder, HistogramOpts, HistogramTimer, HistogramVec, IntCounter, IntCounterVec, Opts,
+    Registry, TextEncoder,
+};
+
+const NAMESPACE: &str = "birdlg_proxy";
+
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    bird_connect_failures: IntCounter,
+    bird_connections_opened: IntCounter,
+    bird_commands: IntCounterVec,
+    bird_commands_rejected: IntCounter,
+    bird_command_duration: HistogramVec,
+    traceroutes: IntCounterVec,
+    traceroute_duration: HistogramVec,
+    auth_rejections: IntCounterVec,
+    wireguard_failures: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+
+        let bird_connect_failures = IntCounter::with_opts(opts(
+            "bird_connect_failures_total",
+            "Failed connections to the bird control socket",
+        ))
+        .unwrap();
+        let bird_connections_opened = IntCounter::with_opts(opts(
+            "bird_connections_opened_total",
+            "Restricted sessions opened on the bird control socket",
+        ))
+        .unwrap();
+        let bird_commands = IntCounterVec::new(
+            opts("bird_commands_total", "Commands sent to bird"),
+            &["command"],
+        )
+        .unwrap();
+        let bird_commands_rejected = IntCounter::with_opts(opts(
+            "bird_commands_rejected_total",
+            "Commands refused because they are not on the allow-list",
+        ))
+        .unwrap();
+        let bird_command_duration = HistogramVec::new(
+            HistogramOpts::from(opts(
+                "bird_command_duration_seconds",
+                "Time until bird finished replying to a command",
+            )),
+            &["command"],
+        )
+        .unwrap();
+        let traceroutes = IntCounterVec::new(
+            opts("traceroutes_total", "Traceroutes started"),
+            &["version"],
+        )
+        .unwrap();
+        let traceroute_duration = HistogramVec::new(
+            HistogramOpts::from(opts(
+                "traceroute_duration_seconds",
+                "Time until a traceroute finished streaming its output",
+            ))
+            .buckets(vec![1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0]),
+            &["version"],
+        )
+        .unwrap();
+        let auth_rejections = IntCounterVec::new(
+            opts(
+                "auth_rejections_total",
+                "Requests rejected by authentication",
+            ),
+            &["reason"],
+        )
+        .unwrap();
+        let wireguard_failures = IntCounterVec::new(
+            opts(
+                "wireguard_failures_total",
+                "Failed runs of the WireGuard command",
+            ),
+            &["reason"],
+        )
+        .unwrap();
+
+        registry
+            .register(Box::new(bird_connect_failures.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(bird_connections_opened.clone()))
+            .unwrap();
+        registry.register(Box::new(bird_commands.clone())).unwrap();
+        registry
+            .register(Box::new(bird_commands_rejected.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(bird_command_duration.clone()))
+            .unwrap();
+        registry.register(Box::new(traceroutes.clone())).unwrap();
+        registry
+            .register(Box::new(traceroute_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(auth_rejections.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(wireguard_failures.clone()))
+            .unwrap();
+
+        Self {
+            registry,
+            bird_connect_failures,
+            bird_connections_opened,
+            bird_commands,
+            bird_commands_rejected,
+            bird_command_duration,
+            traceroutes,
+            traceroute_duration,
+            auth_rejections,
+            wireguard_failures,
+        }
+    }
+
+    pub fn bird_connect_failed(&self) {
+        self.bird_connect_failures.inc();
+    }
+
+    pub fn bird_connection_opened(&self) {
+        self.bird_connections_opened.inc();
+    }
+
+    /// Counts a bird command and returns a timer that records its duration
+    /// when dropped, i.e. once the response stream has been consumed.
+    pub fn bird_command(&self, command: &str) -> HistogramTimer {
+        let kind = command_kind(command);
+        self.bird_commands.with_label_values(&[&kind]).inc();
+        self.bird_command_duration
+            .with_label_values(&[&kind])
+            .start_timer()
+    }
+
+    pub fn bird_command_rejected(&self) {
+        self.bird_commands_rejected.inc();
+    }
+
+    /// Counts a traceroute and returns a timer that records its duration when
+    /// dropped.
+    pub fn traceroute(&self, version: &str) -> HistogramTimer {
+        self.traceroutes.with_label_values(&[version]).inc();
+        self.traceroute_duration
+            .with_label_values(&[version])
+            .start_timer()
+    }
+
+    pub fn auth_rejected(&self, reason: &str) {
+        self.auth_rejections.with_label_values(&[reason]).inc();
+    }
+
+    pub fn wireguard_failed(&self, reason: &str) {
+        self.wireguard_failures.with_label_values(&[reason]).inc();
+    }
+
+    pub fn render(&self) -> Result<String, String> {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .map_err(|e| e.to_string())?;
+        String::from_utf8(buffer).map_err(|e| e.to_string())
+    }
+}
+
+/// Reduces a command to its leading keywords (`show route for 10.0.0.1 all`
+/// becomes `show route`) so arguments don't end up as label values.
+fn command_kind(command: &str) -> String {
+    let words: Vec<&str> = command
+        .split_whitespace()
+        .take(2)
+        .take_while(|w| w.chars().all(|c| c.is_ascii_lowercase()))
+        .collect();
+    if words.is_empty() {
+        "other".to_string()
+    } else {
+        words.join(" ")
+    }
+}
+
+fn opts(name: &str, help: &str) -> Opts {
+    Opts::new(name, help).namespace(NAMESPACE)
+}
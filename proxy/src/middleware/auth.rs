@@ -1,14 +1,15 @@
-use std::sync::Arc;
+use std::{net::IpAddr, sync::Arc};
 
 use axum::{
     body::Body,
     extract::Request,
+    http::StatusCode,
     response::{IntoResponse, Response},
 };
 use hyper::HeaderMap;
 use tracing::{error, warn};
 
-use crate::config::Config;
+use crate::{config::Config, metrics::Metrics};
 
 pub async fn auth_middleware(
     headers: HeaderMap,
@@ -17,22 +18,94 @@ pub async fn auth_middleware(
 ) -> Response {
     let config = match req.extensions().get::<Arc<Config>>().cloned() {
         Some(cfg) => cfg,
-        None => {
-            error!("Request missing proxy config extension");
-            return (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                "Server error",
-            )
-                .into_response();
-        }
+        None => return missing_config(),
     };
 
+    if let Some(secret) = config.shared_secret.as_ref().filter(|s| !s.is_empty())
+        && !header_matches(&headers, "x-shared-secret", secret)
+    {
+        warn!("Rejected request due to invalid shared secret");
+        reject(&req, "invalid_secret");
+        return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+    }
+
+    if !from_allowed_network(&config, &headers, &req) {
+        reject(&req, "forbidden_network");
+        return (StatusCode::FORBIDDEN, "Forbidden").into_response();
+    }
+
+    next.run(req).await
+}
+
+/// Guards `/provision`, which writes config files and reloads bird over an
+/// unrestricted session. It takes its own secret, so the shared secret of
+/// the read-only endpoints can't be used to change the router.
+pub async fn provision_auth_middleware(
+    headers: HeaderMap,
+    req: Request<Body>,
+    next: axum::middleware::Next,
+) -> Response {
+    let config = match req.extensions().get::<Arc<Config>>().cloned() {
+        Some(cfg) => cfg,
+        None => return missing_config(),
+    };
+
+    let authorized = config.provision.as_ref().is_some_and(|p| {
+        !p.secret.is_empty() && header_matches(&headers, "x-provision-secret", &p.secret)
+    });
+    if !authorized {
+        warn!("Rejected provisioning request due to invalid provision secret");
+        reject(&req, "invalid_provision_secret");
+        return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+    }
+
+    if !from_allowed_network(&config, &headers, &req) {
+        reject(&req, "forbidden_network");
+        return (StatusCode::FORBIDDEN, "Forbidden").into_response();
+    }
+
+    next.run(req).await
+}
+
+fn missing_config() -> Response {
+    error!("Request missing proxy config extension");
+    (StatusCode::INTERNAL_SERVER_ERROR, "Server error").into_response()
+}
+
+fn reject(req: &Request<Body>, reason: &str) {
+    if let Some(metrics) = req.extensions().get::<Metrics>() {
+        metrics.auth_rejected(reason);
+    }
+}
+
+fn header_matches(headers: &HeaderMap, name: &str, secret: &str) -> bool {
+    headers
+        .get(name)
+        .is_some_and(|v| constant_time_eq(v.as_bytes(), secret.as_bytes()))
+}
+
+/// Compares secrets without returning early at the first differing byte.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn from_allowed_network(config: &Config, headers: &HeaderMap, req: &Request<Body>) -> bool {
+    let client_addr = client_addr(headers, req);
+    let allowed =
+        client_addr.is_some_and(|addr| config.allowed_nets.iter().any(|net| net.contains(&addr)));
+    if !allowed {
+        warn!(client_ip = ?client_addr, "Rejected request from unauthorized network");
+    }
+    allowed
+}
+
+fn client_addr(headers: &HeaderMap, req: &Request<Body>) -> Option<IpAddr> {
     let connect_info = req
         .extensions()
         .get::<axum::extract::ConnectInfo<std::net::SocketAddr>>()
         .cloned();
 
-    let client_addr = headers
+    headers
         .get("x-forwarded-for")
         .and_then(|v| v.to_str().ok())
         .and_then(|s| s.split(',').next().map(|s| s.trim().to_string()))
@@ -44,30 +117,5 @@ pub async fn auth_middleware(
         })
         .or_else(|| connect_info.map(|info| info.0.ip().to_string()))
         .as_deref()
-        .and_then(|s| s.parse::<std::net::IpAddr>().ok());
-
-    if let Some(secret) = config.shared_secret.as_ref().filter(|s| !s.is_empty()) {
-        let header_ok = headers
-            .get("x-shared-secret")
-            .and_then(|v| v.to_str().ok())
-            .map(|s| s == secret)
-            .unwrap_or(false);
-
-        if !header_ok {
-            warn!("Rejected request due to invalid shared secret");
-            return (axum::http::StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
-        }
-    }
-
-    if let Some(addr) = client_addr {
-        for net in config.allowed_nets.iter() {
-            if net.contains(&addr) {
-                return next.run(req).await;
-            }
-        }
-    }
-
-    warn!(client_ip = ?client_addr, "Rejected request from unauthorized network");
-
-    (axum::http::StatusCode::FORBIDDEN, "Forbidden").into_response()
+        .and_then(|s| s.parse::<IpAddr>().ok())
 }
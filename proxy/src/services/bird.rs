@@ -1,38 +1,123 @@
 use std::{
+    collections::HashMap,
+    io,
     pin::Pin,
+    sync::Arc,
     task::{Context as TaskContext, Poll},
+    time::Duration,
 };
 
 use anyhow::{Context as _, bail};
 use bytes::BytesMut;
+use common::{
+    command::BirdError,
+    protocol::{ProtocolDetails, parse_protocol_details},
+};
 use tokio::{
-    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
-    net::UnixStream,
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    sync::Mutex,
+    time::Instant,
 };
 use tokio_stream::Stream;
 use tokio_util::codec::{Decoder, Framed};
+use tracing::warn;
 
-pub async fn connect(socket_path: &str) -> anyhow::Result<UnixStream> {
-    let mut stream = UnixStream::connect(socket_path)
-        .await
-        .with_context(|| format!("Failed to connect to bird socket {}", socket_path))?;
+pub use self::{
+    pool::{BirdPool, PooledConnection},
+    socket::BirdSocket,
+};
+use crate::{config::Config, metrics::Metrics};
 
-    let mut buffer = [0; 1024];
-    let n = stream
-        .read(&mut buffer)
-        .await
-        .context("Failed to read greeting from bird socket")?;
+mod pool;
+mod socket;
 
-    if !buffer[..n].starts_with(b"0001") {
-        bail!("Unexpected birdc response: {:?}", &buffer[..n]);
+/// Upper bound for a chunk, so large replies are streamed instead of being
+/// collected while bird keeps the socket readable.
+const MAX_CHUNK: usize = 64 * 1024;
+
+/// How long the BGP protocols of all instances are reused, so mapping
+/// WireGuard interfaces doesn't dump every protocol on each request.
+const BGP_PROTOCOLS_TTL: Duration = Duration::from_secs(60);
+
+/// The pools of all bird instances of the proxy. Requests that don't name an
+/// instance go to the one at `bind_socket`.
+#[derive(Clone)]
+pub struct BirdInstances {
+    default: BirdPool,
+    named: HashMap<String, BirdPool>,
+    bgp_protocols: Arc<Mutex<Option<CachedProtocols>>>,
+}
+
+/// Protocols and when they were fetched.
+type CachedProtocols = (Instant, Vec<ProtocolDetails>);
+
+impl BirdInstances {
+    pub fn new(config: &Config, metrics: &Metrics) -> Self {
+        let pool = |endpoint: &str| BirdPool::new(endpoint, &config.bird_pool, metrics.clone());
+        Self {
+            default: pool(&config.bind_socket),
+            named: config
+                .bird_instances
+                .iter()
+                .map(|(name, endpoint)| (name.clone(), pool(endpoint)))
+                .collect(),
+            bgp_protocols: Arc::default(),
+        }
+    }
+
+    pub fn get(&self, instance: Option<&str>) -> Option<&BirdPool> {
+        match instance {
+            None => Some(&self.default),
+            Some(name) => self.named.get(name),
+        }
+    }
+
+    pub fn all(&self) -> impl Iterator<Item = &BirdPool> {
+        std::iter::once(&self.default).chain(self.named.values())
+    }
+
+    /// The BGP protocols of all instances, fetched at most once per
+    /// `BGP_PROTOCOLS_TTL`. Instances that can't be queried are left out and
+    /// the result isn't reused then.
+    pub async fn bgp_protocols(&self) -> Vec<ProtocolDetails> {
+        let mut cached = self.bgp_protocols.lock().await;
+        if let Some((fetched, protocols)) = &*cached
+            && fetched.elapsed() < BGP_PROTOCOLS_TTL
+        {
+            return protocols.clone();
+        }
+
+        let mut protocols = Vec::new();
+        let mut complete = true;
+        for pool in self.all() {
+            match pool.query("show protocols all").await {
+                Ok(lines) => protocols.extend(
+                    parse_protocol_details(&lines)
+                        .into_iter()
+                        .filter(|p| p.summary.proto == "BGP"),
+                ),
+                Err(e) => {
+                    warn!(error = %e, "Failed to list BGP protocols");
+                    complete = false;
+                }
+            }
+        }
+        if complete {
+            *cached = Some((Instant::now(), protocols.clone()));
+        }
+        protocols
     }
+}
+
+async fn connect(endpoint: &str) -> anyhow::Result<BirdSocket> {
+    let mut stream = open(endpoint).await?;
 
     stream
         .write_all(b"restrict\n")
         .await
         .context("Failed to enable restrict mode on bird socket")?;
 
-    buffer.fill(0);
+    let mut buffer = [0; 1024];
     let n = stream
         .read(&mut buffer)
         .await
@@ -45,78 +130,360 @@ pub async fn connect(socket_path: &str) -> anyhow::Result<UnixStream> {
     Ok(stream)
 }
 
+async fn open(endpoint: &str) -> anyhow::Result<BirdSocket> {
+    let mut stream = BirdSocket::connect(endpoint)
+        .await
+        .with_context(|| format!("Failed to connect to bird socket {}", endpoint))?;
+
+    let mut buffer = [0; 1024];
+    let n = stream
+        .read(&mut buffer)
+        .await
+        .context("Failed to read greeting from bird socket")?;
+
+    if !buffer[..n].starts_with(b"0001") {
+        bail!("Unexpected birdc response: {:?}", &buffer[..n]);
+    }
+
+    Ok(stream)
+}
+
+/// Asks bird to reload its configuration. This needs an unrestricted
+/// session, so it must never be reachable from user supplied commands.
+pub async fn configure(endpoint: &str) -> anyhow::Result<String> {
+    let mut stream = open(endpoint).await?;
+    stream
+        .write_all(b"configure\n")
+        .await
+        .context("Failed to send configure to bird socket")?;
+
+    let mut lines = BufReader::new(stream).lines();
+    let mut output = String::new();
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .context("Failed to read configure reply from bird socket")?
+    {
+        let (code, text) = match line.get(..4) {
+            Some(code) if code.bytes().all(|b| b.is_ascii_digit()) => (code, &line[4..]),
+            _ => ("", line.as_str()),
+        };
+        output.push_str(text.get(1..).unwrap_or_default());
+        output.push('\n');
+
+        if !code.is_empty() && text.starts_with(' ') {
+            if code.starts_with('8') || code.starts_with('9') {
+                bail!("bird rejected configuration: {}", output.trim_end());
+            }
+            return Ok(output);
+        }
+    }
+
+    bail!("bird closed the socket before finishing configure")
+}
+
+/// Splits a reply into lines, each with the reply code it belongs to.
+/// Continuation lines, which start with a space instead of a code, keep the
+/// code of the line before them.
 #[derive(Default)]
 pub struct BirdDecoder {
-    last_type: u8,
-    current_message: String,
+    code: u16,
 }
 
 pub struct BirdLine {
-    content: String,
+    code: u16,
+    text: String,
     is_last: bool,
 }
 
 impl Decoder for BirdDecoder {
     type Item = BirdLine;
-    type Error = std::io::Error;
+    type Error = io::Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        loop {
-            if let Some(offset) = src.iter().position(|&b| b == b'\n') {
-                let line_len = offset + 1;
-                let line_bytes = src.split_to(line_len);
-                let line = &line_bytes[..offset];
-
-                if line.len() >= 4 && line[0..4].iter().all(|&b| b.is_ascii_digit()) {
-                    self.last_type = line[0];
-                    if line.len() >= 5 {
-                        self.current_message
-                            .push_str(&String::from_utf8_lossy(&line[5..]));
-                    }
-                } else {
-                    self.current_message
-                        .push_str(&String::from_utf8_lossy(line));
-                }
-                self.current_message.push('\n');
+        let Some(offset) = src.iter().position(|&b| b == b'\n') else {
+            return Ok(None);
+        };
+        let line_bytes = src.split_to(offset + 1);
+        let line = &line_bytes[..offset];
+
+        let code = line
+            .get(..4)
+            .filter(|code| code.iter().all(u8::is_ascii_digit))
+            .and_then(|code| std::str::from_utf8(code).ok()?.parse().ok());
+        let (text, is_last) = match code {
+            Some(code) => {
+                self.code = code;
+                // `-` means more lines with this code follow. Codes 0xxx end
+                // a successful reply, 8xxx and 9xxx a failed one.
+                let is_final = line.get(4) != Some(&b'-');
+                let ends_reply = code < 1000 || BirdError::is_error_code(code);
+                (line.get(5..).unwrap_or_default(), is_final && ends_reply)
+            }
+            None => (line, false),
+        };
+
+        Ok(Some(BirdLine {
+            code: self.code,
+            text: String::from_utf8_lossy(text).into_owned(),
+            is_last,
+        }))
+    }
+}
+
+/// The reply to a command in chunks of complete lines. An error reply from
+/// bird ends the stream with an [`io::Error`] wrapping the [`BirdError`].
+/// The session goes back to the pool once the final line was read.
+pub struct BirdStream {
+    inner: Framed<PooledConnection, BirdDecoder>,
+    error: Option<BirdError>,
+    done: bool,
+}
+
+impl BirdStream {
+    pub fn new(connection: PooledConnection) -> Self {
+        Self {
+            inner: Framed::new(connection, BirdDecoder::default()),
+            error: None,
+            done: false,
+        }
+    }
+}
 
-                let is_last = b"089".contains(&self.last_type);
+impl Stream for BirdStream {
+    type Item = Result<String, io::Error>;
 
-                if is_last {
-                    let content = std::mem::take(&mut self.current_message);
-                    return Ok(Some(BirdLine { content, is_last }));
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        let mut chunk = String::new();
+
+        while !self.done && chunk.len() < MAX_CHUNK {
+            let line = match Pin::new(&mut self.inner).poll_next(cx) {
+                Poll::Ready(Some(Ok(line))) => line,
+                Poll::Ready(Some(Err(e))) => {
+                    self.done = true;
+                    return Poll::Ready(Some(Err(e)));
+                }
+                Poll::Ready(None) => {
+                    self.done = true;
+                    break;
+                }
+                Poll::Pending if chunk.is_empty() => return Poll::Pending,
+                Poll::Pending => break,
+            };
+
+            if BirdError::is_error_code(line.code) {
+                let error = self.error.get_or_insert_with(|| BirdError {
+                    code: line.code,
+                    message: String::new(),
+                });
+                if !error.message.is_empty() {
+                    error.message.push('\n');
                 }
+                error.message.push_str(line.text.trim());
             } else {
-                return Ok(None);
+                chunk.push_str(&line.text);
+                chunk.push('\n');
+            }
+
+            if line.is_last {
+                self.done = true;
+                if self.inner.read_buffer().is_empty() {
+                    self.inner.get_mut().finish();
+                }
             }
         }
+
+        if !chunk.is_empty() {
+            return Poll::Ready(Some(Ok(chunk)));
+        }
+        // Output before an error is sent first, the error comes last.
+        Poll::Ready(self.error.take().map(|e| Err(io::Error::other(e))))
     }
 }
 
-pub struct BirdStream<T> {
-    pub inner: Framed<T, BirdDecoder>,
-    pub done: bool,
-}
+#[cfg(test)]
+pub(crate) mod tests {
+    use common::command::BirdErrorKind;
+    use tokio::{
+        io::{AsyncRead, AsyncWrite, AsyncWriteExt},
+        net::{TcpListener, UnixListener},
+    };
+    use tokio_stream::StreamExt;
 
-impl<T> Stream for BirdStream<T>
-where
-    T: AsyncRead + AsyncWrite + Unpin,
-{
-    type Item = Result<String, std::io::Error>;
+    use super::*;
+    use crate::config::BirdPoolConfig;
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
-        if self.done {
-            return Poll::Ready(None);
+    fn decode(input: &str) -> Vec<BirdLine> {
+        let mut decoder = BirdDecoder::default();
+        let mut buffer = BytesMut::from(input);
+        let mut lines = Vec::new();
+        while let Some(line) = decoder.decode(&mut buffer).unwrap() {
+            lines.push(line);
         }
+        lines
+    }
 
-        match std::task::ready!(Pin::new(&mut self.inner).poll_next(cx)) {
-            Some(Ok(line)) => {
-                if line.is_last {
-                    self.done = true;
-                }
-                Poll::Ready(Some(Ok(line.content)))
+    fn bird_error(line: &BirdLine) -> BirdError {
+        BirdError {
+            code: line.code,
+            message: line.text.clone(),
+        }
+    }
+
+    #[test]
+    fn continuation_lines() {
+        let lines = decode(
+            "2002-Name       Proto      Table      State  Since         Info\n\
+             1002-peer1      BGP        ---        up     2025-01-01    Established\n\
+             1006-  BGP state:          Established\n     Neighbor AS:      4242420001\n\
+             1002-peer2      BGP        ---        start  2025-01-01    Active\n\
+             0000 \n",
+        );
+        let codes: Vec<u16> = lines.iter().map(|l| l.code).collect();
+        assert_eq!(codes, [2002, 1002, 1006, 1006, 1002, 0]);
+        assert_eq!(
+            lines[1].text,
+            "peer1      BGP        ---        up     2025-01-01    Established"
+        );
+        assert_eq!(lines[3].text, "     Neighbor AS:      4242420001");
+        let last: Vec<bool> = lines.iter().map(|l| l.is_last).collect();
+        assert_eq!(last, [false, false, false, false, false, true]);
+    }
+
+    #[test]
+    fn incomplete_line() {
+        let mut decoder = BirdDecoder::default();
+        let mut buffer = BytesMut::from("1002-peer1");
+        assert!(decoder.decode(&mut buffer).unwrap().is_none());
+        buffer.extend_from_slice(b"\n0000 \n");
+        assert_eq!(decoder.decode(&mut buffer).unwrap().unwrap().text, "peer1");
+        assert!(decoder.decode(&mut buffer).unwrap().unwrap().is_last);
+    }
+
+    #[test]
+    fn error_codes() {
+        for (reply, kind) in [
+            ("8001 Network not found\n", BirdErrorKind::NotFound),
+            ("8003 No protocols match\n", BirdErrorKind::NotFound),
+            ("8007 Access denied\n", BirdErrorKind::AccessDenied),
+            (
+                "9001 syntax error, unexpected CF_SYM_UNDEFINED\n",
+                BirdErrorKind::InvalidCommand,
+            ),
+            ("8000 Reply too long\n", BirdErrorKind::Failed),
+        ] {
+            let lines = decode(reply);
+            assert_eq!(lines.len(), 1);
+            assert!(lines[0].is_last, "{}", reply);
+            assert_eq!(bird_error(&lines[0]).kind(), kind, "{}", reply);
+        }
+        // A `-` keeps the reply open even for error codes.
+        let lines = decode("8001-first\n8001 second\n");
+        assert!(!lines[0].is_last);
+        assert!(lines[1].is_last);
+    }
+
+    /// Serves bird sessions on a Unix socket in the temp dir. `restrict` is
+    /// confirmed like bird does, every other command gets `reply(command)`.
+    /// A reply without a trailing newline is sent and the session closed, as
+    /// if bird died in the middle of it.
+    pub(crate) async fn fake_bird(name: &str, reply: fn(&str) -> &'static str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("bird-lg-test-{}-{}.ctl", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve_bird(stream, reply));
+            }
+        });
+        path.to_string_lossy().into_owned()
+    }
+
+    /// Like [`fake_bird`], on a TCP port of the loopback address.
+    pub(crate) async fn fake_bird_tcp(reply: fn(&str) -> &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve_bird(stream, reply));
+            }
+        });
+        addr.to_string()
+    }
+
+    async fn serve_bird<S>(stream: S, reply: fn(&str) -> &'static str) -> io::Result<()>
+    where
+        S: AsyncRead + AsyncWrite,
+    {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(reader).lines();
+        writer.write_all(b"0001 BIRD 2.15 ready.\n").await?;
+        while let Some(line) = lines.next_line().await? {
+            let answer = match line.as_str() {
+                "restrict" => "0016 Access restricted\n",
+                command => reply(command),
+            };
+            writer.write_all(answer.as_bytes()).await?;
+            if !answer.ends_with('\n') {
+                break;
             }
-            Some(Err(e)) => Poll::Ready(Some(Err(e))),
-            None => Poll::Ready(None),
         }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn tcp_socket() {
+        let endpoint = fake_bird_tcp(|command| match command {
+            "show status" => {
+                "1000-BIRD 2.15\n1011-Router ID is 172.20.0.1\n0013 Daemon is up and running\n"
+            }
+            _ => "9001 syntax error\n",
+        })
+        .await;
+        assert!(matches!(
+            BirdSocket::connect(&endpoint).await.unwrap(),
+            BirdSocket::Tcp(_)
+        ));
+        let pool = BirdPool::new(&endpoint, &BirdPoolConfig::default(), Metrics::new());
+
+        let mut connection = pool.get().await.unwrap();
+        connection.send("show status").await.unwrap();
+        let output: Vec<String> = BirdStream::new(connection)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(
+            output.concat(),
+            "BIRD 2.15\nRouter ID is 172.20.0.1\nDaemon is up and running\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn error_after_output() {
+        let path = fake_bird(
+            "error-after-output",
+            |_| "1007-Table master4:\n 10.0.0.0/8 unicast [peer1]\n8001 Network not found\n",
+        )
+        .await;
+        let pool = BirdPool::new(&path, &BirdPoolConfig::default(), Metrics::new());
+
+        let mut connection = pool.get().await.unwrap();
+        connection.send("show route for 10.0.0.0/8").await.unwrap();
+        let mut stream = BirdStream::new(connection);
+
+        let output = stream.next().await.unwrap().unwrap();
+        assert_eq!(output, "Table master4:\n 10.0.0.0/8 unicast [peer1]\n");
+        let error = stream.next().await.unwrap().unwrap_err();
+        let error = error
+            .get_ref()
+            .unwrap()
+            .downcast_ref::<BirdError>()
+            .unwrap();
+        assert_eq!(error.code, 8001);
+        assert_eq!(error.kind(), BirdErrorKind::NotFound);
+        assert!(stream.next().await.is_none());
+
+        std::fs::remove_file(path).unwrap();
     }
 }
@@ -0,0 +1,397 @@
+use std::{
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex, Weak},
+    task::{Context as TaskContext, Poll},
+    time::Duration,
+};
+
+use anyhow::{Context as _, anyhow};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf},
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::{Instant, interval, timeout},
+};
+use tokio_stream::StreamExt;
+use tracing::debug;
+
+use super::{BirdSocket, BirdStream, connect};
+use crate::{config::BirdPoolConfig, metrics::Metrics};
+
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Restricted bird sessions shared by all requests. A session goes back to
+/// the pool once its reply was read completely; sessions dropped in the
+/// middle of a reply are closed instead.
+#[derive(Clone)]
+pub struct BirdPool {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    endpoint: String,
+    idle: Mutex<Vec<IdleConnection>>,
+    permits: Arc<Semaphore>,
+    acquire_timeout: Duration,
+    idle_timeout: Duration,
+    metrics: Metrics,
+}
+
+struct IdleConnection {
+    stream: BirdSocket,
+    since: Instant,
+}
+
+impl IdleConnection {
+    /// Bird never writes to a session unprompted, so an idle session with
+    /// something to read was closed or is out of sync.
+    fn is_healthy(&self, idle_timeout: Duration) -> bool {
+        if self.since.elapsed() >= idle_timeout {
+            return false;
+        }
+        let mut buf = [0; 1];
+        matches!(
+            self.stream.try_read(&mut buf),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock
+        )
+    }
+}
+
+impl BirdPool {
+    pub fn new(endpoint: &str, config: &BirdPoolConfig, metrics: Metrics) -> Self {
+        let inner = Arc::new(Inner {
+            endpoint: endpoint.to_string(),
+            idle: Mutex::new(Vec::new()),
+            permits: Arc::new(Semaphore::new(config.max_size)),
+            acquire_timeout: Duration::from_secs(config.acquire_timeout),
+            idle_timeout: Duration::from_secs(config.idle_timeout),
+            metrics,
+        });
+        tokio::spawn(health_check(Arc::downgrade(&inner)));
+        Self { inner }
+    }
+
+    /// Takes an idle session or opens a new one. While `max_size` sessions
+    /// are in use, requests queue up to `acquire_timeout`.
+    pub async fn get(&self) -> anyhow::Result<PooledConnection> {
+        let permit = timeout(
+            self.inner.acquire_timeout,
+            self.inner.permits.clone().acquire_owned(),
+        )
+        .await
+        .map_err(|_| anyhow!("Timed out waiting for a free bird session"))?
+        .context("Bird session pool is closed")?;
+
+        let stream = match self.take_idle() {
+            Some(stream) => stream,
+            None => {
+                let stream = connect(&self.inner.endpoint).await.inspect_err(|_| {
+                    self.inner.metrics.bird_connect_failed();
+                })?;
+                self.inner.metrics.bird_connection_opened();
+                stream
+            }
+        };
+
+        Ok(PooledConnection {
+            stream: Some(stream),
+            pool: self.inner.clone(),
+            single_command: false,
+            reusable: false,
+            _permit: permit,
+        })
+    }
+
+    /// Runs a command and returns its complete output.
+    pub async fn query(&self, command: &str) -> anyhow::Result<Vec<String>> {
+        let mut connection = self.get().await?;
+        connection
+            .send(command)
+            .await
+            .context("Failed to write bird request")?;
+
+        let mut output = BirdStream::new(connection);
+        let mut lines = Vec::new();
+        while let Some(chunk) = output.next().await {
+            let chunk = chunk.context("Failed to read bird reply")?;
+            lines.extend(chunk.lines().map(str::to_string));
+        }
+        Ok(lines)
+    }
+
+    fn take_idle(&self) -> Option<BirdSocket> {
+        let mut idle = self.inner.idle.lock().unwrap();
+        while let Some(connection) = idle.pop() {
+            if connection.is_healthy(self.inner.idle_timeout) {
+                return Some(connection.stream);
+            }
+        }
+        None
+    }
+}
+
+/// Closes idle sessions that timed out or were closed by bird, until the
+/// pool is dropped.
+async fn health_check(pool: Weak<Inner>) {
+    let mut ticker = interval(HEALTH_CHECK_INTERVAL);
+    loop {
+        ticker.tick().await;
+        let Some(pool) = pool.upgrade() else {
+            return;
+        };
+        let mut idle = pool.idle.lock().unwrap();
+        let before = idle.len();
+        idle.retain(|c| c.is_healthy(pool.idle_timeout));
+        if idle.len() < before {
+            debug!(closed = before - idle.len(), "Closed stale bird sessions");
+        }
+    }
+}
+
+/// A session taken from the pool. It is returned on drop if its reply was
+/// read completely, and closed otherwise.
+pub struct PooledConnection {
+    stream: Option<BirdSocket>,
+    pool: Arc<Inner>,
+    single_command: bool,
+    reusable: bool,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl PooledConnection {
+    /// Sends a command. Input with several lines runs several commands whose
+    /// replies may still be in flight after the first one, so such sessions
+    /// are never reused.
+    pub async fn send(&mut self, command: &str) -> io::Result<()> {
+        let command = command.trim_end_matches('\n');
+        self.single_command = !command.contains('\n');
+        self.stream_mut()
+            .write_all(format!("{}\n", command).as_bytes())
+            .await
+    }
+
+    /// Marks the reply to the last command as completely read.
+    pub(super) fn finish(&mut self) {
+        self.reusable = self.single_command;
+    }
+
+    fn stream_mut(&mut self) -> &mut BirdSocket {
+        self.stream.as_mut().expect("stream is only taken on drop")
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        if self.reusable
+            && let Some(stream) = self.stream.take()
+        {
+            self.pool.idle.lock().unwrap().push(IdleConnection {
+                stream,
+                since: Instant::now(),
+            });
+        }
+    }
+}
+
+impl AsyncRead for PooledConnection {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(self.stream_mut()).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for PooledConnection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(self.stream_mut()).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Pin::new(self.stream_mut()).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Pin::new(self.stream_mut()).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::UnixStream;
+
+    use super::*;
+    use crate::services::bird::tests::fake_bird;
+
+    fn reply(command: &str) -> &'static str {
+        match command {
+            "show status" => "1000-BIRD 2.15\n0013 Daemon is up and running\n",
+            "show route for 10.0.0.0/8" => "8001 Network not found\n",
+            // The reply never ends.
+            "show partial" => "1000-BIRD 2.15\n",
+            // Bird goes away in the middle of a line.
+            "show crash" => "1000-BIRD 2.15\n1011-Router",
+            _ => "9001 syntax error\n",
+        }
+    }
+
+    fn pool(path: &str, max_size: usize) -> BirdPool {
+        let config = BirdPoolConfig {
+            max_size,
+            acquire_timeout: 1,
+            ..BirdPoolConfig::default()
+        };
+        BirdPool::new(path, &config, Metrics::new())
+    }
+
+    fn idle(pool: &BirdPool) -> usize {
+        pool.inner.idle.lock().unwrap().len()
+    }
+
+    /// Sends `command` and reads the first chunk of the reply, then drops
+    /// the connection.
+    async fn run(pool: &BirdPool, command: &str) -> Option<io::Result<String>> {
+        let mut connection = pool.get().await.unwrap();
+        connection.send(command).await.unwrap();
+        BirdStream::new(connection).next().await
+    }
+
+    #[tokio::test]
+    async fn reuses_sessions_after_complete_replies() {
+        let path = fake_bird("pool-reuse", reply).await;
+        let pool = pool(&path, 2);
+
+        assert_eq!(pool.query("show status").await.unwrap().len(), 2);
+        assert_eq!(idle(&pool), 1);
+        assert_eq!(pool.query("show status").await.unwrap().len(), 2);
+        assert_eq!(idle(&pool), 1);
+
+        // The session stays out of the pool until the final line was read.
+        let mut connection = pool.get().await.unwrap();
+        assert_eq!(idle(&pool), 0);
+        connection.send("show status").await.unwrap();
+        let mut stream = BirdStream::new(connection);
+        assert!(stream.next().await.unwrap().is_ok());
+        assert!(stream.next().await.is_none());
+        drop(stream);
+        assert_eq!(idle(&pool), 1);
+
+        // An error reply still ends the reply.
+        assert!(
+            run(&pool, "show route for 10.0.0.0/8")
+                .await
+                .unwrap()
+                .is_err()
+        );
+        assert_eq!(idle(&pool), 1);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn discards_sessions_with_unread_replies() {
+        let path = fake_bird("pool-discard", reply).await;
+        let pool = pool(&path, 2);
+
+        // Dropped before reading anything.
+        let mut connection = pool.get().await.unwrap();
+        connection.send("show status").await.unwrap();
+        drop(connection);
+        assert_eq!(idle(&pool), 0);
+
+        // Dropped after a partial reply.
+        assert_eq!(
+            run(&pool, "show partial").await.unwrap().unwrap(),
+            "BIRD 2.15\n"
+        );
+        assert_eq!(idle(&pool), 0);
+
+        // Bird closed the session in the middle of the reply.
+        let mut connection = pool.get().await.unwrap();
+        connection.send("show crash").await.unwrap();
+        let results: Vec<_> = BirdStream::new(connection).collect().await;
+        assert!(results.iter().any(Result::is_err));
+        assert_eq!(idle(&pool), 0);
+
+        // Several commands: the later replies may still be on their way.
+        let mut connection = pool.get().await.unwrap();
+        connection.send("show status\nshow status").await.unwrap();
+        let mut stream = BirdStream::new(connection);
+        while stream.next().await.is_some() {}
+        drop(stream);
+        assert_eq!(idle(&pool), 0);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn queues_at_max_size() {
+        let path = fake_bird("pool-queue", reply).await;
+        let pool = pool(&path, 1);
+
+        let held = pool.get().await.unwrap();
+        let queued = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.query("show status").await }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!queued.is_finished());
+        drop(held);
+        assert_eq!(queued.await.unwrap().unwrap().len(), 2);
+
+        let _held = pool.get().await.unwrap();
+        let error = pool.get().await.err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Timed out waiting for a free bird session"
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn health_check_drops_dead_sessions() {
+        let idle_timeout = Duration::from_secs(300);
+        let connection = |stream| IdleConnection {
+            stream: BirdSocket::Unix(stream),
+            since: Instant::now(),
+        };
+
+        let (ours, _theirs) = UnixStream::pair().unwrap();
+        assert!(connection(ours).is_healthy(idle_timeout));
+
+        // `try_read` sees what the runtime has been notified of, so each
+        // check waits for the event first.
+        let (ours, theirs) = UnixStream::pair().unwrap();
+        drop(theirs);
+        ours.readable().await.unwrap();
+        assert!(!connection(ours).is_healthy(idle_timeout));
+
+        // Bird never talks first, anything to read means the session is out
+        // of sync.
+        let (ours, mut theirs) = UnixStream::pair().unwrap();
+        theirs.write_all(b"0000 \n").await.unwrap();
+        ours.readable().await.unwrap();
+        assert!(!connection(ours).is_healthy(idle_timeout));
+
+        let (ours, _theirs) = UnixStream::pair().unwrap();
+        assert!(!connection(ours).is_healthy(Duration::ZERO));
+
+        // A dead idle session is skipped for a new one.
+        let path = fake_bird("pool-health", reply).await;
+        let pool = pool(&path, 1);
+        let (ours, theirs) = UnixStream::pair().unwrap();
+        drop(theirs);
+        ours.readable().await.unwrap();
+        pool.inner.idle.lock().unwrap().push(connection(ours));
+        assert_eq!(pool.query("show status").await.unwrap().len(), 2);
+        assert_eq!(idle(&pool), 1);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,368 @@
+use std::{
+    fs,
+    io::Write,
+    net::{Ipv4Addr, Ipv6Addr},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+};
+
+use common::{
+    auto_peer::{PeeringSession, RenderedFile},
+    utils::{validate_endpoint, validate_wg_pubkey},
+};
+use tracing::warn;
+
+use crate::config::ProvisionConfig;
+
+const MAX_INTERFACE_LEN: usize = 15;
+
+/// Renders the BIRD and WireGuard files for a session. Every `{key}`
+/// placeholder is substituted; a line that references a placeholder without
+/// a value (e.g. `{ipv4}` on an IPv6-only session) is left out entirely.
+pub fn render(
+    config: &ProvisionConfig,
+    asn: u32,
+    session: &PeeringSession,
+) -> Result<Vec<RenderedFile>, String> {
+    let (asn_short, interface) = interface_name(config, asn)?;
+    let mut values = placeholders(session)?;
+    values.extend([
+        ("asn", Some(asn.to_string())),
+        ("asn_short", Some(asn_short)),
+        ("interface", Some(interface.clone())),
+    ]);
+
+    Ok(vec![
+        RenderedFile {
+            path: file_path(&config.bird_dir, &interface),
+            content: substitute(&config.bird_template, &values),
+        },
+        RenderedFile {
+            path: file_path(&config.wireguard_dir, &interface),
+            content: substitute(&config.wireguard_template, &values),
+        },
+    ])
+}
+
+/// Writes the rendered files, keeping the previous contents so that a failed
+/// reload can put them back.
+pub fn write(files: &[RenderedFile]) -> Result<Vec<(PathBuf, Option<String>)>, String> {
+    let mut previous = Vec::new();
+    for file in files {
+        let path = PathBuf::from(&file.path);
+        let old = fs::read_to_string(&path).ok();
+        if let Err(e) = write_atomic(&path, &file.content) {
+            restore(&previous);
+            return Err(format!("Failed to write {}: {}", file.path, e));
+        }
+        previous.push((path, old));
+    }
+    Ok(previous)
+}
+
+pub fn restore(previous: &[(PathBuf, Option<String>)]) {
+    for (path, old) in previous {
+        let result = match old {
+            Some(content) => write_atomic(path, content),
+            None => fs::remove_file(path),
+        };
+        if let Err(e) = result {
+            warn!(error = %e, path = %path.display(), "Failed to restore provisioned file");
+        }
+    }
+}
+
+fn interface_name(config: &ProvisionConfig, asn: u32) -> Result<(String, String), String> {
+    let asn_short = if (4242420000..=4242429999).contains(&asn) {
+        format!("{:04}", asn % 10000)
+    } else {
+        asn.to_string()
+    };
+    let interface = format!("{}{}", config.interface_prefix, asn_short);
+    if interface.len() > MAX_INTERFACE_LEN {
+        return Err(format!(
+            "Interface name '{}' is longer than {} characters",
+            interface, MAX_INTERFACE_LEN
+        ));
+    }
+    Ok((asn_short, interface))
+}
+
+fn placeholders(session: &PeeringSession) -> Result<Vec<(&'static str, Option<String>)>, String> {
+    let ipv4 = session
+        .ipv4
+        .as_deref()
+        .map(|ip| {
+            ip.parse::<Ipv4Addr>()
+                .map(|ip| ip.to_string())
+                .map_err(|_| format!("Invalid IPv4 address '{}'", ip))
+        })
+        .transpose()?;
+    let ipv6 = session
+        .ipv6
+        .as_deref()
+        .map(|ip| {
+            ip.parse::<Ipv6Addr>()
+                .map(|ip| ip.to_string())
+                .map_err(|_| format!("Invalid IPv6 address '{}'", ip))
+        })
+        .transpose()?;
+    if ipv4.is_none() && ipv6.is_none() {
+        return Err("Session needs an IPv4 or IPv6 address".to_string());
+    }
+
+    let endpoint = Some(session.endpoint.trim())
+        .filter(|e| !e.is_empty())
+        .map(|e| validate_endpoint(e).map(|_| e.to_string()))
+        .transpose()?;
+
+    let wg_pubkey = session
+        .wg_pubkey
+        .as_deref()
+        .map(str::trim)
+        .map(|key| validate_wg_pubkey(key).map(|_| key.to_string()))
+        .transpose()?;
+
+    let name: String = session
+        .name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let comment = session.comment.as_deref().map(|c| {
+        c.chars()
+            .filter(|c| !c.is_control() && !"\"\\{}".contains(*c))
+            .collect::<String>()
+    });
+
+    Ok(vec![
+        ("name", Some(name)),
+        ("ipv4", ipv4),
+        ("ipv6", ipv6),
+        ("endpoint", endpoint),
+        ("wg_pubkey", wg_pubkey),
+        ("comment", comment),
+    ])
+}
+
+fn substitute(template: &str, values: &[(&str, Option<String>)]) -> String {
+    let mut out = String::new();
+    'lines: for line in template.lines() {
+        let mut line = line.to_string();
+        for (key, value) in values {
+            let placeholder = format!("{{{}}}", key);
+            if !line.contains(&placeholder) {
+                continue;
+            }
+            match value {
+                Some(value) => line = line.replace(&placeholder, value),
+                None => continue 'lines,
+            }
+        }
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+fn file_path(dir: &str, interface: &str) -> String {
+    Path::new(dir)
+        .join(format!("{}.conf", interface))
+        .to_string_lossy()
+        .to_string()
+}
+
+fn write_atomic(path: &Path, content: &str) -> std::io::Result<()> {
+    let tmp = path.with_extension("conf.tmp");
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o640)
+        .open(&tmp)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) const BIRD_TEMPLATE: &str = "\
+protocol bgp {interface} from dnpeers {
+    description \"{comment}\";
+    neighbor {ipv6}%{interface} as {asn};
+    neighbor {ipv4} as {asn};
+}";
+    pub(crate) const WIREGUARD_TEMPLATE: &str = "\
+[Interface]
+ListenPort = 2{asn_short}
+[Peer]
+PublicKey = {wg_pubkey}
+Endpoint = {endpoint}";
+
+    /// An empty directory in the temp dir, with `bird` and `wireguard`
+    /// subdirectories.
+    pub(crate) fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("bird-lg-provision-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("bird")).unwrap();
+        fs::create_dir_all(dir.join("wireguard")).unwrap();
+        dir
+    }
+
+    fn config(dir: &Path) -> ProvisionConfig {
+        ProvisionConfig {
+            secret: "provision-secret".to_string(),
+            bird_dir: dir.join("bird").to_string_lossy().into_owned(),
+            wireguard_dir: dir.join("wireguard").to_string_lossy().into_owned(),
+            bird_template: BIRD_TEMPLATE.to_string(),
+            wireguard_template: WIREGUARD_TEMPLATE.to_string(),
+            interface_prefix: "dn42_".to_string(),
+        }
+    }
+
+    pub(crate) fn session() -> PeeringSession {
+        PeeringSession {
+            id: None,
+            node: "node1".to_string(),
+            name: "peer-1".to_string(),
+            ipv4: Some("172.20.0.1".to_string()),
+            ipv6: Some("fe80::1".to_string()),
+            endpoint: "peer.example.com:51820".to_string(),
+            wg_pubkey: Some("dGVzdGtleXRlc3RrZXl0ZXN0a2V5dGVzdGtleXRlc3Q=".to_string()),
+            comment: Some("Example \"peer\" {1}".to_string()),
+        }
+    }
+
+    #[test]
+    fn substitutes_placeholders() {
+        let dir = test_dir("substitute");
+        let files = render(&config(&dir), 4242420001, &session()).unwrap();
+
+        assert_eq!(
+            files[0].path,
+            dir.join("bird/dn42_0001.conf").to_string_lossy()
+        );
+        assert_eq!(
+            files[0].content,
+            "protocol bgp dn42_0001 from dnpeers {
+    description \"Example peer 1\";
+    neighbor fe80::1%dn42_0001 as 4242420001;
+    neighbor 172.20.0.1 as 4242420001;
+}
+"
+        );
+        assert_eq!(
+            files[1].path,
+            dir.join("wireguard/dn42_0001.conf").to_string_lossy()
+        );
+        assert_eq!(
+            files[1].content,
+            "[Interface]
+ListenPort = 20001
+[Peer]
+PublicKey = dGVzdGtleXRlc3RrZXl0ZXN0a2V5dGVzdGtleXRlc3Q=
+Endpoint = peer.example.com:51820
+"
+        );
+
+        // Only ASNs of the DN42 range are shortened.
+        let files = render(&config(&dir), 64512, &session()).unwrap();
+        assert!(files[0].path.ends_with("dn42_64512.conf"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn drops_lines_with_unset_placeholders() {
+        let dir = test_dir("unset");
+        let session = PeeringSession {
+            ipv4: None,
+            endpoint: String::new(),
+            wg_pubkey: None,
+            comment: None,
+            ..session()
+        };
+        let files = render(&config(&dir), 4242420001, &session).unwrap();
+
+        assert_eq!(
+            files[0].content,
+            "protocol bgp dn42_0001 from dnpeers {
+    neighbor fe80::1%dn42_0001 as 4242420001;
+}
+"
+        );
+        assert_eq!(
+            files[1].content,
+            "[Interface]\nListenPort = 20001\n[Peer]\n"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_invalid_sessions() {
+        let dir = test_dir("invalid");
+        let render = |session| render(&config(&dir), 4242420001, &session);
+
+        let no_addresses = PeeringSession {
+            ipv4: None,
+            ipv6: None,
+            ..session()
+        };
+        assert_eq!(
+            render(no_addresses).unwrap_err(),
+            "Session needs an IPv4 or IPv6 address"
+        );
+        let bad_ipv4 = PeeringSession {
+            ipv4: Some("172.20.0.1; }".to_string()),
+            ..session()
+        };
+        assert_eq!(
+            render(bad_ipv4).unwrap_err(),
+            "Invalid IPv4 address '172.20.0.1; }'"
+        );
+        let bad_endpoint = PeeringSession {
+            endpoint: "peer.example.com".to_string(),
+            ..session()
+        };
+        assert!(render(bad_endpoint).is_err());
+        let bad_key = PeeringSession {
+            wg_pubkey: Some("key\nEndpoint = evil:1".to_string()),
+            ..session()
+        };
+        assert!(render(bad_key).is_err());
+
+        let long_prefix = ProvisionConfig {
+            interface_prefix: "dn42_peer_".to_string(),
+            ..config(&dir)
+        };
+        assert!(super::render(&long_prefix, 4294967295, &session()).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn restores_previous_files() {
+        let dir = test_dir("restore");
+        let files = render(&config(&dir), 4242420001, &session()).unwrap();
+        fs::write(&files[0].path, "old bird config\n").unwrap();
+
+        let previous = write(&files).unwrap();
+        assert_eq!(
+            fs::read_to_string(&files[0].path).unwrap(),
+            files[0].content
+        );
+        assert_eq!(
+            fs::read_to_string(&files[1].path).unwrap(),
+            files[1].content
+        );
+
+        restore(&previous);
+        assert_eq!(
+            fs::read_to_string(&files[0].path).unwrap(),
+            "old bird config\n"
+        );
+        assert!(!Path::new(&files[1].path).exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -11,6 +11,16 @@ pub enum IpVersion {
     Any,
 }
 
+impl IpVersion {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IpVersion::V4 => "ipv4",
+            IpVersion::V6 => "ipv6",
+            IpVersion::Any => "any",
+        }
+    }
+}
+
 pub fn build_traceroute_command(
     config: &Config,
     target: &str,
@@ -1,8 +1,8 @@
-use std::fs;
+use std::{collections::HashMap, fs};
 
 use anyhow::Result;
 pub use common::models::{NetworkInfo, PeeringInfo};
-use common::utils::deserialize_listen_address;
+use common::{auto_peer::ChallengeMethod, utils::deserialize_listen_address};
 use serde::Deserialize;
 
 #[derive(Deserialize, Clone, Debug)]
@@ -14,6 +14,15 @@ pub struct Config {
     pub network: Option<NetworkInfo>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub poll_idle_timeout: Option<u64>,
+    /// Seconds between WireGuard polls of all nodes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wireguard_poll_interval: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registry: Option<RegistryConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub autopeer: Option<AutoPeerConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alerts: Option<AlertsConfig>,
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -22,6 +31,140 @@ pub struct NodeConfig {
     pub url: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub shared_secret: Option<String>,
+    /// One of the proxy's `bird_instances`, its default instance if not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    /// Seconds between protocol polls of this node.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub poll_interval: Option<u64>,
+    /// Seconds to wait for the node to answer a poll.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct RegistryConfig {
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reload_interval: Option<u64>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct AutoPeerConfig {
+    /// SQLite database holding sessions, credentials and audit events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub database_path: Option<String>,
+    /// JSON sessions file written by earlier versions. It is imported into
+    /// the database once, and used to derive `database_path` when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_path: Option<String>,
+    #[serde(default = "default_challenge_methods")]
+    pub challenge_methods: Vec<ChallengeMethod>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub challenge_ttl: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential_ttl: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gpg_bin: Option<String>,
+    /// Wrong codes or signatures accepted per challenge, email or PGP,
+    /// before the session has to start over.
+    #[serde(alias = "email_max_attempts", skip_serializing_if = "Option::is_none")]
+    pub max_attempts: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipv4_ranges: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipv6_ranges: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_sessions_per_asn: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mail: Option<MailConfig>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct MailConfig {
+    pub from: String,
+    #[serde(flatten)]
+    pub transport: MailTransportConfig,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "transport", rename_all = "lowercase")]
+pub enum MailTransportConfig {
+    Smtp {
+        host: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        port: Option<u16>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        username: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        password: Option<String>,
+        #[serde(default)]
+        tls: SmtpTls,
+    },
+    Sendmail {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        command: Option<String>,
+    },
+    File {
+        path: String,
+    },
+    Log,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    #[default]
+    Starttls,
+    Tls,
+    None,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct AlertsConfig {
+    pub rules: Vec<AlertRule>,
+    pub targets: Vec<AlertTarget>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AlertRule {
+    /// A BGP session has not been established for `after` seconds.
+    SessionDown {
+        after: u64,
+        /// Protocol name prefixes to watch; all BGP sessions when empty.
+        #[serde(default)]
+        protocols: Vec<String>,
+    },
+    /// A node has not answered polls for `after` seconds.
+    NodeUnreachable { after: u64 },
+    /// A protocol went down at least `flaps` times in the last `window` seconds.
+    Flapping { flaps: usize, window: i64 },
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AlertTarget {
+    Webhook {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+    Telegram {
+        bot_token: String,
+        chat_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        api_url: Option<String>,
+    },
+    Command {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+fn default_challenge_methods() -> Vec<ChallengeMethod> {
+    vec![ChallengeMethod::Pgp, ChallengeMethod::Email]
 }
 
 impl Config {
@@ -1,5 +1,9 @@
+pub mod autopeer;
+pub mod history;
 pub mod info;
+pub mod metrics;
 pub mod protocol;
+pub mod registry;
 pub mod route;
 pub mod status;
 pub mod traceroute;
@@ -0,0 +1,18 @@
+use axum::{Json, extract::Extension, http::HeaderMap};
+use common::auto_peer::{AutoPeerRequest, AutoPeerResponse};
+
+use crate::services::autopeer::AutoPeer;
+
+pub async fn handle(
+    Extension(autopeer): Extension<AutoPeer>,
+    headers: HeaderMap,
+    Json(req): Json<AutoPeerRequest>,
+) -> Json<AutoPeerResponse> {
+    let token = headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.strip_prefix("Bearer "))
+        .map(str::trim);
+
+    Json(autopeer.handle(token, req).await)
+}
@@ -0,0 +1,28 @@
+use axum::{
+    Json,
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use common::history::ProtocolHistory;
+
+use crate::state::AppState;
+
+pub async fn get_protocol_history(
+    Path((node_name, protocol)): Path<(String, String)>,
+    Extension(state): Extension<AppState>,
+) -> Response {
+    if let Some(history) = state.history.get(&node_name, &protocol) {
+        return Json(history).into_response();
+    }
+
+    let nodes = state.nodes.read().unwrap();
+    let Some(node) = nodes.iter().find(|n| n.name == node_name) else {
+        return (StatusCode::NOT_FOUND, "Node not found").into_response();
+    };
+    if !node.protocols.iter().any(|p| p.name == protocol) {
+        return (StatusCode::NOT_FOUND, "Protocol not found").into_response();
+    }
+
+    Json(ProtocolHistory::new(node_name, protocol, Vec::new())).into_response()
+}
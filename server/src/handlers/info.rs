@@ -20,7 +20,9 @@ pub async fn get_network_info(
         info.peering = peering.clone();
         Json(AppResponse::NetworkInfo(info))
     } else {
-        Json(AppResponse::Error("Network info not available".to_string()))
+        Json(AppResponse::Error {
+            error: "Network info not available".to_string(),
+        })
     }
 }
 
@@ -32,9 +34,9 @@ pub async fn get_network_info_with_port(
     if !(20000..=29999).contains(&port) {
         return (
             StatusCode::BAD_REQUEST,
-            Json(AppResponse::Error(
-                "Port must be between 20000 and 29999".to_string(),
-            )),
+            Json(AppResponse::Error {
+                error: "Port must be between 20000 and 29999".to_string(),
+            }),
         )
             .into_response();
     }
@@ -52,7 +54,10 @@ pub async fn get_network_info_with_port(
         info.peering = modified_peering;
         Json(AppResponse::NetworkInfo(info)).into_response()
     } else {
-        Json(AppResponse::Error("Network info not available".to_string())).into_response()
+        Json(AppResponse::Error {
+            error: "Network info not available".to_string(),
+        })
+        .into_response()
     }
 }
 
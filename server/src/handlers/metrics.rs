@@ -0,0 +1,28 @@
+use std::sync::atomic::Ordering;
+
+use axum::{
+    extract::Extension,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use tracing::error;
+
+use crate::state::AppState;
+
+pub async fn get_metrics(Extension(state): Extension<AppState>) -> Response {
+    let nodes = state.nodes.read().unwrap().clone();
+    let wireguard = state.wireguard.read().unwrap().clone();
+    let connections = state.active_connections.load(Ordering::Relaxed);
+
+    match state.metrics.render(&nodes, connections, &wireguard) {
+        Ok(body) => ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to encode metrics");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to encode metrics",
+            )
+                .into_response()
+        }
+    }
+}
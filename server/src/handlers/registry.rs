@@ -0,0 +1,32 @@
+use axum::{
+    Json,
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+
+use crate::registry::{Registry, parse_asn, parse_prefix};
+
+pub async fn get_asn(
+    Path(asn): Path<String>,
+    Extension(registry): Extension<Registry>,
+) -> Response {
+    let Some(asn) = parse_asn(&asn) else {
+        return (StatusCode::BAD_REQUEST, "Invalid ASN").into_response();
+    };
+
+    match registry.asn(asn) {
+        Some(info) => Json(info).into_response(),
+        None => (StatusCode::NOT_FOUND, "ASN not found").into_response(),
+    }
+}
+
+pub async fn get_prefix(
+    Path(prefix): Path<String>,
+    Extension(registry): Extension<Registry>,
+) -> Response {
+    match parse_prefix(&prefix) {
+        Some(prefix) => Json(registry.prefix(prefix)).into_response(),
+        None => (StatusCode::BAD_REQUEST, "Invalid prefix").into_response(),
+    }
+}
@@ -11,6 +11,7 @@ use axum::{
     response::IntoResponse,
 };
 use futures_util::{SinkExt, Stream, StreamExt, stream};
+use tokio::sync::broadcast::error::RecvError;
 
 use crate::{
     config::Config,
@@ -59,26 +60,48 @@ async fn handle_socket(socket: WebSocket, state: AppState, config: Arc<Config>)
         return;
     }
 
-    let state_for_wg = state.clone();
-    let config_for_wg = config.clone();
-    tokio::spawn(async move {
-        let wg_stream =
-            crate::services::api::get_wireguard(state_for_wg.clone(), config_for_wg).await;
-        let mut stream = Box::pin(wg_stream);
-        if let Some(resp) = stream.next().await {
-            let _ = state_for_wg.tx.send(resp);
-        }
-    });
-
     let (tx, mut mpsc_rx) = tokio::sync::mpsc::unbounded_channel();
 
+    // Later WireGuard changes arrive as broadcast diffs against this snapshot.
+    // Before the first WireGuard poll finished, fetch it for this client.
+    let wireguard = state.wireguard.read().unwrap().clone();
+    if wireguard.is_empty() {
+        let state_for_wg = state.clone();
+        let config_for_wg = config.clone();
+        let tx_for_wg = tx.clone();
+        tokio::spawn(async move {
+            let mut stream = crate::services::api::get_wireguard(state_for_wg, config_for_wg).await;
+            if let Some(resp) = stream.next().await {
+                let _ = tx_for_wg.send(resp);
+            }
+        });
+    } else {
+        let _ = tx.send(AppResponse::WireGuard { data: wireguard });
+    }
+
+    let tunnels = state.tunnels.read().unwrap().clone();
+    if !tunnels.is_empty() {
+        let _ = tx.send(AppResponse::Tunnels { data: tunnels });
+    }
+
+    let state_for_send = state.clone();
     let mut send_task = tokio::spawn(async move {
         loop {
             tokio::select! {
-                Ok(broadcast_msg) = rx.recv() => {
-                    if let Ok(json) = serde_json::to_string(&broadcast_msg)
-                        && sender.send(Message::Text(json.into())).await.is_err()
-                    {
+                broadcast_msg = rx.recv() => {
+                    let Some(messages) = broadcast_messages(broadcast_msg, &state_for_send) else {
+                        break;
+                    };
+                    let mut failed = false;
+                    for msg in messages {
+                        if let Ok(json) = serde_json::to_string(&msg)
+                            && sender.send(Message::Text(json.into())).await.is_err()
+                        {
+                            failed = true;
+                            break;
+                        }
+                    }
+                    if failed {
                         tracing::error!("Failed to send broadcast update");
                         break;
                     }
@@ -131,6 +154,38 @@ async fn handle_socket(socket: WebSocket, state: AppState, config: Arc<Config>)
     }
 }
 
+/// What to send a client for a broadcast, `None` once the channel closed.
+/// Updates are diffs, so a client that lagged and missed some gets the full
+/// state again instead.
+fn broadcast_messages(
+    received: Result<AppResponse, RecvError>,
+    state: &AppState,
+) -> Option<Vec<AppResponse>> {
+    match received {
+        Ok(msg) => Some(vec![msg]),
+        Err(RecvError::Lagged(skipped)) => {
+            tracing::warn!(skipped, "WebSocket client lagged, resending full state");
+            Some(snapshot(state))
+        }
+        Err(RecvError::Closed) => None,
+    }
+}
+
+/// The full state that broadcasts are diffs against.
+fn snapshot(state: &AppState) -> Vec<AppResponse> {
+    vec![
+        AppResponse::Protocols {
+            data: state.nodes.read().unwrap().clone(),
+        },
+        AppResponse::WireGuard {
+            data: state.wireguard.read().unwrap().clone(),
+        },
+        AppResponse::Tunnels {
+            data: state.tunnels.read().unwrap().clone(),
+        },
+    ]
+}
+
 async fn handle_request(
     req: AppRequest,
     state: AppState,
@@ -171,3 +226,69 @@ async fn handle_request(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use common::models::{NodeProtocol, NodeWireGuard};
+    use serde_json::{Value, json};
+    use tokio::sync::broadcast;
+
+    use super::*;
+
+    fn json(messages: &[AppResponse]) -> Vec<Value> {
+        messages
+            .iter()
+            .map(|m| serde_json::to_value(m).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn lagged_client_gets_snapshot() {
+        let state = AppState::new();
+        let now = Utc::now();
+        *state.nodes.write().unwrap() = vec![NodeProtocol {
+            name: "node1".to_string(),
+            protocols: Vec::new(),
+            last_updated: now,
+            error: None,
+        }];
+        *state.wireguard.write().unwrap() = vec![NodeWireGuard {
+            name: "node1".to_string(),
+            interfaces: Vec::new(),
+            last_updated: now,
+            error: None,
+        }];
+
+        let mut rx = state.tx.subscribe();
+        for _ in 0..20 {
+            state
+                .tx
+                .send(AppResponse::NoChange { last_updated: now })
+                .unwrap();
+        }
+
+        let messages = broadcast_messages(rx.recv().await, &state).unwrap();
+        assert_eq!(json(&messages), json(&snapshot(&state)));
+        let tags: Vec<Value> = json(&messages)
+            .into_iter()
+            .map(|m| m["t"].clone())
+            .collect();
+        assert_eq!(tags, [json!("pr"), json!("wg"), json!("tn")]);
+
+        // Broadcasts that are still buffered follow as usual.
+        let messages = broadcast_messages(rx.recv().await, &state).unwrap();
+        assert_eq!(
+            json(&messages),
+            json(&[AppResponse::NoChange { last_updated: now }])
+        );
+    }
+
+    #[tokio::test]
+    async fn closed_channel_ends_the_client() {
+        let state = AppState::new();
+        let (tx, mut rx) = broadcast::channel::<AppResponse>(1);
+        drop(tx);
+        assert!(broadcast_messages(rx.recv().await, &state).is_none());
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, RwLock},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use common::{
+    history::{ProtocolHistory, StateTransition, protocol_status},
+    models::Protocol,
+};
+
+const MAX_TRANSITIONS: usize = 1000;
+const RETENTION_DAYS: i64 = 7;
+
+/// Protocol state transitions per node, kept in memory for the last
+/// `RETENTION_DAYS` days.
+#[derive(Clone, Default)]
+pub struct History {
+    nodes: Arc<RwLock<HashMap<String, NodeHistory>>>,
+}
+
+#[derive(Default)]
+struct NodeHistory {
+    /// The last recorded status of every protocol.
+    statuses: HashMap<String, String>,
+    transitions: HashMap<String, VecDeque<StateTransition>>,
+}
+
+impl History {
+    /// Records every protocol whose status changed since the last poll of
+    /// `node`. The first poll of a node only seeds the statuses, as there is
+    /// nothing to compare with; protocols that appear later are recorded
+    /// with no previous state.
+    pub fn record(&self, node: &str, protocols: &[Protocol], now: DateTime<Utc>) {
+        let mut nodes = self.nodes.write().unwrap();
+        let Some(history) = nodes.get_mut(node) else {
+            let statuses = protocols
+                .iter()
+                .map(|p| (p.name.clone(), protocol_status(p)))
+                .collect();
+            nodes.insert(
+                node.to_string(),
+                NodeHistory {
+                    statuses,
+                    transitions: HashMap::new(),
+                },
+            );
+            return;
+        };
+
+        let cutoff = now - Duration::days(RETENTION_DAYS);
+        for protocol in protocols {
+            let to = protocol_status(protocol);
+            let from = history.statuses.insert(protocol.name.clone(), to.clone());
+            if from.as_ref() == Some(&to) {
+                continue;
+            }
+
+            let transitions = history
+                .transitions
+                .entry(protocol.name.clone())
+                .or_default();
+            transitions.push_back(StateTransition {
+                timestamp: now,
+                from,
+                to,
+                reason: protocol.reason.clone(),
+            });
+            while transitions.len() > MAX_TRANSITIONS
+                || transitions.front().is_some_and(|t| t.timestamp < cutoff)
+            {
+                transitions.pop_front();
+            }
+        }
+    }
+
+    pub fn get(&self, node: &str, protocol: &str) -> Option<ProtocolHistory> {
+        let nodes = self.nodes.read().unwrap();
+        let transitions = nodes.get(node)?.transitions.get(protocol)?;
+        Some(ProtocolHistory::new(
+            node.to_string(),
+            protocol.to_string(),
+            transitions.iter().cloned().collect(),
+        ))
+    }
+
+    pub fn flap_count(
+        &self,
+        node: &str,
+        protocol: &str,
+        window_secs: i64,
+        now: DateTime<Utc>,
+    ) -> usize {
+        let since = now - Duration::seconds(window_secs);
+        let nodes = self.nodes.read().unwrap();
+        nodes
+            .get(node)
+            .and_then(|history| history.transitions.get(protocol))
+            .map(|transitions| {
+                transitions
+                    .iter()
+                    .filter(|t| t.timestamp >= since && t.is_flap())
+                    .count()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn protocol(name: &str, state: &str, info: &str) -> Protocol {
+        Protocol {
+            name: name.to_string(),
+            proto: "BGP".to_string(),
+            table: "---".to_string(),
+            state: state.to_string(),
+            since: "12:00:00".to_string(),
+            info: info.to_string(),
+            reason: None,
+        }
+    }
+
+    fn statuses(history: &History, name: &str) -> Vec<(Option<String>, String)> {
+        history
+            .get("node1", name)
+            .map(|h| h.transitions.into_iter().map(|t| (t.from, t.to)).collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn first_poll_only_seeds() {
+        let history = History::default();
+        let now = Utc::now();
+        history.record("node1", &[protocol("a", "up", "Established")], now);
+        assert!(history.get("node1", "a").is_none());
+
+        history.record(
+            "node1",
+            &[
+                protocol("a", "up", "Established"),
+                protocol("b", "start", "Connect"),
+            ],
+            now,
+        );
+        assert!(history.get("node1", "a").is_none());
+        assert_eq!(statuses(&history, "b"), [(None, "start".to_string())]);
+    }
+
+    #[test]
+    fn records_state_changes_only() {
+        let history = History::default();
+        let now = Utc::now();
+        history.record("node1", &[protocol("a", "start", "Active")], now);
+        for info in ["Connect", "Active", "Connect", "OpenSent"] {
+            history.record("node1", &[protocol("a", "start", info)], now);
+        }
+        assert!(history.get("node1", "a").is_none());
+
+        history.record("node1", &[protocol("a", "up", "Established")], now);
+        history.record("node1", &[protocol("a", "start", "Active")], now);
+        assert_eq!(
+            statuses(&history, "a"),
+            [
+                (Some("start".to_string()), "up".to_string()),
+                (Some("up".to_string()), "start".to_string()),
+            ]
+        );
+        assert_eq!(history.flap_count("node1", "a", 60, now), 1);
+    }
+}
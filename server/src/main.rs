@@ -1,7 +1,9 @@
 mod cli;
 mod config;
-mod diff;
 mod handlers;
+mod history;
+mod metrics;
+mod registry;
 mod services;
 mod state;
 mod utils;
@@ -10,10 +12,10 @@ use std::{net::SocketAddr, sync::Arc};
 
 use axum::{
     Extension, Router,
-    extract::Request,
+    extract::{MatchedPath, Request},
     middleware::{self, Next},
     response::Response,
-    routing::get,
+    routing::{get, post},
 };
 use tokio::net::TcpListener;
 use tower_http::cors::CorsLayer;
@@ -21,11 +23,14 @@ use tower_http::cors::CorsLayer;
 use crate::{
     cli::Cli,
     config::Config,
-    handlers::{info, protocol, route, status, traceroute, ws},
-    services::poller,
+    handlers::{autopeer, info, protocol, route, status, traceroute, ws},
+    registry::Registry,
+    services::{autopeer::AutoPeer, poller},
     state::AppState,
 };
 
+const METRICS_PATH: &str = "/metrics";
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
@@ -39,7 +44,21 @@ async fn main() -> anyhow::Result<()> {
     let state = AppState::new();
     poller::spawn(state.clone(), config.clone());
 
-    let app = Router::new()
+    let registry = config.registry.as_ref().map(Registry::load).transpose()?;
+    if let (Some(registry), Some(registry_config)) = (&registry, &config.registry) {
+        registry::spawn(registry.clone(), registry_config);
+    }
+
+    let autopeer_service = config
+        .autopeer
+        .clone()
+        .map(|c| {
+            let nodes = config.nodes.iter().map(|n| n.name.clone()).collect();
+            AutoPeer::load(c, nodes, registry.clone())
+        })
+        .transpose()?;
+
+    let mut app = Router::new()
         .route("/api/protocols", get(status::get_all_protocols))
         .route(
             "/api/protocols/{node_name}",
@@ -54,6 +73,10 @@ async fn main() -> anyhow::Result<()> {
             get(traceroute::proxy_traceroute),
         )
         .route("/api/routes/{node_name}", get(route::get_route))
+        .route(
+            "/api/history/{node_name}/{protocol}",
+            get(handlers::history::get_protocol_history),
+        )
         .route("/api/info", get(info::get_network_info))
         .route(
             "/api/info/port/{port}",
@@ -61,6 +84,25 @@ async fn main() -> anyhow::Result<()> {
         )
         .route("/api/peering/{node_name}", get(info::get_node_peering))
         .route("/api/ws", get(ws::ws_handler))
+        .route(METRICS_PATH, get(handlers::metrics::get_metrics));
+
+    if let Some(registry) = registry {
+        app = app
+            .route("/api/registry/asn/{asn}", get(handlers::registry::get_asn))
+            .route(
+                "/api/registry/prefix/{prefix}",
+                get(handlers::registry::get_prefix),
+            )
+            .layer(Extension(registry));
+    }
+
+    if let Some(autopeer_service) = autopeer_service {
+        app = app
+            .route("/autopeer", post(autopeer::handle))
+            .layer(Extension(autopeer_service));
+    }
+
+    let app = app
         .layer(CorsLayer::permissive())
         .layer(middleware::from_fn(track_request))
         .layer(Extension(state))
@@ -108,6 +150,23 @@ async fn track_request(
     request: Request,
     next: Next,
 ) -> Response {
-    state.record_request();
-    next.run(request).await
+    let method = request.method().to_string();
+    // Label by route pattern rather than the raw path to keep the number of
+    // series bounded.
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    // Scrapes must not keep polling alive, only visitors and API clients.
+    if path != METRICS_PATH {
+        state.record_request();
+    }
+
+    let response = next.run(request).await;
+    state
+        .metrics
+        .record_request(&method, &path, response.status().as_u16());
+    response
 }
@@ -0,0 +1,363 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use prometheus::{
+    Encoder, GaugeVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+
+use crate::state::{NodeProtocol, NodeWireGuard};
+
+const NAMESPACE: &str = "birdlg";
+
+/// Prometheus metrics of the server. Poll and request metrics are recorded
+/// as they happen; node, protocol and WireGuard metrics are built from the
+/// polled state on every scrape.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    poll_duration: GaugeVec,
+    poll_errors: IntCounterVec,
+    requests: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+
+        let poll_duration = GaugeVec::new(
+            opts(
+                "poll_duration_seconds",
+                "Duration of the last protocol poll of the node",
+            ),
+            &["node"],
+        )
+        .unwrap();
+        let poll_errors = IntCounterVec::new(
+            opts("poll_errors_total", "Failed protocol polls of the node"),
+            &["node"],
+        )
+        .unwrap();
+        let requests = IntCounterVec::new(
+            opts("http_requests_total", "HTTP requests handled by the server"),
+            &["method", "path", "status"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(poll_duration.clone())).unwrap();
+        registry.register(Box::new(poll_errors.clone())).unwrap();
+        registry.register(Box::new(requests.clone())).unwrap();
+
+        Self {
+            registry,
+            poll_duration,
+            poll_errors,
+            requests,
+        }
+    }
+
+    pub fn record_poll(&self, node: &str, duration: Duration, success: bool) {
+        self.poll_duration
+            .with_label_values(&[node])
+            .set(duration.as_secs_f64());
+        if !success {
+            self.poll_errors.with_label_values(&[node]).inc();
+        }
+    }
+
+    pub fn record_request(&self, method: &str, path: &str, status: u16) {
+        self.requests
+            .with_label_values(&[method, path, &status.to_string()])
+            .inc();
+    }
+
+    /// Renders all metrics in the Prometheus text format. State metrics go
+    /// into a registry of their own for each scrape, so removed nodes,
+    /// protocols and peers disappear and concurrent scrapes don't overwrite
+    /// each other's values.
+    pub fn render(
+        &self,
+        nodes: &[NodeProtocol],
+        websocket_connections: usize,
+        wireguard: &[NodeWireGuard],
+    ) -> Result<String, String> {
+        let state =
+            state_registry(nodes, websocket_connections, wireguard).map_err(|e| e.to_string())?;
+        let mut families = self.registry.gather();
+        families.extend(state.gather());
+        families.sort_by(|a, b| a.name().cmp(b.name()));
+
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&families, &mut buffer)
+            .map_err(|e| e.to_string())?;
+        String::from_utf8(buffer).map_err(|e| e.to_string())
+    }
+}
+
+/// Node, protocol, WebSocket and WireGuard metrics of the polled state.
+fn state_registry(
+    nodes: &[NodeProtocol],
+    websocket_connections: usize,
+    wireguard: &[NodeWireGuard],
+) -> prometheus::Result<Registry> {
+    let registry = Registry::new();
+
+    let node_up = IntGaugeVec::new(
+        opts("node_up", "Whether the last poll of the node succeeded"),
+        &["node"],
+    )?;
+    let protocol_state = IntGaugeVec::new(
+        opts(
+            "protocol_state",
+            "Current state of a BIRD protocol, 1 for the state it is in",
+        ),
+        &["node", "protocol", "proto", "state"],
+    )?;
+    for node in nodes {
+        node_up
+            .with_label_values(&[&node.name])
+            .set(node.error.is_none() as i64);
+        for protocol in &node.protocols {
+            protocol_state
+                .with_label_values(&[&node.name, &protocol.name, &protocol.proto, &protocol.state])
+                .set(1);
+        }
+    }
+
+    let websocket = IntGauge::with_opts(opts(
+        "websocket_connections",
+        "Currently open WebSocket connections",
+    ))?;
+    websocket.set(websocket_connections as i64);
+
+    // The byte counts are kept by the kernel and only start over when the
+    // interface is recreated, which Prometheus handles as a counter reset.
+    let wireguard_rx = IntCounterVec::new(
+        opts(
+            "wireguard_rx_bytes_total",
+            "Bytes received from a WireGuard peer",
+        ),
+        &["node", "interface", "peer"],
+    )?;
+    let wireguard_tx = IntCounterVec::new(
+        opts("wireguard_tx_bytes_total", "Bytes sent to a WireGuard peer"),
+        &["node", "interface", "peer"],
+    )?;
+    let wireguard_handshake_age = IntGaugeVec::new(
+        opts(
+            "wireguard_handshake_age_seconds",
+            "Seconds since the last handshake with a WireGuard peer",
+        ),
+        &["node", "interface", "peer"],
+    )?;
+    let now = Utc::now().timestamp();
+    for node in wireguard {
+        for (interface, index, peer) in node
+            .interfaces
+            .iter()
+            .flat_map(|i| i.peers.iter().enumerate().map(move |(n, p)| (i, n, p)))
+        {
+            // Peers are told apart by public key unless the proxy redacts it.
+            let peer_label = peer.public_key.clone().unwrap_or_else(|| index.to_string());
+            let labels = [
+                node.name.as_str(),
+                interface.name.as_str(),
+                peer_label.as_str(),
+            ];
+            wireguard_rx
+                .with_label_values(&labels)
+                .inc_by(peer.rx_bytes);
+            wireguard_tx
+                .with_label_values(&labels)
+                .inc_by(peer.tx_bytes);
+            // Peers that never completed a handshake have no age.
+            if let Some(handshake) = peer.latest_handshake {
+                wireguard_handshake_age
+                    .with_label_values(&labels)
+                    .set(now - handshake);
+            }
+        }
+    }
+
+    registry.register(Box::new(node_up))?;
+    registry.register(Box::new(protocol_state))?;
+    registry.register(Box::new(websocket))?;
+    registry.register(Box::new(wireguard_rx))?;
+    registry.register(Box::new(wireguard_tx))?;
+    registry.register(Box::new(wireguard_handshake_age))?;
+    Ok(registry)
+}
+
+fn opts(name: &str, help: &str) -> Opts {
+    Opts::new(name, help).namespace(NAMESPACE)
+}
+
+#[cfg(test)]
+mod tests {
+    use common::models::{Protocol, WireGuardInterface, WireGuardPeer};
+
+    use super::*;
+
+    fn node(name: &str, protocols: &[(&str, &str)], error: Option<&str>) -> NodeProtocol {
+        NodeProtocol {
+            name: name.to_string(),
+            protocols: protocols
+                .iter()
+                .map(|(name, state)| Protocol {
+                    name: name.to_string(),
+                    proto: "BGP".to_string(),
+                    table: "---".to_string(),
+                    state: state.to_string(),
+                    since: "2025-01-01".to_string(),
+                    info: String::new(),
+                    reason: None,
+                })
+                .collect(),
+            last_updated: Utc::now(),
+            error: error.map(str::to_string),
+        }
+    }
+
+    fn peer(public_key: Option<&str>, latest_handshake: Option<i64>) -> WireGuardPeer {
+        WireGuardPeer {
+            public_key: public_key.map(str::to_string),
+            endpoint: None,
+            allowed_ips: Vec::new(),
+            latest_handshake,
+            rx_bytes: 1024,
+            tx_bytes: 2048,
+            persistent_keepalive: None,
+        }
+    }
+
+    fn wireguard(name: &str, peers: Vec<WireGuardPeer>) -> NodeWireGuard {
+        NodeWireGuard {
+            name: name.to_string(),
+            interfaces: vec![WireGuardInterface {
+                name: "wg0".to_string(),
+                public_key: None,
+                listen_port: None,
+                peers,
+                protocols: Vec::new(),
+            }],
+            last_updated: Utc::now(),
+            error: None,
+        }
+    }
+
+    fn lines(output: &str) -> Vec<&str> {
+        output
+            .lines()
+            .filter(|l| !l.starts_with("# HELP"))
+            .collect()
+    }
+
+    #[test]
+    fn renders_state() {
+        let metrics = Metrics::new();
+        metrics.record_poll("node1", Duration::from_millis(250), true);
+        metrics.record_poll("node2", Duration::from_secs(5), false);
+        metrics.record_request("GET", "/api/summary", 200);
+
+        let nodes = [
+            node("node1", &[("peer1", "up"), ("peer2", "start")], None),
+            node("node2", &[], Some("Node is not reachable")),
+        ];
+        let handshake = Utc::now().timestamp() - 30;
+        let wireguard = [wireguard(
+            "node1",
+            vec![peer(Some("key1"), Some(handshake)), peer(None, None)],
+        )];
+        let output = metrics.render(&nodes, 3, &wireguard).unwrap();
+        let lines = lines(&output);
+
+        for expected in [
+            "# TYPE birdlg_node_up gauge",
+            r#"birdlg_node_up{node="node1"} 1"#,
+            r#"birdlg_node_up{node="node2"} 0"#,
+            r#"birdlg_protocol_state{node="node1",proto="BGP",protocol="peer1",state="up"} 1"#,
+            r#"birdlg_protocol_state{node="node1",proto="BGP",protocol="peer2",state="start"} 1"#,
+            "birdlg_websocket_connections 3",
+            r#"birdlg_poll_duration_seconds{node="node1"} 0.25"#,
+            r#"birdlg_poll_errors_total{node="node2"} 1"#,
+            r#"birdlg_http_requests_total{method="GET",path="/api/summary",status="200"} 1"#,
+            "# TYPE birdlg_wireguard_rx_bytes_total counter",
+            r#"birdlg_wireguard_rx_bytes_total{interface="wg0",node="node1",peer="key1"} 1024"#,
+            "# TYPE birdlg_wireguard_tx_bytes_total counter",
+            r#"birdlg_wireguard_tx_bytes_total{interface="wg0",node="node1",peer="key1"} 2048"#,
+            // Redacted keys are labelled by position.
+            r#"birdlg_wireguard_rx_bytes_total{interface="wg0",node="node1",peer="1"} 1024"#,
+        ] {
+            assert!(
+                lines.contains(&expected),
+                "{} missing in\n{}",
+                expected,
+                output
+            );
+        }
+
+        let age = lines
+            .iter()
+            .find_map(|l| {
+                l.strip_prefix(
+                    r#"birdlg_wireguard_handshake_age_seconds{interface="wg0",node="node1",peer="key1"} "#,
+                )
+            })
+            .unwrap();
+        assert!((30..35).contains(&age.parse::<i64>().unwrap()), "{}", age);
+        // No handshake, no age.
+        assert!(!output.contains(
+            r#"birdlg_wireguard_handshake_age_seconds{interface="wg0",node="node1",peer="1"}"#
+        ));
+
+        // Families are in name order, each with a single TYPE line.
+        let types: Vec<&str> = lines
+            .iter()
+            .filter_map(|l| l.strip_prefix("# TYPE "))
+            .collect();
+        let mut sorted = types.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(types, sorted);
+    }
+
+    #[test]
+    fn removed_state_disappears() {
+        let metrics = Metrics::new();
+        let nodes = [node("node1", &[("peer1", "up")], None)];
+        let wireguard = [wireguard("node1", vec![peer(Some("key1"), None)])];
+        let output = metrics.render(&nodes, 1, &wireguard).unwrap();
+        assert!(output.contains("peer1"));
+        assert!(output.contains("key1"));
+
+        let output = metrics.render(&[node("node1", &[], None)], 0, &[]).unwrap();
+        assert!(output.contains(r#"birdlg_node_up{node="node1"} 1"#));
+        assert!(!output.contains("peer1"));
+        assert!(!output.contains("key1"));
+    }
+
+    #[test]
+    fn concurrent_scrapes_are_consistent() {
+        let metrics = Metrics::new();
+        std::thread::scope(|scope| {
+            for thread in 0..8 {
+                let metrics = &metrics;
+                scope.spawn(move || {
+                    let name = format!("node{}", thread);
+                    let nodes = [node(&name, &[("peer1", "up")], None)];
+                    for _ in 0..50 {
+                        let output = metrics.render(&nodes, thread, &[]).unwrap();
+                        let up: Vec<&str> = output
+                            .lines()
+                            .filter(|l| l.starts_with("birdlg_node_up{"))
+                            .collect();
+                        assert_eq!(up, [format!(r#"birdlg_node_up{{node="{}"}} 1"#, name)]);
+                        assert!(
+                            output.contains(&format!("birdlg_websocket_connections {}\n", thread))
+                        );
+                    }
+                });
+            }
+        });
+    }
+}
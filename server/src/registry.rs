@@ -0,0 +1,441 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use anyhow::{Context, bail};
+use common::registry::{AsnInfo, PrefixInfo, RegistryObject};
+use ipnet::IpNet;
+use tracing::{info, warn};
+
+use crate::config::RegistryConfig;
+
+const DEFAULT_RELOAD_INTERVAL: u64 = 3600;
+
+#[derive(Clone)]
+pub struct Registry {
+    path: PathBuf,
+    index: Arc<RwLock<Arc<RegistryIndex>>>,
+}
+
+#[derive(Default)]
+struct RegistryIndex {
+    aut_nums: HashMap<u32, RegistryObject>,
+    mntners: HashMap<String, RegistryObject>,
+    contacts: HashMap<String, RegistryObject>,
+    routes: PrefixIndex,
+    networks: PrefixIndex,
+}
+
+/// Objects keyed by their network. The networks covering a prefix take one
+/// lookup per prefix length in use rather than a scan of all objects.
+#[derive(Default)]
+struct PrefixIndex {
+    objects: HashMap<IpNet, Vec<RegistryObject>>,
+    /// Prefix lengths in use, keyed by whether they are IPv6.
+    lengths: BTreeSet<(bool, u8)>,
+}
+
+impl Registry {
+    pub fn load(config: &RegistryConfig) -> anyhow::Result<Self> {
+        let path = PathBuf::from(&config.path);
+        if !path.join("data").is_dir() {
+            bail!("registry path '{}' has no data directory", config.path);
+        }
+
+        let index = RegistryIndex::load(&path)?;
+        Ok(Self {
+            path,
+            index: Arc::new(RwLock::new(Arc::new(index))),
+        })
+    }
+
+    fn index(&self) -> Arc<RegistryIndex> {
+        self.index.read().unwrap().clone()
+    }
+
+    pub fn asn(&self, asn: u32) -> Option<AsnInfo> {
+        let index = self.index();
+        let aut_num = index.aut_nums.get(&asn)?.clone();
+
+        let mntners = aut_num
+            .get_all("mnt-by")
+            .filter_map(|m| index.mntners.get(m).cloned())
+            .collect();
+        let contacts = aut_num
+            .get_all("admin-c")
+            .chain(aut_num.get_all("tech-c"))
+            .filter_map(|h| index.contacts.get(h).cloned())
+            .fold(Vec::new(), |mut acc: Vec<RegistryObject>, c| {
+                if !acc.contains(&c) {
+                    acc.push(c);
+                }
+                acc
+            });
+
+        Some(AsnInfo {
+            asn,
+            name: aut_num.get("as-name").map(str::to_string),
+            aut_num,
+            mntners,
+            contacts,
+        })
+    }
+
+    pub fn prefix(&self, prefix: IpNet) -> PrefixInfo {
+        let index = self.index();
+        PrefixInfo {
+            prefix: prefix.to_string(),
+            routes: index.routes.covering(&prefix),
+            networks: index.networks.covering(&prefix),
+        }
+    }
+
+    pub fn pgp_fingerprints(&self, asn: u32) -> Result<Vec<String>, String> {
+        let info = self
+            .asn(asn)
+            .ok_or_else(|| format!("AS{} is not registered", asn))?;
+
+        let mut fingerprints = Vec::new();
+        for auth in info.mntners.iter().flat_map(|m| m.get_all("auth")) {
+            let mut parts = auth.split_whitespace();
+            if parts.next() == Some("pgp-fingerprint") {
+                let fingerprint = parts.collect::<String>().to_ascii_uppercase();
+                if !fingerprint.is_empty() && !fingerprints.contains(&fingerprint) {
+                    fingerprints.push(fingerprint);
+                }
+            }
+        }
+
+        Ok(fingerprints)
+    }
+
+    pub fn contact_emails(&self, asn: u32) -> Result<Vec<String>, String> {
+        let info = self
+            .asn(asn)
+            .ok_or_else(|| format!("AS{} is not registered", asn))?;
+
+        let mut emails: Vec<String> = Vec::new();
+        for email in info.contacts.iter().flat_map(|c| c.get_all("e-mail")) {
+            if !emails.iter().any(|e| e.eq_ignore_ascii_case(email)) {
+                emails.push(email.to_string());
+            }
+        }
+
+        Ok(emails)
+    }
+}
+
+pub fn spawn(registry: Registry, config: &RegistryConfig) {
+    let interval = Duration::from_secs(config.reload_interval.unwrap_or(DEFAULT_RELOAD_INTERVAL));
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+
+            let path = registry.path.clone();
+            match tokio::task::spawn_blocking(move || RegistryIndex::load(&path)).await {
+                Ok(Ok(index)) => *registry.index.write().unwrap() = Arc::new(index),
+                Ok(Err(e)) => {
+                    warn!(error = ?e, "Failed to reload registry, keeping previous index")
+                }
+                Err(e) => warn!(error = ?e, "Registry reload task failed"),
+            }
+        }
+    });
+}
+
+impl RegistryIndex {
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        let data = path.join("data");
+        let mut index = Self::default();
+
+        for object in read_dir(&data.join("aut-num"))? {
+            if let Some(asn) = object
+                .key
+                .strip_prefix("AS")
+                .and_then(|n| n.parse::<u32>().ok())
+            {
+                index.aut_nums.insert(asn, object);
+            }
+        }
+        for object in read_dir(&data.join("mntner"))? {
+            index.mntners.insert(object.key.clone(), object);
+        }
+        for class in ["person", "role"] {
+            for object in read_dir(&data.join(class))? {
+                let handle = object.get("nic-hdl").unwrap_or(&object.key).to_string();
+                index.contacts.insert(handle, object);
+            }
+        }
+        for class in ["route", "route6"] {
+            for object in read_dir(&data.join(class))? {
+                if let Ok(net) = object.key.parse::<IpNet>() {
+                    index.routes.insert(net, object);
+                }
+            }
+        }
+        for class in ["inetnum", "inet6num"] {
+            for object in read_dir(&data.join(class))? {
+                if let Some(net) = object.get("cidr").and_then(|c| c.parse::<IpNet>().ok()) {
+                    index.networks.insert(net, object);
+                }
+            }
+        }
+
+        info!(
+            aut_nums = index.aut_nums.len(),
+            mntners = index.mntners.len(),
+            contacts = index.contacts.len(),
+            routes = index.routes.len(),
+            networks = index.networks.len(),
+            "Loaded registry from {}",
+            path.display()
+        );
+        Ok(index)
+    }
+}
+
+impl PrefixIndex {
+    fn insert(&mut self, net: IpNet, object: RegistryObject) {
+        let net = net.trunc();
+        self.lengths.insert((is_ipv6(&net), net.prefix_len()));
+        self.objects.entry(net).or_default().push(object);
+    }
+
+    fn len(&self) -> usize {
+        self.objects.values().map(Vec::len).sum()
+    }
+
+    /// Objects of the networks containing `prefix`, most specific first.
+    fn covering(&self, prefix: &IpNet) -> Vec<RegistryObject> {
+        let family = is_ipv6(prefix);
+        self.lengths
+            .range((family, 0)..=(family, prefix.prefix_len()))
+            .rev()
+            .filter_map(|&(_, len)| {
+                let net = IpNet::new(prefix.addr(), len).ok()?.trunc();
+                self.objects.get(&net)
+            })
+            .flatten()
+            .cloned()
+            .collect()
+    }
+}
+
+fn is_ipv6(net: &IpNet) -> bool {
+    matches!(net, IpNet::V6(_))
+}
+
+pub fn parse_asn(asn: &str) -> Option<u32> {
+    let asn = asn.trim();
+    let digits = asn
+        .strip_prefix("AS")
+        .or_else(|| asn.strip_prefix("as"))
+        .unwrap_or(asn);
+    digits.parse::<u32>().ok()
+}
+
+pub fn parse_prefix(prefix: &str) -> Option<IpNet> {
+    let prefix = prefix.trim().replace('_', "/");
+    prefix
+        .parse::<IpNet>()
+        .ok()
+        .or_else(|| prefix.parse::<IpAddr>().ok().map(IpNet::from))
+        .map(|net| net.trunc())
+}
+
+fn read_dir(dir: &Path) -> anyhow::Result<Vec<RegistryObject>> {
+    if !dir.is_dir() {
+        warn!("Registry directory {} is missing", dir.display());
+        return Ok(Vec::new());
+    }
+
+    let mut objects = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read '{}'", dir.display()))? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        match fs::read_to_string(&path) {
+            Ok(raw) => {
+                if let Some(object) = parse_object(&raw) {
+                    objects.push(object);
+                }
+            }
+            Err(e) => warn!(error = %e, "Failed to read registry object {}", path.display()),
+        }
+    }
+    Ok(objects)
+}
+
+fn parse_object(raw: &str) -> Option<RegistryObject> {
+    let mut attributes: Vec<(String, String)> = Vec::new();
+
+    for line in raw.lines() {
+        if line.starts_with(char::is_whitespace) || line.starts_with('+') {
+            if let Some((_, value)) = attributes.last_mut() {
+                let continuation = line.trim_start_matches('+').trim();
+                if !continuation.is_empty() {
+                    value.push(' ');
+                    value.push_str(continuation);
+                }
+            }
+            continue;
+        }
+
+        if let Some((key, value)) = line.split_once(':') {
+            attributes.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let (class, key) = attributes.first().cloned()?;
+    Some(RegistryObject {
+        class,
+        key,
+        attributes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> Registry {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/registry");
+        Registry::load(&RegistryConfig {
+            path: path.to_string_lossy().to_string(),
+            reload_interval: None,
+        })
+        .unwrap()
+    }
+
+    fn keys(objects: &[RegistryObject]) -> Vec<&str> {
+        objects.iter().map(|o| o.key.as_str()).collect()
+    }
+
+    #[test]
+    fn parses_continuation_lines() {
+        let object = parse_object(
+            "aut-num:            AS4242420001\n\
+             descr:              Example network\n\
+             \x20                   spanning several lines\n\
+             +\n\
+             \x20                   after an empty one\n\
+             remarks:            with: a colon\n\
+             \tand a tab\n",
+        )
+        .unwrap();
+        assert_eq!(object.class, "aut-num");
+        assert_eq!(object.key, "AS4242420001");
+        assert_eq!(
+            object.get("descr"),
+            Some("Example network spanning several lines after an empty one")
+        );
+        assert_eq!(object.get("remarks"), Some("with: a colon and a tab"));
+        assert!(parse_object("\n  no attributes\n").is_none());
+    }
+
+    #[test]
+    fn resolves_maintainers_and_contacts() {
+        let registry = registry();
+        let info = registry.asn(4242420001).unwrap();
+        assert_eq!(info.name.as_deref(), Some("OTHER-AS"));
+        assert_eq!(
+            info.aut_num.get("descr"),
+            Some("Example network spanning several lines after an empty one")
+        );
+        // Unknown maintainers are skipped, contacts listed once.
+        assert_eq!(keys(&info.mntners), ["OTHER-MNT"]);
+        assert_eq!(keys(&info.contacts), ["Example Person"]);
+
+        assert_eq!(
+            registry.pgp_fingerprints(4242420001).unwrap(),
+            ["0123456789ABCDEF0123456789ABCDEF01234567"]
+        );
+        assert_eq!(
+            registry.contact_emails(4242420001).unwrap(),
+            ["noc@example.test"]
+        );
+        assert!(registry.asn(4242429999).is_none());
+        assert_eq!(
+            registry.contact_emails(4242429999).unwrap_err(),
+            "AS4242429999 is not registered"
+        );
+    }
+
+    #[test]
+    fn finds_route_origins() {
+        let registry = registry();
+
+        let info = registry.prefix("172.20.1.128/25".parse().unwrap());
+        assert_eq!(keys(&info.routes), ["172.20.1.0/24", "172.20.0.0/16"]);
+        let origins: Vec<_> = info.routes.iter().filter_map(|r| r.get("origin")).collect();
+        assert_eq!(origins, ["AS4242420001", "AS4242420000"]);
+
+        let info = registry.prefix("172.20.2.1/32".parse().unwrap());
+        assert_eq!(keys(&info.routes), ["172.20.0.0/16"]);
+
+        let info = registry.prefix(parse_prefix("fd00:1234:0:1::1").unwrap());
+        assert_eq!(keys(&info.routes), ["fd00:1234::/48"]);
+        assert_eq!(info.routes[0].get("origin"), Some("AS4242420001"));
+
+        // Less specific than any route, or another family with the same bits.
+        assert!(
+            registry
+                .prefix("172.16.0.0/12".parse().unwrap())
+                .routes
+                .is_empty()
+        );
+        assert!(
+            registry
+                .prefix("::ac14:101/128".parse().unwrap())
+                .routes
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn finds_networks() {
+        let registry = registry();
+
+        let info = registry.prefix(parse_prefix("172.20.1.0_24").unwrap());
+        assert_eq!(
+            keys(&info.networks),
+            ["172.20.1.0 - 172.20.1.255", "172.20.0.0 - 172.23.255.255"]
+        );
+        assert_eq!(info.networks[0].get("netname"), Some("OTHER-NET"));
+
+        let info = registry.prefix("172.22.0.0/16".parse().unwrap());
+        let netnames: Vec<_> = info
+            .networks
+            .iter()
+            .filter_map(|n| n.get("netname"))
+            .collect();
+        assert_eq!(netnames, ["DN42"]);
+
+        let info = registry.prefix("fd00:1234::/48".parse().unwrap());
+        assert_eq!(info.networks[0].get("netname"), Some("OTHER-NET6"));
+        assert!(
+            registry
+                .prefix("fd00:1235::/48".parse().unwrap())
+                .networks
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn parses_queries() {
+        assert_eq!(parse_asn("AS4242420001"), Some(4242420001));
+        assert_eq!(parse_asn(" as4242420001 "), Some(4242420001));
+        assert_eq!(parse_asn("4242420001"), Some(4242420001));
+        assert_eq!(parse_asn("AS"), None);
+        assert_eq!(parse_prefix("172.20.1.7/24"), "172.20.1.0/24".parse().ok());
+        assert_eq!(parse_prefix("172.20.1.7"), "172.20.1.7/32".parse().ok());
+        assert_eq!(parse_prefix("fd00::_8"), "fd00::/8".parse().ok());
+        assert_eq!(parse_prefix("not a prefix"), None);
+    }
+}
@@ -1,3 +1,6 @@
+pub mod alerts;
 pub mod api;
+pub mod autopeer;
 pub mod poller;
 pub mod request;
+pub mod tunnels;
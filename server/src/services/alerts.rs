@@ -0,0 +1,570 @@
+use std::{
+    collections::{HashMap, HashSet},
+    process::Stdio,
+};
+
+use chrono::{DateTime, Utc};
+use common::{
+    humanize::humanize_duration,
+    models::{DiffOp, NodeProtocol, NodeStatusDiff, Protocol},
+};
+use serde::Serialize;
+use tokio::{io::AsyncWriteExt, process::Command};
+use tracing::{info, warn};
+
+use crate::{
+    config::{AlertRule, AlertTarget, AlertsConfig},
+    history::History,
+};
+
+const DEFAULT_TELEGRAM_API: &str = "https://api.telegram.org";
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertStatus {
+    Firing,
+    Resolved,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Alert {
+    pub rule: &'static str,
+    pub status: AlertStatus,
+    pub node: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    pub message: String,
+    pub since: DateTime<Utc>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Rule index, node and protocol. An alert is delivered once when its key
+/// starts firing and once more when it stops.
+type AlertKey = (usize, String, Option<String>);
+
+struct TrackedProtocol {
+    bgp: bool,
+    down_since: Option<DateTime<Utc>>,
+}
+
+pub struct AlertEngine {
+    config: AlertsConfig,
+    client: reqwest::Client,
+    unreachable_since: HashMap<String, DateTime<Utc>>,
+    protocols: HashMap<(String, String), TrackedProtocol>,
+    active: HashMap<AlertKey, Alert>,
+}
+
+impl AlertEngine {
+    pub fn new(config: AlertsConfig, client: reqwest::Client) -> Self {
+        info!(
+            rules = config.rules.len(),
+            targets = config.targets.len(),
+            "Alerting enabled"
+        );
+        Self {
+            config,
+            client,
+            unreachable_since: HashMap::new(),
+            protocols: HashMap::new(),
+            active: HashMap::new(),
+        }
+    }
+
+    /// Updates the tracked node and protocol states from one poll and
+    /// delivers alerts that started or stopped firing. Only protocols the
+    /// diffs report as inserted or replaced are re-examined; `nodes` is used
+    /// to pick up protocols seen for the first time and drop removed ones.
+    /// Nodes that haven't been polled yet must be left out of `nodes`.
+    pub fn evaluate(
+        &mut self,
+        nodes: &[NodeProtocol],
+        diffs: &[NodeStatusDiff],
+        history: &History,
+    ) {
+        for alert in self.update(nodes, diffs, history, Utc::now()) {
+            self.deliver(alert);
+        }
+    }
+
+    /// Returns the alerts that started or stopped firing at `now`.
+    fn update(
+        &mut self,
+        nodes: &[NodeProtocol],
+        diffs: &[NodeStatusDiff],
+        history: &History,
+        now: DateTime<Utc>,
+    ) -> Vec<Alert> {
+        let mut changed = HashSet::new();
+        for diff in diffs {
+            for op in &diff.d {
+                if let DiffOp::Insert { i: items } | DiffOp::Replace { i: items } = op {
+                    changed.extend(items.iter().map(|p| (diff.n.clone(), p.name.clone())));
+                }
+            }
+        }
+
+        for node in nodes {
+            if node.error.is_some() {
+                self.unreachable_since
+                    .entry(node.name.clone())
+                    .or_insert(now);
+                // Protocols are cached data while the node is unreachable.
+                continue;
+            }
+            self.unreachable_since.remove(&node.name);
+
+            for protocol in &node.protocols {
+                let key = (node.name.clone(), protocol.name.clone());
+                if self.protocols.contains_key(&key) && !changed.contains(&key) {
+                    continue;
+                }
+                let previous = self.protocols.get(&key).and_then(|p| p.down_since);
+                self.protocols.insert(
+                    key,
+                    TrackedProtocol {
+                        bgp: protocol.proto == "BGP",
+                        down_since: is_down(protocol).then(|| previous.unwrap_or(now)),
+                    },
+                );
+            }
+            self.protocols.retain(|(name, protocol), _| {
+                name != &node.name || node.protocols.iter().any(|p| &p.name == protocol)
+            });
+        }
+
+        let firing = self.firing(now, history);
+
+        let mut changes = Vec::new();
+        for (key, alert) in &firing {
+            if !self.active.contains_key(key) {
+                changes.push(alert.clone());
+            }
+        }
+        for (key, alert) in std::mem::take(&mut self.active) {
+            if !firing.contains_key(&key) {
+                changes.push(Alert {
+                    status: AlertStatus::Resolved,
+                    timestamp: now,
+                    ..alert
+                });
+            }
+        }
+        self.active = firing;
+        changes
+    }
+
+    fn firing(&self, now: DateTime<Utc>, history: &History) -> HashMap<AlertKey, Alert> {
+        let mut firing = HashMap::new();
+        let alert = |rule, node: &str, protocol: Option<&str>, message, since| Alert {
+            rule,
+            status: AlertStatus::Firing,
+            node: node.to_string(),
+            protocol: protocol.map(str::to_string),
+            message,
+            since,
+            timestamp: now,
+        };
+
+        for (index, rule) in self.config.rules.iter().enumerate() {
+            match rule {
+                AlertRule::NodeUnreachable { after } => {
+                    for (node, &since) in &self.unreachable_since {
+                        if (now - since).num_seconds() >= *after as i64 {
+                            let message =
+                                format!("Node {} stopped responding {}", node, ago(since));
+                            firing.insert(
+                                (index, node.clone(), None),
+                                alert("node_unreachable", node, None, message, since),
+                            );
+                        }
+                    }
+                }
+                AlertRule::SessionDown { after, protocols } => {
+                    for ((node, protocol), tracked) in &self.protocols {
+                        let Some(since) = tracked.down_since else {
+                            continue;
+                        };
+                        if !tracked.bgp
+                            || (!protocols.is_empty()
+                                && !protocols.iter().any(|p| protocol.starts_with(p.as_str())))
+                            || (now - since).num_seconds() < *after as i64
+                        {
+                            continue;
+                        }
+                        let message = format!(
+                            "BGP session {} on {} went down {}",
+                            protocol,
+                            node,
+                            ago(since)
+                        );
+                        firing.insert(
+                            (index, node.clone(), Some(protocol.clone())),
+                            alert("session_down", node, Some(protocol), message, since),
+                        );
+                    }
+                }
+                AlertRule::Flapping { flaps, window } => {
+                    for (node, protocol) in self.protocols.keys() {
+                        let count = history.flap_count(node, protocol, *window, now);
+                        if count < *flaps {
+                            continue;
+                        }
+                        let key = (index, node.clone(), Some(protocol.clone()));
+                        let since = self.active.get(&key).map_or(now, |a| a.since);
+                        let message = format!(
+                            "{} on {} went down {} times in the last {}s",
+                            protocol, node, count, window
+                        );
+                        firing.insert(key, alert("flapping", node, Some(protocol), message, since));
+                    }
+                }
+            }
+        }
+
+        firing
+    }
+
+    fn deliver(&self, alert: Alert) {
+        match alert.status {
+            AlertStatus::Firing => {
+                warn!(node = %alert.node, rule = alert.rule, "{}", alert.message)
+            }
+            AlertStatus::Resolved => {
+                info!(node = %alert.node, rule = alert.rule, "Resolved: {}", alert.message)
+            }
+        }
+
+        for target in &self.config.targets {
+            let target = target.clone();
+            let client = self.client.clone();
+            let alert = alert.clone();
+            tokio::spawn(async move {
+                if let Err(e) = send(&client, &target, &alert).await {
+                    warn!(node = %alert.node, rule = alert.rule, error = %e, "Failed to deliver alert");
+                }
+            });
+        }
+    }
+}
+
+/// A session counts as down when it is not `up`, unless it was disabled on
+/// purpose (`down` without any info).
+fn is_down(protocol: &Protocol) -> bool {
+    protocol.state != "up" && !(protocol.state == "down" && protocol.info.is_empty())
+}
+
+fn ago(since: DateTime<Utc>) -> String {
+    humanize_duration(since.timestamp()).unwrap_or_else(|| since.to_rfc3339())
+}
+
+async fn send(client: &reqwest::Client, target: &AlertTarget, alert: &Alert) -> Result<(), String> {
+    match target {
+        AlertTarget::Webhook { url, headers } => {
+            let mut req = client.post(url).json(alert);
+            for (name, value) in headers {
+                req = req.header(name, value);
+            }
+            check_response(req.send().await)
+        }
+        AlertTarget::Telegram {
+            bot_token,
+            chat_id,
+            api_url,
+        } => {
+            let url = format!(
+                "{}/bot{}/sendMessage",
+                api_url
+                    .as_deref()
+                    .unwrap_or(DEFAULT_TELEGRAM_API)
+                    .trim_end_matches('/'),
+                bot_token
+            );
+            let text = match alert.status {
+                AlertStatus::Firing => format!("🔴 {}", alert.message),
+                AlertStatus::Resolved => format!("🟢 Resolved: {}", alert.message),
+            };
+            let body = serde_json::json!({ "chat_id": chat_id, "text": text });
+            check_response(client.post(url).json(&body).send().await)
+        }
+        AlertTarget::Command { command, args } => {
+            let payload = serde_json::to_vec(alert).map_err(|e| e.to_string())?;
+            let mut child = Command::new(command)
+                .args(args)
+                .env("ALERT_RULE", alert.rule)
+                .env(
+                    "ALERT_STATUS",
+                    match alert.status {
+                        AlertStatus::Firing => "firing",
+                        AlertStatus::Resolved => "resolved",
+                    },
+                )
+                .env("ALERT_NODE", &alert.node)
+                .env(
+                    "ALERT_PROTOCOL",
+                    alert.protocol.as_deref().unwrap_or_default(),
+                )
+                .env("ALERT_MESSAGE", &alert.message)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .spawn()
+                .map_err(|e| format!("Failed to run {}: {}", command, e))?;
+
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(&payload).await.map_err(|e| e.to_string())?;
+            }
+            let status = child.wait().await.map_err(|e| e.to_string())?;
+            if !status.success() {
+                return Err(format!("{} exited with {}", command, status));
+            }
+            Ok(())
+        }
+    }
+}
+
+fn check_response(result: reqwest::Result<reqwest::Response>) -> Result<(), String> {
+    match result {
+        Ok(resp) if resp.status().is_success() => Ok(()),
+        Ok(resp) => Err(format!("Endpoint returned {}", resp.status())),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use common::diff::calculate_diff;
+
+    use super::*;
+
+    fn engine(rule: AlertRule) -> AlertEngine {
+        AlertEngine::new(
+            AlertsConfig {
+                rules: vec![rule],
+                targets: Vec::new(),
+            },
+            reqwest::Client::new(),
+        )
+    }
+
+    fn protocol(name: &str, proto: &str, state: &str, info: &str) -> Protocol {
+        Protocol {
+            name: name.to_string(),
+            proto: proto.to_string(),
+            table: "---".to_string(),
+            state: state.to_string(),
+            since: "12:00:00".to_string(),
+            info: info.to_string(),
+            reason: None,
+        }
+    }
+
+    fn node(protocols: &[Protocol], error: Option<&str>) -> NodeProtocol {
+        NodeProtocol {
+            name: "node1".to_string(),
+            protocols: protocols.to_vec(),
+            last_updated: Utc::now(),
+            error: error.map(str::to_string),
+        }
+    }
+
+    fn diff(old: &[Protocol], new: &[Protocol]) -> Vec<NodeStatusDiff> {
+        vec![NodeStatusDiff {
+            n: "node1".to_string(),
+            d: calculate_diff(old, new),
+            u: Utc::now(),
+            e: None,
+        }]
+    }
+
+    fn summary(alerts: &[Alert]) -> Vec<(&'static str, AlertStatus, Option<&str>)> {
+        alerts
+            .iter()
+            .map(|a| (a.rule, a.status, a.protocol.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn session_down_after_delay() {
+        let mut engine = engine(AlertRule::SessionDown {
+            after: 60,
+            protocols: Vec::new(),
+        });
+        let history = History::default();
+        let t0 = Utc::now();
+        let up = [
+            protocol("peer1", "BGP", "up", "Established"),
+            protocol("static1", "Static", "up", ""),
+        ];
+        let down = [
+            protocol("peer1", "BGP", "start", "Active"),
+            protocol("static1", "Static", "start", ""),
+        ];
+
+        assert!(
+            engine
+                .update(&[node(&up, None)], &[], &history, t0)
+                .is_empty()
+        );
+        let went_down = t0 + Duration::seconds(10);
+        assert!(
+            engine
+                .update(&[node(&down, None)], &diff(&up, &down), &history, went_down)
+                .is_empty()
+        );
+        let alerts = engine.update(
+            &[node(&down, None)],
+            &[],
+            &history,
+            t0 + Duration::seconds(69),
+        );
+        assert!(alerts.is_empty());
+
+        let alerts = engine.update(
+            &[node(&down, None)],
+            &[],
+            &history,
+            t0 + Duration::seconds(70),
+        );
+        assert_eq!(
+            summary(&alerts),
+            [("session_down", AlertStatus::Firing, Some("peer1"))]
+        );
+        assert_eq!(alerts[0].since, went_down);
+
+        // Still down, but already delivered.
+        let alerts = engine.update(
+            &[node(&down, None)],
+            &[],
+            &history,
+            t0 + Duration::seconds(80),
+        );
+        assert!(alerts.is_empty());
+
+        let alerts = engine.update(
+            &[node(&up, None)],
+            &diff(&down, &up),
+            &history,
+            t0 + Duration::seconds(90),
+        );
+        assert_eq!(
+            summary(&alerts),
+            [("session_down", AlertStatus::Resolved, Some("peer1"))]
+        );
+    }
+
+    #[test]
+    fn session_down_filters() {
+        let mut engine = engine(AlertRule::SessionDown {
+            after: 0,
+            protocols: vec!["dn42_".to_string()],
+        });
+        let history = History::default();
+        let protocols = [
+            protocol("dn42_peer1", "BGP", "start", "Connect"),
+            protocol("ibgp_node2", "BGP", "start", "Connect"),
+            // Disabled on purpose.
+            protocol("dn42_peer2", "BGP", "down", ""),
+        ];
+
+        let alerts = engine.update(&[node(&protocols, None)], &[], &history, Utc::now());
+        assert_eq!(
+            summary(&alerts),
+            [("session_down", AlertStatus::Firing, Some("dn42_peer1"))]
+        );
+    }
+
+    #[test]
+    fn node_unreachable() {
+        let mut engine = engine(AlertRule::NodeUnreachable { after: 30 });
+        let history = History::default();
+        let t0 = Utc::now();
+
+        // Nodes that haven't been polled yet are left out and not tracked.
+        assert!(engine.update(&[], &[], &history, t0).is_empty());
+        let failed = t0 + Duration::seconds(100);
+        let error = Some("Node is not reachable");
+        assert!(
+            engine
+                .update(&[node(&[], error)], &[], &history, failed)
+                .is_empty()
+        );
+        assert!(
+            engine
+                .update(
+                    &[node(&[], error)],
+                    &[],
+                    &history,
+                    failed + Duration::seconds(29)
+                )
+                .is_empty()
+        );
+
+        let alerts = engine.update(
+            &[node(&[], error)],
+            &[],
+            &history,
+            failed + Duration::seconds(30),
+        );
+        assert_eq!(
+            summary(&alerts),
+            [("node_unreachable", AlertStatus::Firing, None)]
+        );
+        assert_eq!(alerts[0].since, failed);
+        assert!(
+            engine
+                .update(
+                    &[node(&[], error)],
+                    &[],
+                    &history,
+                    failed + Duration::seconds(40)
+                )
+                .is_empty()
+        );
+
+        let alerts = engine.update(
+            &[node(&[], None)],
+            &[],
+            &history,
+            failed + Duration::seconds(50),
+        );
+        assert_eq!(
+            summary(&alerts),
+            [("node_unreachable", AlertStatus::Resolved, None)]
+        );
+    }
+
+    #[test]
+    fn flapping() {
+        let mut engine = engine(AlertRule::Flapping {
+            flaps: 2,
+            window: 300,
+        });
+        let history = History::default();
+        let t0 = Utc::now();
+        let up = [protocol("peer1", "BGP", "up", "Established")];
+        let down = [protocol("peer1", "BGP", "start", "Active")];
+
+        let mut poll = |protocols: &[Protocol], secs: i64| {
+            let now = t0 + Duration::seconds(secs);
+            history.record("node1", protocols, now);
+            engine.update(&[node(protocols, None)], &[], &history, now)
+        };
+        assert!(poll(&up, 0).is_empty());
+        assert!(poll(&down, 10).is_empty());
+        assert!(poll(&up, 20).is_empty());
+        let alerts = poll(&down, 30);
+        assert_eq!(
+            summary(&alerts),
+            [("flapping", AlertStatus::Firing, Some("peer1"))]
+        );
+        assert!(poll(&up, 40).is_empty());
+
+        // The flaps leave the window.
+        let alerts = poll(&up, 311);
+        assert_eq!(
+            summary(&alerts),
+            [("flapping", AlertStatus::Resolved, Some("peer1"))]
+        );
+    }
+}
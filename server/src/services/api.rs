@@ -1,16 +1,25 @@
-use std::{net::IpAddr, pin::Pin, sync::Arc};
+use std::{
+    net::IpAddr,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
 
 use common::{
+    command::{BirdCommand, BirdError},
+    models::WireGuardInterface,
+    protocol::parse_protocol_details,
+    route::parse_routes,
     traceroute::{TracerouteHop, parse_traceroute_line},
     utils::validate_target,
+    wireguard::parse_wireguard_dump,
 };
-use futures_util::{Stream, StreamExt, stream};
+use futures_util::{Stream, StreamExt, future, stream};
 use ipnet::IpNet;
 use tracing::warn;
 
 use crate::{
-    config::Config,
-    services::request::{build_get, get_stream, post_stream},
+    config::{Config, NodeConfig},
+    services::request::{bird_stream, build_get, get_stream},
     state::{AppResponse, AppState},
     utils::byte_stream_to_lines,
 };
@@ -18,7 +27,51 @@ use crate::{
 type BoxStream = Pin<Box<dyn Stream<Item = AppResponse> + Send>>;
 
 fn stream_error(msg: String) -> BoxStream {
-    Box::pin(stream::once(async move { AppResponse::Error(msg) }))
+    Box::pin(stream::once(
+        async move { AppResponse::Error { error: msg } },
+    ))
+}
+
+/// Forwards raw lines through `update` as they arrive and emits `finish`
+/// over the complete output once the stream ends. Structured results need
+/// the whole output since an entry can span chunk boundaries. When the
+/// output ends with an error trailer from the proxy, the error is emitted
+/// instead of `finish`.
+fn with_parsed<S>(
+    lines: S,
+    mut update: impl FnMut(Vec<String>) -> AppResponse + Send + 'static,
+    finish: impl FnOnce(Vec<String>) -> AppResponse + Send + 'static,
+) -> impl Stream<Item = AppResponse> + Send
+where
+    S: Stream<Item = Vec<String>> + Send,
+{
+    let collected = Arc::new(Mutex::new((Vec::new(), None)));
+    let sink = collected.clone();
+    let updates = lines.filter_map(move |mut lines| {
+        let mut sink = sink.lock().unwrap();
+        if let Some(error) = lines.last().and_then(|l| BirdError::from_trailer(l)) {
+            lines.pop();
+            sink.1 = Some(error);
+        }
+        sink.0.extend(lines.iter().cloned());
+        future::ready((!lines.is_empty()).then(|| update(lines)))
+    });
+    let parsed = stream::once(async move {
+        let (lines, error) = std::mem::take(&mut *collected.lock().unwrap());
+        match error {
+            Some(error) => {
+                warn!(
+                    code = error.code,
+                    "BIRD failed after sending output: {}", error.message
+                );
+                AppResponse::Error {
+                    error: error.to_string(),
+                }
+            }
+            None => finish(lines),
+        }
+    });
+    updates.chain(parsed)
 }
 
 pub async fn perform_traceroute(
@@ -105,15 +158,14 @@ pub async fn perform_route_lookup(
         None => return stream_error("Node not found".into()),
     };
 
-    let command = if all {
-        format!("show route for {} all", target)
-    } else {
-        format!("show route for {}", target)
+    let command = BirdCommand::ShowRoute {
+        prefix: target.clone(),
+        all,
     };
 
     let http_client = state.http_client.clone();
 
-    match post_stream(&http_client, &node_config, "/bird", &command).await {
+    match bird_stream(&http_client, &node_config, &command).await {
         Ok(byte_stream) => {
             let node_for_init = node.clone();
             let init = stream::once(async move {
@@ -123,12 +175,17 @@ pub async fn perform_route_lookup(
             });
 
             let node_name = node.clone();
-            let updates = byte_stream_to_lines(byte_stream).map(move |lines| {
-                AppResponse::RouteLookupUpdate {
+            let updates = with_parsed(
+                byte_stream_to_lines(byte_stream),
+                move |lines| AppResponse::RouteLookupUpdate {
                     node: node_name.clone(),
                     lines,
-                }
-            });
+                },
+                move |lines| AppResponse::RouteLookupRoutes {
+                    node,
+                    routes: parse_routes(&lines),
+                },
+            );
             Box::pin(init.chain(updates))
         }
         Err(err_msg) => {
@@ -154,10 +211,15 @@ pub async fn get_protocol_details(
         None => return stream_error("Node not found".into()),
     };
 
-    let command = format!("show protocols all {}", protocol);
+    let command = BirdCommand::ShowProtocolsAll {
+        name: protocol.clone(),
+    };
+    if let Err(e) = command.validate() {
+        return stream_error(e);
+    }
     let http_client = state.http_client.clone();
 
-    match post_stream(&http_client, &node_config, "/bird", &command).await {
+    match bird_stream(&http_client, &node_config, &command).await {
         Ok(byte_stream) => {
             let node_for_init = node.clone();
             let protocol_for_init = protocol.clone();
@@ -170,13 +232,25 @@ pub async fn get_protocol_details(
 
             let node_name = node.clone();
             let protocol_name = protocol.clone();
-            let updates = byte_stream_to_lines(byte_stream).map(move |lines| {
-                AppResponse::ProtocolDetailsUpdate {
+            let updates = with_parsed(
+                byte_stream_to_lines(byte_stream),
+                move |lines| AppResponse::ProtocolDetailsUpdate {
                     node: node_name.clone(),
                     protocol: protocol_name.clone(),
                     lines,
-                }
-            });
+                },
+                move |lines| {
+                    let details = parse_protocol_details(&lines)
+                        .into_iter()
+                        .find(|d| d.summary.name == protocol)
+                        .map(Box::new);
+                    AppResponse::ProtocolDetailsParsed {
+                        node,
+                        protocol,
+                        details,
+                    }
+                },
+            );
 
             Box::pin(init.chain(updates))
         }
@@ -194,53 +268,21 @@ pub async fn get_protocol_details(
 
 pub async fn get_wireguard(state: AppState, config: Arc<Config>) -> BoxStream {
     use chrono::Utc;
-    use common::{models::NodeWireGuard, wireguard::parse_wireguard_dump};
+    use common::models::NodeWireGuard;
 
-    let http_client = state.http_client.clone();
     let mut wireguard_data = Vec::new();
 
     for node in &config.nodes {
-        let req = build_get(&http_client, node, "/wireguard");
-        match req.send().await {
-            Ok(resp) if resp.status().is_success() => match resp.text().await {
-                Ok(dump_output) => {
-                    let peers = parse_wireguard_dump(&dump_output);
-                    wireguard_data.push(NodeWireGuard {
-                        name: node.name.clone(),
-                        peers,
-                        last_updated: Utc::now(),
-                        error: None,
-                    });
-                }
-                Err(e) => {
-                    warn!(node = %node.name, error = ?e, "Failed to read WireGuard response");
-                    wireguard_data.push(NodeWireGuard {
-                        name: node.name.clone(),
-                        peers: Vec::new(),
-                        last_updated: Utc::now(),
-                        error: Some("Failed to read response".to_string()),
-                    });
-                }
-            },
-            Ok(resp) => {
-                warn!(node = %node.name, status = %resp.status(), "WireGuard endpoint returned error");
-                wireguard_data.push(NodeWireGuard {
-                    name: node.name.clone(),
-                    peers: Vec::new(),
-                    last_updated: Utc::now(),
-                    error: Some(format!("Node returned error: {}", resp.status())),
-                });
-            }
-            Err(e) => {
-                warn!(node = %node.name, error = ?e, "Failed to contact node for WireGuard info");
-                wireguard_data.push(NodeWireGuard {
-                    name: node.name.clone(),
-                    peers: Vec::new(),
-                    last_updated: Utc::now(),
-                    error: Some("Node is not reachable".to_string()),
-                });
-            }
-        }
+        let (interfaces, error) = match fetch_wireguard(&state.http_client, node).await {
+            Ok(interfaces) => (interfaces, None),
+            Err(e) => (Vec::new(), Some(e)),
+        };
+        wireguard_data.push(NodeWireGuard {
+            name: node.name.clone(),
+            interfaces,
+            last_updated: Utc::now(),
+            error,
+        });
     }
 
     Box::pin(stream::once(async move {
@@ -249,3 +291,115 @@ pub async fn get_wireguard(state: AppState, config: Arc<Config>) -> BoxStream {
         }
     }))
 }
+
+/// Fetches the WireGuard interfaces of a node from its proxy. Proxies that
+/// predate the JSON format ignore `format` and send filtered `wg show dump`
+/// lines instead.
+pub async fn fetch_wireguard(
+    client: &reqwest::Client,
+    node: &NodeConfig,
+) -> Result<Vec<WireGuardInterface>, String> {
+    let req = build_get(client, node, "/wireguard?format=json");
+    match req.send().await {
+        Ok(resp) if resp.status().is_success() => match resp.text().await {
+            Ok(text) => serde_json::from_str(&text).or_else(|_| {
+                parse_wireguard_dump(&text, None).map_err(|e| {
+                    warn!(node = %node.name, error = %e, "Failed to parse WireGuard response");
+                    e
+                })
+            }),
+            Err(e) => {
+                warn!(node = %node.name, error = ?e, "Failed to read WireGuard response");
+                Err("Failed to read response".to_string())
+            }
+        },
+        Ok(resp) => {
+            warn!(node = %node.name, status = %resp.status(), "WireGuard endpoint returned error");
+            Err(format!("Node returned error: {}", resp.status()))
+        }
+        Err(e) => {
+            warn!(node = %node.name, error = ?e, "Failed to contact node for WireGuard info");
+            Err("Node is not reachable".to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Router, routing::post};
+    use serde_json::json;
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    const OUTPUT: &str = "Table master4:
+172.23.0.0/24        unicast [dn42_kioubit_v4 2024-03-04 10:15:27] * (100) [AS4242423914i]
+";
+
+    /// Serves `body` on `/bird` of a node like the proxy would.
+    async fn node(body: String) -> Arc<Config> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new().route(
+            "/bird",
+            post(move || {
+                let body = body.clone();
+                async move { body }
+            }),
+        );
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let config = serde_json::from_value(json!({
+            "listen": "127.0.0.1:0",
+            "nodes": [{ "name": "node1", "url": url }],
+        }))
+        .unwrap();
+        Arc::new(config)
+    }
+
+    async fn route_lookup(config: Arc<Config>) -> Vec<serde_json::Value> {
+        perform_route_lookup(
+            AppState::new(),
+            config,
+            "node1".to_string(),
+            "172.23.0.0/24".to_string(),
+            false,
+        )
+        .await
+        .map(|r| serde_json::to_value(r).unwrap())
+        .collect()
+        .await
+    }
+
+    #[tokio::test]
+    async fn parses_complete_output() {
+        let responses = route_lookup(node(OUTPUT.to_string()).await).await;
+        assert_eq!(responses[0], json!({ "t": "rli", "node": "node1" }));
+        let last = responses.last().unwrap();
+        assert_eq!(last["t"], "rlr");
+        assert_eq!(last["routes"][0]["prefix"], "172.23.0.0/24");
+    }
+
+    #[tokio::test]
+    async fn error_after_output_is_an_error() {
+        let error = BirdError {
+            code: 8001,
+            message: "Network not found".to_string(),
+        };
+        let body = format!("{}{}", OUTPUT, error.trailer());
+        let responses = route_lookup(node(body).await).await;
+
+        // The output is forwarded without the trailer, then the error
+        // replaces the parsed routes.
+        let lines: Vec<&serde_json::Value> = responses
+            .iter()
+            .filter(|r| r["t"] == "rlu")
+            .flat_map(|r| r["lines"].as_array().unwrap())
+            .collect();
+        assert_eq!(lines, OUTPUT.lines().collect::<Vec<_>>());
+        assert!(responses.iter().all(|r| r["t"] != "rlr"));
+        assert_eq!(
+            responses.last().unwrap(),
+            &json!({ "t": "e", "error": "Network not found (BIRD 8001)" })
+        );
+    }
+}
@@ -0,0 +1,1465 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, bail};
+use chrono::Utc;
+use common::{
+    auto_peer::{AutoPeerRequest, AutoPeerResponse, ChallengeMethod, PeeringSession},
+    utils::{validate_endpoint, validate_wg_pubkey},
+};
+use ipnet::IpNet;
+use rand::{Rng, distr::Alphanumeric};
+use tracing::{info, warn};
+
+use self::{
+    mail::Mailer,
+    storage::{AuditEvent, MemoryStorage, SqliteStorage, Storage},
+};
+use crate::{
+    config::AutoPeerConfig,
+    registry::{Registry, parse_asn},
+};
+
+mod mail;
+mod pgp;
+mod storage;
+
+const DEFAULT_CHALLENGE_TTL: u64 = 600;
+const DEFAULT_CREDENTIAL_TTL: u64 = 3600;
+/// Upper bound for `challenge_ttl` and `credential_ttl`, in seconds.
+const MAX_TTL: u64 = 365 * 24 * 3600;
+const DEFAULT_GPG_BIN: &str = "gpg";
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+/// Minimum time between two codes for the same ASN or to the same address.
+const EMAIL_RESEND_INTERVAL: Duration = Duration::from_secs(60);
+const MAX_PENDING_AUTHS: usize = 1024;
+const TOKEN_LENGTH: usize = 32;
+const SESSION_ID_LENGTH: usize = 12;
+const SESSION_NAME_MAX_LENGTH: usize = 64;
+const SESSION_COMMENT_MAX_LENGTH: usize = 256;
+const DEFAULT_MAX_SESSIONS_PER_ASN: usize = 8;
+const DEFAULT_IPV4_RANGES: &[&str] = &["172.20.0.0/14", "172.31.0.0/16", "10.0.0.0/8"];
+const DEFAULT_IPV6_RANGES: &[&str] = &["fe80::/10", "fd00::/8"];
+
+#[derive(Clone)]
+pub struct AutoPeer {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    config: AutoPeerConfig,
+    nodes: Vec<String>,
+    ipv4_ranges: Vec<IpNet>,
+    ipv6_ranges: Vec<IpNet>,
+    registry: Option<Registry>,
+    mailer: Option<Mailer>,
+    pending: Mutex<HashMap<String, PendingAuth>>,
+    email_limiter: Mutex<EmailLimiter>,
+    storage: Box<dyn Storage>,
+    /// Serializes session changes so quota and node checks see a stable view.
+    sessions_lock: Mutex<()>,
+}
+
+struct PendingAuth {
+    asn: u32,
+    challenge: Option<Challenge>,
+    expires_at: Instant,
+}
+
+struct Challenge {
+    method: ChallengeMethod,
+    secret: String,
+    attempts: u32,
+}
+
+/// Tracks when codes were last mailed. Sessions are unauthenticated, so the
+/// limit has to hold across sessions, per ASN and per recipient address.
+#[derive(Default)]
+struct EmailLimiter {
+    asns: HashMap<u32, Instant>,
+    recipients: HashMap<String, Instant>,
+}
+
+impl EmailLimiter {
+    fn reserve(&mut self, asn: u32, recipients: &[String]) -> Result<(), String> {
+        self.asns
+            .retain(|_, sent| sent.elapsed() < EMAIL_RESEND_INTERVAL);
+        self.recipients
+            .retain(|_, sent| sent.elapsed() < EMAIL_RESEND_INTERVAL);
+
+        let recipients: Vec<String> = recipients.iter().map(|r| r.to_lowercase()).collect();
+        if self.asns.contains_key(&asn)
+            || recipients.iter().any(|r| self.recipients.contains_key(r))
+        {
+            return Err(
+                "A code was sent recently, please wait before requesting another".to_string(),
+            );
+        }
+
+        let now = Instant::now();
+        self.asns.insert(asn, now);
+        self.recipients
+            .extend(recipients.into_iter().map(|r| (r, now)));
+        Ok(())
+    }
+}
+
+impl AutoPeer {
+    pub fn load(
+        config: AutoPeerConfig,
+        nodes: Vec<String>,
+        registry: Option<Registry>,
+    ) -> anyhow::Result<Self> {
+        if config.challenge_methods.contains(&ChallengeMethod::Pgp) && registry.is_none() {
+            bail!("autopeer challenge method 'pgp' requires registry");
+        }
+
+        if config.challenge_methods.contains(&ChallengeMethod::Email)
+            && (registry.is_none() || config.mail.is_none())
+        {
+            bail!("autopeer challenge method 'email' requires registry and mail");
+        }
+
+        for (name, ttl) in [
+            ("challenge_ttl", config.challenge_ttl),
+            ("credential_ttl", config.credential_ttl),
+        ] {
+            if ttl.is_some_and(|ttl| ttl > MAX_TTL) {
+                bail!("autopeer {} must be at most {} seconds", name, MAX_TTL);
+            }
+        }
+
+        let mailer = config.mail.as_ref().map(Mailer::new).transpose()?;
+        let ipv4_ranges = parse_ranges(config.ipv4_ranges.as_deref(), DEFAULT_IPV4_RANGES)
+            .context("Invalid autopeer ipv4_ranges")?;
+        let ipv6_ranges = parse_ranges(config.ipv6_ranges.as_deref(), DEFAULT_IPV6_RANGES)
+            .context("Invalid autopeer ipv6_ranges")?;
+
+        let storage = open_storage(&config)?;
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                config,
+                nodes,
+                ipv4_ranges,
+                ipv6_ranges,
+                registry,
+                mailer,
+                pending: Mutex::new(HashMap::new()),
+                email_limiter: Mutex::new(EmailLimiter::default()),
+                storage,
+                sessions_lock: Mutex::new(()),
+            }),
+        })
+    }
+
+    pub async fn handle(&self, token: Option<&str>, req: AutoPeerRequest) -> AutoPeerResponse {
+        match req {
+            AutoPeerRequest::InitSession { asn } => self.init_session(&asn),
+            AutoPeerRequest::SelectChallenge { method } => {
+                self.select_challenge(token, method).await
+            }
+            AutoPeerRequest::VerifyPgp { pubkey, signature } => {
+                self.verify_pgp(token, &pubkey, &signature).await
+            }
+            AutoPeerRequest::VerifyEmail { code } => self.verify_email(token, &code).await,
+            AutoPeerRequest::GetSessions => {
+                self.authorized(token, |asn| async move {
+                    let sessions = self.sessions_for(asn).await?;
+                    Ok(AutoPeerResponse::SessionsUpdate { sessions })
+                })
+                .await
+            }
+            AutoPeerRequest::CreateSession { session } => {
+                self.authorized(token, |asn| self.create_session(asn, session))
+                    .await
+            }
+            AutoPeerRequest::UpdateSession { id, session } => {
+                self.authorized(token, |asn| self.update_session(asn, id, session))
+                    .await
+            }
+            AutoPeerRequest::DeleteSession { id } => {
+                self.authorized(token, |asn| self.delete_session(asn, id))
+                    .await
+            }
+        }
+    }
+
+    /// Runs `f` for the ASN of a valid credential.
+    async fn authorized<F, Fut>(&self, token: Option<&str>, f: F) -> AutoPeerResponse
+    where
+        F: FnOnce(u32) -> Fut,
+        Fut: Future<Output = Result<AutoPeerResponse, String>>,
+    {
+        let result = match self.authorize(token).await {
+            Ok(asn) => f(asn).await,
+            Err(error) => Err(error),
+        };
+        result.unwrap_or_else(|error| AutoPeerResponse::OperationError { error })
+    }
+
+    fn init_session(&self, asn: &str) -> AutoPeerResponse {
+        let asn = match parse_asn(asn) {
+            Some(asn) => asn,
+            None => {
+                return AutoPeerResponse::InitError {
+                    error: "Invalid ASN".to_string(),
+                };
+            }
+        };
+
+        if self.inner.config.challenge_methods.is_empty() {
+            return AutoPeerResponse::InitError {
+                error: "No verification methods are available".to_string(),
+            };
+        }
+
+        let token = random_string(TOKEN_LENGTH);
+        let mut pending = self.inner.pending.lock().unwrap();
+        pending.retain(|_, p| p.expires_at > Instant::now());
+        if pending.len() >= MAX_PENDING_AUTHS {
+            warn!(%asn, "AutoPeer pending verifications are full");
+            return AutoPeerResponse::InitError {
+                error: "Too many verifications in progress, please try again later".to_string(),
+            };
+        }
+        pending.insert(
+            token.clone(),
+            PendingAuth {
+                asn,
+                challenge: None,
+                expires_at: Instant::now() + self.challenge_ttl(),
+            },
+        );
+
+        info!(%asn, "AutoPeer session initialized");
+        AutoPeerResponse::InitSuccess {
+            token,
+            challenge_methods: self.inner.config.challenge_methods.clone(),
+        }
+    }
+
+    async fn select_challenge(
+        &self,
+        token: Option<&str>,
+        method: ChallengeMethod,
+    ) -> AutoPeerResponse {
+        if !self.inner.config.challenge_methods.contains(&method) {
+            return AutoPeerResponse::OperationError {
+                error: "Verification method is not available".to_string(),
+            };
+        }
+
+        let asn = {
+            let pending = self.inner.pending.lock().unwrap();
+            let auth = match token
+                .and_then(|t| pending.get(t))
+                .filter(|p| p.expires_at > Instant::now())
+            {
+                Some(auth) => auth,
+                None => {
+                    return AutoPeerResponse::OperationError {
+                        error: "Session expired, please start over".to_string(),
+                    };
+                }
+            };
+
+            auth.asn
+        };
+
+        let (secret, challenge_text) = match method {
+            ChallengeMethod::Pgp => {
+                let text = pgp::challenge_text(asn);
+                (text.clone(), Some(text))
+            }
+            ChallengeMethod::Email => match self.send_email_code(asn).await {
+                Ok(sent) => sent,
+                Err(error) => return AutoPeerResponse::OperationError { error },
+            },
+        };
+
+        let mut pending = self.inner.pending.lock().unwrap();
+        match token.and_then(|t| pending.get_mut(t)) {
+            Some(auth) => {
+                auth.challenge = Some(Challenge {
+                    method,
+                    secret,
+                    attempts: 0,
+                });
+                auth.expires_at = Instant::now() + self.challenge_ttl();
+                AutoPeerResponse::ChallengeSelected { challenge_text }
+            }
+            None => AutoPeerResponse::OperationError {
+                error: "Session expired, please start over".to_string(),
+            },
+        }
+    }
+
+    async fn send_email_code(&self, asn: u32) -> Result<(String, Option<String>), String> {
+        let recipients = self.registry()?.contact_emails(asn)?;
+        if recipients.is_empty() {
+            return Err(format!("No contact email registered for AS{}", asn));
+        }
+
+        let mailer = self
+            .inner
+            .mailer
+            .as_ref()
+            .ok_or_else(|| "Email verification is not available".to_string())?;
+        self.inner
+            .email_limiter
+            .lock()
+            .unwrap()
+            .reserve(asn, &recipients)?;
+        let code = format!("{:06}", rand::rng().random_range(0..1_000_000));
+        mailer
+            .send_code(&recipients, asn, &code, self.challenge_ttl())
+            .await?;
+
+        info!(%asn, recipients = recipients.len(), "AutoPeer email code sent");
+        let masked = recipients
+            .iter()
+            .map(|r| mask_email(r))
+            .collect::<Vec<_>>()
+            .join(", ");
+        Ok((code, Some(format!("Code sent to {}", masked))))
+    }
+
+    async fn verify_pgp(
+        &self,
+        token: Option<&str>,
+        pubkey: &str,
+        signature: &str,
+    ) -> AutoPeerResponse {
+        let (asn, challenge) = {
+            let pending = self.inner.pending.lock().unwrap();
+            match token
+                .and_then(|t| pending.get(t))
+                .filter(|p| p.expires_at > Instant::now())
+            {
+                Some(PendingAuth {
+                    asn,
+                    challenge:
+                        Some(Challenge {
+                            method: ChallengeMethod::Pgp,
+                            secret,
+                            ..
+                        }),
+                    ..
+                }) => (*asn, secret.clone()),
+                Some(_) => {
+                    return AutoPeerResponse::VerifyError {
+                        error: "No PGP challenge has been issued".to_string(),
+                    };
+                }
+                None => {
+                    return AutoPeerResponse::VerifyError {
+                        error: "Session expired, please start over".to_string(),
+                    };
+                }
+            }
+        };
+
+        let fingerprints = match self.registry().and_then(|r| r.pgp_fingerprints(asn)) {
+            Ok(f) if !f.is_empty() => f,
+            Ok(_) => {
+                return AutoPeerResponse::VerifyError {
+                    error: format!("No PGP fingerprint registered for AS{}", asn),
+                };
+            }
+            Err(error) => return AutoPeerResponse::VerifyError { error },
+        };
+
+        let gpg_bin = self
+            .inner
+            .config
+            .gpg_bin
+            .as_deref()
+            .unwrap_or(DEFAULT_GPG_BIN);
+        let signer = match pgp::verify(gpg_bin, pubkey, signature, &challenge).await {
+            Ok(signer) => signer,
+            Err(error) => {
+                warn!(%asn, %error, "AutoPeer PGP verification failed");
+                self.record(asn, "verify_failed", Some(format!("pgp: {}", error)))
+                    .await;
+                return self.failed_pgp_attempt(token, asn, error).await;
+            }
+        };
+
+        if !fingerprints.contains(&signer) {
+            warn!(%asn, %signer, "AutoPeer PGP signer is not a registered maintainer key");
+            self.record(
+                asn,
+                "verify_failed",
+                Some(format!("pgp: unknown key {}", signer)),
+            )
+            .await;
+            let error = format!("Key {} is not registered for AS{}", signer, asn);
+            return self.failed_pgp_attempt(token, asn, error).await;
+        }
+
+        if let Some(t) = token {
+            self.inner.pending.lock().unwrap().remove(t);
+        }
+        self.complete_verification(asn, ChallengeMethod::Pgp).await
+    }
+
+    /// Counts a rejected signature against the challenge, and burns the
+    /// session once `max_attempts` is reached, as for email codes.
+    async fn failed_pgp_attempt(
+        &self,
+        token: Option<&str>,
+        asn: u32,
+        error: String,
+    ) -> AutoPeerResponse {
+        let exhausted = {
+            let mut pending = self.inner.pending.lock().unwrap();
+            let Some(token) = token else {
+                return AutoPeerResponse::VerifyError { error };
+            };
+            let Some(challenge) = pending
+                .get_mut(token)
+                .and_then(|auth| auth.challenge.as_mut())
+                .filter(|c| c.method == ChallengeMethod::Pgp)
+            else {
+                return AutoPeerResponse::VerifyError { error };
+            };
+            challenge.attempts += 1;
+            let exhausted = challenge.attempts >= self.max_attempts();
+            if exhausted {
+                pending.remove(token);
+            }
+            exhausted
+        };
+
+        if !exhausted {
+            return AutoPeerResponse::VerifyError { error };
+        }
+        warn!(%asn, "AutoPeer PGP verification attempts exhausted");
+        self.record(
+            asn,
+            "verify_failed",
+            Some("pgp: attempts exhausted".to_string()),
+        )
+        .await;
+        AutoPeerResponse::VerifyError {
+            error: "Too many failed attempts, please start over".to_string(),
+        }
+    }
+
+    async fn verify_email(&self, token: Option<&str>, code: &str) -> AutoPeerResponse {
+        let (asn, verified) = {
+            let mut pending = self.inner.pending.lock().unwrap();
+            let Some(token) = token else {
+                return AutoPeerResponse::VerifyError {
+                    error: "Session expired, please start over".to_string(),
+                };
+            };
+            let auth = match pending
+                .get_mut(token)
+                .filter(|p| p.expires_at > Instant::now())
+            {
+                Some(auth) => auth,
+                None => {
+                    return AutoPeerResponse::VerifyError {
+                        error: "Session expired, please start over".to_string(),
+                    };
+                }
+            };
+
+            let Some(challenge) = auth
+                .challenge
+                .as_mut()
+                .filter(|c| c.method == ChallengeMethod::Email)
+            else {
+                return AutoPeerResponse::VerifyError {
+                    error: "No email challenge has been issued".to_string(),
+                };
+            };
+
+            let verified = constant_time_eq(&challenge.secret, code.trim());
+            if !verified {
+                challenge.attempts += 1;
+                let max_attempts = self.max_attempts();
+                if challenge.attempts < max_attempts {
+                    return AutoPeerResponse::VerifyError {
+                        error: format!(
+                            "Invalid verification code, {} attempt(s) left",
+                            max_attempts - challenge.attempts
+                        ),
+                    };
+                }
+                warn!(asn = %auth.asn, "AutoPeer email verification attempts exhausted");
+            }
+
+            let asn = auth.asn;
+            pending.remove(token);
+            (asn, verified)
+        };
+
+        if !verified {
+            self.record(
+                asn,
+                "verify_failed",
+                Some("email: attempts exhausted".to_string()),
+            )
+            .await;
+            return AutoPeerResponse::VerifyError {
+                error: "Too many failed attempts, please start over".to_string(),
+            };
+        }
+        self.complete_verification(asn, ChallengeMethod::Email)
+            .await
+    }
+
+    async fn complete_verification(&self, asn: u32, method: ChallengeMethod) -> AutoPeerResponse {
+        let credential = random_string(TOKEN_LENGTH);
+        let Some(expires_at) = chrono::Duration::from_std(self.credential_ttl())
+            .ok()
+            .and_then(|ttl| Utc::now().checked_add_signed(ttl))
+        else {
+            warn!(%asn, "AutoPeer credential_ttl is out of range");
+            return AutoPeerResponse::VerifyError {
+                error: "Failed to issue credential".to_string(),
+            };
+        };
+        let token = credential.clone();
+        let issued = self
+            .storage(move |storage| {
+                storage.purge_expired_credentials()?;
+                storage.insert_credential(&token, asn, expires_at)
+            })
+            .await;
+        if let Err(e) = issued {
+            warn!(%asn, error = ?e, "Failed to store AutoPeer credential");
+            return AutoPeerResponse::VerifyError {
+                error: "Failed to issue credential".to_string(),
+            };
+        }
+
+        info!(%asn, ?method, "AutoPeer verification succeeded");
+        self.record(
+            asn,
+            "verified",
+            Some(format!("{:?}", method).to_lowercase()),
+        )
+        .await;
+        match self.sessions_for(asn).await {
+            Ok(sessions) => AutoPeerResponse::VerifySuccess {
+                credential,
+                sessions,
+            },
+            Err(error) => AutoPeerResponse::VerifyError { error },
+        }
+    }
+
+    async fn authorize(&self, token: Option<&str>) -> Result<u32, String> {
+        let Some(token) = token.map(str::to_string) else {
+            return Err("Credential is invalid or expired, please verify again".to_string());
+        };
+        match self
+            .storage(move |storage| storage.credential(&token))
+            .await
+        {
+            Ok(Some(asn)) => Ok(asn),
+            Ok(None) => Err("Credential is invalid or expired, please verify again".to_string()),
+            Err(e) => {
+                warn!(error = ?e, "Failed to look up AutoPeer credential");
+                Err("Failed to check credential".to_string())
+            }
+        }
+    }
+
+    async fn sessions_for(&self, asn: u32) -> Result<Vec<PeeringSession>, String> {
+        self.storage(move |storage| storage.sessions(asn))
+            .await
+            .map_err(|e| {
+                warn!(%asn, error = ?e, "Failed to load AutoPeer sessions");
+                "Failed to load sessions".to_string()
+            })
+    }
+
+    async fn record(&self, asn: u32, action: &str, detail: Option<String>) {
+        let event = AuditEvent::new(asn, action, detail);
+        if let Err(e) = self
+            .storage(move |storage| storage.record_event(&event))
+            .await
+        {
+            warn!(%asn, %action, error = ?e, "Failed to record AutoPeer audit event");
+        }
+    }
+
+    /// Runs a storage call on the blocking pool, since SQLite does disk I/O.
+    async fn storage<T, F>(&self, f: F) -> anyhow::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn Storage) -> anyhow::Result<T> + Send + 'static,
+    {
+        let inner = self.inner.clone();
+        tokio::task::spawn_blocking(move || f(inner.storage.as_ref())).await?
+    }
+
+    async fn create_session(
+        &self,
+        asn: u32,
+        mut session: PeeringSession,
+    ) -> Result<AutoPeerResponse, String> {
+        self.validate_session(&mut session)?;
+        let id = random_string(SESSION_ID_LENGTH);
+        let detail = format!("{} on {}", id, session.node);
+        session.id = Some(id);
+
+        let max_sessions = self.max_sessions_per_asn();
+        let response = self
+            .modify_sessions(asn, move |sessions| {
+                if sessions.len() >= max_sessions {
+                    return Err(format!(
+                        "AS{} has reached the limit of {} peering sessions",
+                        asn, max_sessions
+                    ));
+                }
+                check_node_free(asn, sessions, &session)?;
+                sessions.push(session);
+                Ok(())
+            })
+            .await?;
+        self.record(asn, "session_created", Some(detail)).await;
+        Ok(response)
+    }
+
+    async fn update_session(
+        &self,
+        asn: u32,
+        id: String,
+        mut session: PeeringSession,
+    ) -> Result<AutoPeerResponse, String> {
+        self.validate_session(&mut session)?;
+        session.id = Some(id.clone());
+        let detail = format!("{} on {}", id, session.node);
+
+        let response = self
+            .modify_sessions(asn, move |sessions| {
+                check_node_free(asn, sessions, &session)?;
+                let existing = sessions
+                    .iter_mut()
+                    .find(|s| s.id == session.id)
+                    .ok_or_else(|| "Session not found".to_string())?;
+                *existing = session;
+                Ok(())
+            })
+            .await?;
+        self.record(asn, "session_updated", Some(detail)).await;
+        Ok(response)
+    }
+
+    async fn delete_session(&self, asn: u32, id: String) -> Result<AutoPeerResponse, String> {
+        let deleted = id.clone();
+        let response = self
+            .modify_sessions(asn, move |sessions| {
+                let before = sessions.len();
+                sessions.retain(|s| s.id.as_deref() != Some(deleted.as_str()));
+                if sessions.len() == before {
+                    return Err("Session not found".to_string());
+                }
+                Ok(())
+            })
+            .await?;
+        self.record(asn, "session_deleted", Some(id)).await;
+        Ok(response)
+    }
+
+    async fn modify_sessions<F>(&self, asn: u32, f: F) -> Result<AutoPeerResponse, String>
+    where
+        F: FnOnce(&mut Vec<PeeringSession>) -> Result<(), String> + Send + 'static,
+    {
+        let inner = self.inner.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let _guard = inner.sessions_lock.lock().unwrap();
+            let mut sessions = inner.storage.sessions(asn).map_err(|e| {
+                warn!(%asn, error = ?e, "Failed to load AutoPeer sessions");
+                "Failed to load sessions".to_string()
+            })?;
+            f(&mut sessions)?;
+            sessions.sort_by(|a, b| a.node.cmp(&b.node).then_with(|| a.name.cmp(&b.name)));
+
+            if let Err(e) = inner.storage.save_sessions(asn, &sessions) {
+                warn!(%asn, error = ?e, "Failed to persist AutoPeer sessions");
+                return Err("Failed to save sessions".to_string());
+            }
+            Ok(sessions)
+        })
+        .await;
+
+        match result {
+            Ok(sessions) => Ok(AutoPeerResponse::SessionsUpdate {
+                sessions: sessions?,
+            }),
+            Err(e) => {
+                warn!(%asn, error = ?e, "AutoPeer session update task failed");
+                Err("Failed to save sessions".to_string())
+            }
+        }
+    }
+
+    /// Checks a session submitted by a peer and normalizes its fields. Node
+    /// uniqueness and the per-ASN quota depend on the stored sessions and are
+    /// checked separately under the sessions lock.
+    fn validate_session(&self, session: &mut PeeringSession) -> Result<(), String> {
+        session.node = session.node.trim().to_string();
+        if !self.inner.nodes.contains(&session.node) {
+            return Err(if session.node.is_empty() {
+                "Node is required".to_string()
+            } else {
+                format!(
+                    "Unknown node '{}', available nodes: {}",
+                    session.node,
+                    self.inner.nodes.join(", ")
+                )
+            });
+        }
+
+        session.name = session.name.trim().to_string();
+        if session.name.is_empty() {
+            return Err("Session name is required".to_string());
+        }
+        if session.name.chars().count() > SESSION_NAME_MAX_LENGTH {
+            return Err(format!(
+                "Session name must be at most {} characters",
+                SESSION_NAME_MAX_LENGTH
+            ));
+        }
+
+        session.ipv4 = normalize_address(session.ipv4.take(), &self.inner.ipv4_ranges, "IPv4")?;
+        session.ipv6 = normalize_address(session.ipv6.take(), &self.inner.ipv6_ranges, "IPv6")?;
+        if session.ipv4.is_none() && session.ipv6.is_none() {
+            return Err("At least one of IPv4 or IPv6 is required".to_string());
+        }
+
+        session.endpoint = session.endpoint.trim().to_string();
+        if session.endpoint.is_empty() {
+            return Err("Endpoint is required".to_string());
+        }
+        validate_endpoint(&session.endpoint)?;
+
+        session.wg_pubkey = session
+            .wg_pubkey
+            .take()
+            .map(|k| k.trim().to_string())
+            .filter(|k| !k.is_empty());
+        if let Some(key) = &session.wg_pubkey {
+            validate_wg_pubkey(key)?;
+        }
+
+        session.comment = session
+            .comment
+            .take()
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty());
+        if let Some(comment) = &session.comment
+            && comment.chars().count() > SESSION_COMMENT_MAX_LENGTH
+        {
+            return Err(format!(
+                "Comment must be at most {} characters",
+                SESSION_COMMENT_MAX_LENGTH
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn registry(&self) -> Result<&Registry, String> {
+        self.inner
+            .registry
+            .as_ref()
+            .ok_or_else(|| "Registry is not available".to_string())
+    }
+
+    fn challenge_ttl(&self) -> Duration {
+        Duration::from_secs(
+            self.inner
+                .config
+                .challenge_ttl
+                .unwrap_or(DEFAULT_CHALLENGE_TTL),
+        )
+    }
+
+    fn max_attempts(&self) -> u32 {
+        self.inner
+            .config
+            .max_attempts
+            .unwrap_or(DEFAULT_MAX_ATTEMPTS)
+    }
+
+    fn max_sessions_per_asn(&self) -> usize {
+        self.inner
+            .config
+            .max_sessions_per_asn
+            .unwrap_or(DEFAULT_MAX_SESSIONS_PER_ASN)
+    }
+
+    fn credential_ttl(&self) -> Duration {
+        Duration::from_secs(
+            self.inner
+                .config
+                .credential_ttl
+                .unwrap_or(DEFAULT_CREDENTIAL_TTL),
+        )
+    }
+}
+
+/// Opens the SQLite database, or in-memory storage when none is configured,
+/// and imports the JSON sessions file of earlier versions.
+fn open_storage(config: &AutoPeerConfig) -> anyhow::Result<Box<dyn Storage>> {
+    let database_path = config.database_path.clone().or_else(|| {
+        config.data_path.as_ref().map(|path| {
+            Path::new(path)
+                .with_extension("db")
+                .to_string_lossy()
+                .to_string()
+        })
+    });
+
+    let storage: Box<dyn Storage> = match &database_path {
+        Some(path) => Box::new(SqliteStorage::open(path)?),
+        None => {
+            warn!("autopeer database_path is not set, sessions will be lost on restart");
+            Box::new(MemoryStorage::default())
+        }
+    };
+
+    if let Some(legacy) = &config.data_path
+        && database_path.as_ref() != Some(legacy)
+    {
+        let imported = storage::import_json(storage.as_ref(), legacy)?;
+        if imported > 0 {
+            info!(path = %legacy, sessions = imported, "Imported AutoPeer sessions");
+        }
+    }
+
+    info!(
+        path = database_path.as_deref().unwrap_or("memory"),
+        sessions = storage.session_count()?,
+        "Loaded AutoPeer storage"
+    );
+    Ok(storage)
+}
+
+fn mask_email(email: &str) -> String {
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            let first = local.chars().next().unwrap_or('*');
+            format!("{}***@{}", first, domain)
+        }
+        None => "***".to_string(),
+    }
+}
+
+fn check_node_free(
+    asn: u32,
+    sessions: &[PeeringSession],
+    session: &PeeringSession,
+) -> Result<(), String> {
+    if sessions
+        .iter()
+        .any(|s| s.node == session.node && s.id != session.id)
+    {
+        return Err(format!(
+            "AS{} already has a peering session on node {}",
+            asn, session.node
+        ));
+    }
+    Ok(())
+}
+
+fn normalize_address(
+    address: Option<String>,
+    ranges: &[IpNet],
+    family: &str,
+) -> Result<Option<String>, String> {
+    let Some(address) = address
+        .map(|a| a.trim().to_string())
+        .filter(|a| !a.is_empty())
+    else {
+        return Ok(None);
+    };
+
+    let ip = address
+        .parse::<IpAddr>()
+        .ok()
+        .filter(|ip| ip.is_ipv4() == (family == "IPv4"))
+        .ok_or_else(|| format!("'{}' is not a valid {} address", address, family))?;
+    if !ranges.iter().any(|net| net.contains(&ip)) {
+        let ranges = ranges
+            .iter()
+            .map(|net| net.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        return Err(format!(
+            "{} address {} is outside the allowed peering ranges ({})",
+            family, ip, ranges
+        ));
+    }
+
+    Ok(Some(ip.to_string()))
+}
+
+fn parse_ranges(ranges: Option<&[String]>, defaults: &[&str]) -> anyhow::Result<Vec<IpNet>> {
+    match ranges {
+        Some(ranges) => ranges
+            .iter()
+            .map(|r| {
+                r.parse::<IpNet>()
+                    .with_context(|| format!("'{}' is not a valid prefix", r))
+            })
+            .collect(),
+        None => Ok(defaults.iter().map(|r| r.parse().unwrap()).collect()),
+    }
+}
+
+/// Compares codes without returning early at the first differing byte.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+fn random_string(len: usize) -> String {
+    rand::rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::DirBuilder,
+        io::Write,
+        os::unix::fs::DirBuilderExt,
+        path::{Path, PathBuf},
+        process::{Command, Stdio},
+    };
+
+    use super::*;
+    use crate::config::RegistryConfig;
+
+    const ASN: u32 = 4242420000;
+
+    fn fixture(path: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(path)
+    }
+
+    fn autopeer() -> AutoPeer {
+        autopeer_with(serde_json::json!({ "challenge_methods": ["pgp"] }))
+    }
+
+    /// Without a `database_path` sessions are kept in `MemoryStorage`.
+    fn autopeer_with(config: serde_json::Value) -> AutoPeer {
+        let registry = Registry::load(&RegistryConfig {
+            path: fixture("registry").to_string_lossy().to_string(),
+            reload_interval: None,
+        })
+        .unwrap();
+        let config = serde_json::from_value(config).unwrap();
+        let nodes = vec!["node1".to_string(), "node2".to_string()];
+        AutoPeer::load(config, nodes, Some(registry)).unwrap()
+    }
+
+    async fn credential(autopeer: &AutoPeer, asn: u32) -> String {
+        match autopeer
+            .complete_verification(asn, ChallengeMethod::Pgp)
+            .await
+        {
+            AutoPeerResponse::VerifySuccess { credential, .. } => credential,
+            other => panic!("unexpected response {:?}", other),
+        }
+    }
+
+    fn session(node: &str) -> PeeringSession {
+        PeeringSession {
+            id: None,
+            node: node.to_string(),
+            name: "Example peer".to_string(),
+            ipv4: Some("172.20.0.1".to_string()),
+            ipv6: Some("fe80::1".to_string()),
+            endpoint: "peer.example.com:51820".to_string(),
+            wg_pubkey: None,
+            comment: None,
+        }
+    }
+
+    async fn sessions_request(
+        autopeer: &AutoPeer,
+        credential: &str,
+        req: AutoPeerRequest,
+    ) -> Result<Vec<PeeringSession>, String> {
+        match autopeer.handle(Some(credential), req).await {
+            AutoPeerResponse::SessionsUpdate { sessions } => Ok(sessions),
+            AutoPeerResponse::OperationError { error } => Err(error),
+            other => panic!("unexpected response {:?}", other),
+        }
+    }
+
+    async fn create(
+        autopeer: &AutoPeer,
+        credential: &str,
+        session: PeeringSession,
+    ) -> Result<Vec<PeeringSession>, String> {
+        sessions_request(
+            autopeer,
+            credential,
+            AutoPeerRequest::CreateSession { session },
+        )
+        .await
+    }
+
+    /// Clearsigns `text` with a throwaway key from the fixtures.
+    fn sign(key: &str, text: &str) -> String {
+        let home = std::env::temp_dir().join(format!("bird-lg-test-{}", random_string(16)));
+        DirBuilder::new().mode(0o700).create(&home).unwrap();
+        let gpg = || {
+            let mut cmd = Command::new(DEFAULT_GPG_BIN);
+            cmd.arg("--homedir")
+                .arg(&home)
+                .args(["--batch", "--no-tty", "--yes"]);
+            cmd
+        };
+
+        let imported = gpg()
+            .arg("--import")
+            .arg(fixture(&format!("pgp/{}.key", key)))
+            .output()
+            .unwrap();
+        assert!(imported.status.success(), "failed to import {}", key);
+
+        let mut child = gpg()
+            .arg("--clearsign")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(text.as_bytes())
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        std::fs::remove_dir_all(&home).unwrap();
+        assert!(output.status.success(), "failed to sign with {}", key);
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    fn pubkey(key: &str) -> String {
+        std::fs::read_to_string(fixture(&format!("pgp/{}.asc", key))).unwrap()
+    }
+
+    async fn pgp_challenge(autopeer: &AutoPeer, asn: u32) -> (String, String) {
+        let AutoPeerResponse::InitSuccess { token, .. } = autopeer
+            .handle(
+                None,
+                AutoPeerRequest::InitSession {
+                    asn: format!("AS{}", asn),
+                },
+            )
+            .await
+        else {
+            panic!("init failed");
+        };
+        let response = autopeer
+            .handle(
+                Some(&token),
+                AutoPeerRequest::SelectChallenge {
+                    method: ChallengeMethod::Pgp,
+                },
+            )
+            .await;
+        let AutoPeerResponse::ChallengeSelected {
+            challenge_text: Some(text),
+        } = response
+        else {
+            panic!("unexpected response {:?}", response);
+        };
+        (token, text)
+    }
+
+    async fn verify(
+        autopeer: &AutoPeer,
+        token: &str,
+        key: &str,
+        signature: String,
+    ) -> AutoPeerResponse {
+        autopeer
+            .handle(
+                Some(token),
+                AutoPeerRequest::VerifyPgp {
+                    pubkey: pubkey(key),
+                    signature,
+                },
+            )
+            .await
+    }
+
+    fn verify_error(response: AutoPeerResponse) -> String {
+        match response {
+            AutoPeerResponse::VerifyError { error } => error,
+            other => panic!("expected a verification error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn registry_fingerprints() {
+        let autopeer = autopeer();
+        let registry = autopeer.registry().unwrap();
+        assert_eq!(
+            registry.pgp_fingerprints(ASN).unwrap(),
+            ["B2F900383D951DD6398C773CC05C1675A0A494C8"]
+        );
+        assert!(registry.pgp_fingerprints(4242429999).is_err());
+    }
+
+    #[test]
+    fn email_limiter_holds_across_sessions() {
+        let mut limiter = EmailLimiter::default();
+        let noc = ["noc@example.test".to_string()];
+        assert!(limiter.reserve(ASN, &noc).is_ok());
+        assert!(limiter.reserve(ASN, &[]).is_err());
+        assert!(
+            limiter
+                .reserve(4242420001, &["NOC@example.test".to_string()])
+                .is_err()
+        );
+        assert!(
+            limiter
+                .reserve(4242420001, &["other@example.test".to_string()])
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn pending_auths_are_capped() {
+        let autopeer = autopeer();
+        for _ in 0..MAX_PENDING_AUTHS {
+            let response = autopeer
+                .handle(
+                    None,
+                    AutoPeerRequest::InitSession {
+                        asn: ASN.to_string(),
+                    },
+                )
+                .await;
+            assert!(matches!(response, AutoPeerResponse::InitSuccess { .. }));
+        }
+        let response = autopeer
+            .handle(
+                None,
+                AutoPeerRequest::InitSession {
+                    asn: ASN.to_string(),
+                },
+            )
+            .await;
+        assert!(matches!(response, AutoPeerResponse::InitError { .. }));
+    }
+
+    #[tokio::test]
+    async fn pgp_accepts_registered_key() {
+        let autopeer = autopeer();
+        let (token, challenge) = pgp_challenge(&autopeer, ASN).await;
+        let signature = sign("maintainer", &challenge);
+
+        let response = verify(&autopeer, &token, "maintainer", signature).await;
+        let AutoPeerResponse::VerifySuccess { credential, .. } = response else {
+            panic!("unexpected response {:?}", response);
+        };
+        assert_eq!(autopeer.authorize(Some(&credential)).await, Ok(ASN));
+    }
+
+    #[tokio::test]
+    async fn pgp_rejects_unknown_fingerprint() {
+        let autopeer = autopeer();
+        let (token, challenge) = pgp_challenge(&autopeer, ASN).await;
+        let signature = sign("unregistered", &challenge);
+
+        let error = verify_error(verify(&autopeer, &token, "unregistered", signature).await);
+        assert!(
+            error.contains("is not registered for AS4242420000"),
+            "{}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn pgp_rejects_bad_signature() {
+        let autopeer = autopeer();
+        let (token, challenge) = pgp_challenge(&autopeer, ASN).await;
+
+        // Signed by the right key, but over a different text.
+        let signature = sign("maintainer", &format!("{} tampered", challenge));
+        let error = verify_error(verify(&autopeer, &token, "maintainer", signature).await);
+        assert_eq!(error, "Signed message does not match the challenge");
+
+        // A signature whose content was altered after signing.
+        let signature = sign("maintainer", &challenge).replace("AutoPeer AS", "AutoPeer XS");
+        let error = verify_error(verify(&autopeer, &token, "maintainer", signature).await);
+        assert_eq!(error, "Signature verification failed");
+    }
+
+    #[tokio::test]
+    async fn pgp_attempts_are_limited() {
+        let autopeer = autopeer_with(serde_json::json!({
+            "challenge_methods": ["pgp"],
+            "max_attempts": 2,
+        }));
+        let (token, challenge) = pgp_challenge(&autopeer, ASN).await;
+
+        let signature = sign("maintainer", &format!("{} tampered", challenge));
+        let error = verify_error(verify(&autopeer, &token, "maintainer", signature).await);
+        assert_eq!(error, "Signed message does not match the challenge");
+
+        let signature = sign("unregistered", &challenge);
+        let error = verify_error(verify(&autopeer, &token, "unregistered", signature).await);
+        assert_eq!(error, "Too many failed attempts, please start over");
+
+        // The token is burnt, even for a valid signature.
+        let signature = sign("maintainer", &challenge);
+        let error = verify_error(verify(&autopeer, &token, "maintainer", signature).await);
+        assert_eq!(error, "Session expired, please start over");
+    }
+
+    #[test]
+    fn codes_compare_whole_strings() {
+        assert!(constant_time_eq("123456", "123456"));
+        assert!(!constant_time_eq("123456", "123457"));
+        assert!(!constant_time_eq("123456", "12345"));
+        assert!(!constant_time_eq("", "1"));
+    }
+
+    #[tokio::test]
+    async fn pgp_rejects_missing_asn() {
+        let autopeer = autopeer();
+        let (token, challenge) = pgp_challenge(&autopeer, 4242429999).await;
+        let signature = sign("maintainer", &challenge);
+
+        let error = verify_error(verify(&autopeer, &token, "maintainer", signature).await);
+        assert_eq!(error, "AS4242429999 is not registered");
+    }
+
+    #[test]
+    fn rejects_out_of_range_ttls() {
+        for field in ["challenge_ttl", "credential_ttl"] {
+            let config = serde_json::from_value(serde_json::json!({
+                "challenge_methods": [],
+                field: u64::MAX,
+            }))
+            .unwrap();
+            let error = AutoPeer::load(config, Vec::new(), None).err().unwrap();
+            assert_eq!(
+                error.to_string(),
+                format!("autopeer {} must be at most 31536000 seconds", field)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn session_quota_per_asn() {
+        let autopeer = autopeer_with(serde_json::json!({
+            "challenge_methods": ["pgp"],
+            "max_sessions_per_asn": 1,
+        }));
+        let owner = credential(&autopeer, ASN).await;
+
+        assert!(create(&autopeer, &owner, session("node1")).await.is_ok());
+        assert_eq!(
+            create(&autopeer, &owner, session("node2")).await,
+            Err("AS4242420000 has reached the limit of 1 peering sessions".to_string())
+        );
+
+        // The quota is per ASN.
+        let other = credential(&autopeer, 4242420001).await;
+        assert!(create(&autopeer, &other, session("node2")).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn one_session_per_node() {
+        let autopeer = autopeer();
+        let credential = credential(&autopeer, ASN).await;
+
+        create(&autopeer, &credential, session("node1"))
+            .await
+            .unwrap();
+        assert_eq!(
+            create(&autopeer, &credential, session("node1")).await,
+            Err("AS4242420000 already has a peering session on node node1".to_string())
+        );
+
+        // Moving a session onto a node that is taken fails as well, keeping
+        // it on its own node doesn't.
+        let sessions = create(&autopeer, &credential, session("node2"))
+            .await
+            .unwrap();
+        let id = sessions[1].id.clone().unwrap();
+        let update = |node| AutoPeerRequest::UpdateSession {
+            id: id.clone(),
+            session: session(node),
+        };
+        assert_eq!(
+            sessions_request(&autopeer, &credential, update("node1")).await,
+            Err("AS4242420000 already has a peering session on node node1".to_string())
+        );
+        assert!(
+            sessions_request(&autopeer, &credential, update("node2"))
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn session_addresses() {
+        let autopeer = autopeer();
+        let credential = credential(&autopeer, ASN).await;
+        let with = |ipv4: Option<&str>, ipv6: Option<&str>| PeeringSession {
+            ipv4: ipv4.map(str::to_string),
+            ipv6: ipv6.map(str::to_string),
+            ..session("node1")
+        };
+
+        for (session, error) in [
+            (
+                with(Some("192.168.1.1"), None),
+                "IPv4 address 192.168.1.1 is outside the allowed peering ranges \
+                 (172.20.0.0/14, 172.31.0.0/16, 10.0.0.0/8)",
+            ),
+            (
+                with(None, Some("2001:db8::1")),
+                "IPv6 address 2001:db8::1 is outside the allowed peering ranges \
+                 (fe80::/10, fd00::/8)",
+            ),
+            (
+                with(Some("fe80::1"), None),
+                "'fe80::1' is not a valid IPv4 address",
+            ),
+            (
+                with(None, Some("172.20.0.1")),
+                "'172.20.0.1' is not a valid IPv6 address",
+            ),
+            (
+                with(None, Some(" ")),
+                "At least one of IPv4 or IPv6 is required",
+            ),
+        ] {
+            assert_eq!(
+                create(&autopeer, &credential, session).await,
+                Err(error.to_string())
+            );
+        }
+
+        // Link-local IPv6 alone is enough, and addresses are normalized.
+        let sessions = create(&autopeer, &credential, with(None, Some(" FE80::0042 ")))
+            .await
+            .unwrap();
+        assert_eq!(sessions[0].ipv4, None);
+        assert_eq!(sessions[0].ipv6.as_deref(), Some("fe80::42"));
+    }
+
+    #[tokio::test]
+    async fn session_endpoints() {
+        let autopeer = autopeer();
+        let credential = credential(&autopeer, ASN).await;
+        let with = |endpoint: &str| PeeringSession {
+            endpoint: endpoint.to_string(),
+            ..session("node1")
+        };
+
+        for (endpoint, error) in [
+            ("", "Endpoint is required"),
+            ("peer.example.com", "Endpoint must be in host:port format"),
+            (
+                "fd00::1:51820",
+                "IPv6 endpoints must be written as [address]:port",
+            ),
+            ("peer.example.com:0", "Endpoint port '0' is invalid"),
+            ("peer.example.com:wg", "Endpoint port 'wg' is invalid"),
+        ] {
+            assert_eq!(
+                create(&autopeer, &credential, with(endpoint)).await,
+                Err(error.to_string()),
+                "{}",
+                endpoint
+            );
+        }
+
+        let sessions = create(&autopeer, &credential, with("[fd00::1]:51820"))
+            .await
+            .unwrap();
+        assert_eq!(sessions[0].endpoint, "[fd00::1]:51820");
+    }
+
+    #[tokio::test]
+    async fn session_nodes() {
+        let autopeer = autopeer();
+        let credential = credential(&autopeer, ASN).await;
+
+        assert_eq!(
+            create(&autopeer, &credential, session("node9")).await,
+            Err("Unknown node 'node9', available nodes: node1, node2".to_string())
+        );
+        assert_eq!(
+            create(&autopeer, &credential, session(" ")).await,
+            Err("Node is required".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn sessions_of_other_asns() {
+        let autopeer = autopeer();
+        let owner = credential(&autopeer, ASN).await;
+        let other = credential(&autopeer, 4242420001).await;
+
+        let sessions = create(&autopeer, &owner, session("node1")).await.unwrap();
+        let id = sessions[0].id.clone().unwrap();
+
+        let update = AutoPeerRequest::UpdateSession {
+            id: id.clone(),
+            session: PeeringSession {
+                name: "Hijacked".to_string(),
+                ..session("node1")
+            },
+        };
+        assert_eq!(
+            sessions_request(&autopeer, &other, update).await,
+            Err("Session not found".to_string())
+        );
+        assert_eq!(
+            sessions_request(&autopeer, &other, AutoPeerRequest::DeleteSession { id }).await,
+            Err("Session not found".to_string())
+        );
+        assert_eq!(
+            sessions_request(&autopeer, &owner, AutoPeerRequest::GetSessions).await,
+            Ok(sessions)
+        );
+        assert_eq!(
+            sessions_request(&autopeer, &other, AutoPeerRequest::GetSessions).await,
+            Ok(Vec::new())
+        );
+    }
+
+    #[tokio::test]
+    async fn sessions_need_a_credential() {
+        let autopeer = autopeer();
+        assert_eq!(
+            create(&autopeer, "not-a-credential", session("node1")).await,
+            Err("Credential is invalid or expired, please verify again".to_string())
+        );
+    }
+}
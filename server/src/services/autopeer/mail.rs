@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use lettre::{
+    AsyncFileTransport, AsyncSendmailTransport, AsyncSmtpTransport, AsyncTransport, Message,
+    Tokio1Executor,
+    message::{Mailbox, header::ContentType},
+    transport::smtp::authentication::Credentials,
+};
+use tracing::{info, warn};
+
+use crate::config::{MailConfig, MailTransportConfig, SmtpTls};
+
+pub struct Mailer {
+    from: Mailbox,
+    transport: Transport,
+}
+
+enum Transport {
+    Smtp(AsyncSmtpTransport<Tokio1Executor>),
+    Sendmail(AsyncSendmailTransport<Tokio1Executor>),
+    File(AsyncFileTransport<Tokio1Executor>),
+    Log,
+}
+
+impl Mailer {
+    pub fn new(config: &MailConfig) -> anyhow::Result<Self> {
+        let from = config
+            .from
+            .parse::<Mailbox>()
+            .with_context(|| format!("Invalid mail sender '{}'", config.from))?;
+
+        let transport = match &config.transport {
+            MailTransportConfig::Smtp {
+                host,
+                port,
+                username,
+                password,
+                tls,
+            } => {
+                let mut builder = match tls {
+                    SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                        .with_context(|| format!("Invalid SMTP relay '{}'", host))?,
+                    SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+                        .with_context(|| format!("Invalid SMTP relay '{}'", host))?,
+                    SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+                };
+                if let Some(port) = port {
+                    builder = builder.port(*port);
+                }
+                if let (Some(username), Some(password)) = (username, password) {
+                    builder =
+                        builder.credentials(Credentials::new(username.clone(), password.clone()));
+                }
+                Transport::Smtp(builder.build())
+            }
+            MailTransportConfig::Sendmail { command } => Transport::Sendmail(match command {
+                Some(command) => AsyncSendmailTransport::new_with_command(command),
+                None => AsyncSendmailTransport::new(),
+            }),
+            MailTransportConfig::File { path } => {
+                std::fs::create_dir_all(path)
+                    .with_context(|| format!("Failed to create mail directory '{}'", path))?;
+                Transport::File(AsyncFileTransport::new(path))
+            }
+            MailTransportConfig::Log => Transport::Log,
+        };
+
+        Ok(Self { from, transport })
+    }
+
+    pub async fn send_code(
+        &self,
+        recipients: &[String],
+        asn: u32,
+        code: &str,
+        ttl: Duration,
+    ) -> Result<(), String> {
+        let mut builder = Message::builder()
+            .from(self.from.clone())
+            .subject(format!("AutoPeer verification code for AS{}", asn))
+            .header(ContentType::TEXT_PLAIN);
+        for recipient in recipients {
+            match recipient.parse::<Mailbox>() {
+                Ok(mailbox) => builder = builder.to(mailbox),
+                Err(e) => warn!(%recipient, error = %e, "Skipping invalid registry email"),
+            }
+        }
+
+        let body = format!(
+            "Your AutoPeer verification code for AS{} is:\n\n    {}\n\n\
+             The code expires in {} minutes. If you did not request it, ignore this email.\n",
+            asn,
+            code,
+            ttl.as_secs() / 60
+        );
+        let message = builder
+            .body(body)
+            .map_err(|e| format!("Failed to build email: {}", e))?;
+
+        let result = match &self.transport {
+            Transport::Smtp(t) => t.send(message).await.map(|_| ()).map_err(|e| e.to_string()),
+            Transport::Sendmail(t) => t.send(message).await.map_err(|e| e.to_string()),
+            Transport::File(t) => t.send(message).await.map(|_| ()).map_err(|e| e.to_string()),
+            Transport::Log => {
+                info!(?recipients, %asn, %code, "AutoPeer verification code");
+                Ok(())
+            }
+        };
+
+        result.map_err(|e| {
+            warn!(%asn, error = %e, "Failed to deliver AutoPeer verification email");
+            "Failed to send verification email".to_string()
+        })
+    }
+}
@@ -0,0 +1,146 @@
+use std::{
+    fs::DirBuilder,
+    os::unix::fs::DirBuilderExt,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use chrono::Utc;
+use tokio::{io::AsyncWriteExt, process::Command};
+use tracing::warn;
+
+use super::random_string;
+
+const SIGNED_MESSAGE_HEADER: &str = "-----BEGIN PGP SIGNED MESSAGE-----";
+
+pub fn challenge_text(asn: u32) -> String {
+    format!(
+        "AutoPeer AS{} {} {}",
+        asn,
+        Utc::now().timestamp(),
+        random_string(32)
+    )
+}
+
+/// Verifies `signature` over `challenge` with `pubkey` and returns the
+/// primary key fingerprint of the signer. Both clearsigned messages and
+/// detached armored signatures are accepted.
+pub async fn verify(
+    gpg_bin: &str,
+    pubkey: &str,
+    signature: &str,
+    challenge: &str,
+) -> Result<String, String> {
+    let home = GpgHome::create()?;
+    let gpg = |args: &[&str]| {
+        let mut cmd = Command::new(gpg_bin);
+        cmd.arg("--homedir")
+            .arg(&home.0)
+            .args(["--batch", "--no-tty", "--no-autostart", "--status-fd", "1"])
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        cmd
+    };
+
+    run(gpg(&["--import"]), Some(pubkey))
+        .await
+        .map_err(|_| "Failed to import public key".to_string())?;
+
+    let signature_path = home.0.join("signature.asc");
+    std::fs::write(&signature_path, signature)
+        .map_err(|e| format!("Failed to prepare signature: {}", e))?;
+    let signature_arg = signature_path.to_string_lossy().to_string();
+
+    if signature.trim_start().starts_with(SIGNED_MESSAGE_HEADER) {
+        let plain_path = home.0.join("plain.txt");
+        let plain_arg = plain_path.to_string_lossy().to_string();
+        let status = run(
+            gpg(&["--output", &plain_arg, "--decrypt", &signature_arg]),
+            None,
+        )
+        .await
+        .map_err(|_| "Signature verification failed".to_string())?;
+
+        let plain = std::fs::read_to_string(&plain_path).unwrap_or_default();
+        if plain.trim() != challenge {
+            return Err("Signed message does not match the challenge".to_string());
+        }
+        return signer_fingerprint(&status);
+    }
+
+    let challenge_path = home.0.join("challenge.txt");
+    let challenge_arg = challenge_path.to_string_lossy().to_string();
+    for content in [format!("{}\n", challenge), challenge.to_string()] {
+        std::fs::write(&challenge_path, &content)
+            .map_err(|e| format!("Failed to prepare challenge: {}", e))?;
+        if let Ok(status) = run(gpg(&["--verify", &signature_arg, &challenge_arg]), None).await {
+            return signer_fingerprint(&status);
+        }
+    }
+
+    Err("Signature verification failed".to_string())
+}
+
+async fn run(mut cmd: Command, input: Option<&str>) -> Result<String, ()> {
+    let mut child = cmd.spawn().map_err(|e| {
+        warn!(error = %e, "Failed to spawn gpg");
+    })?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        if let Some(input) = input
+            && let Err(e) = stdin.write_all(input.as_bytes()).await
+        {
+            warn!(error = %e, "Failed to write gpg input");
+        }
+        drop(stdin);
+    }
+
+    let output = child.wait_with_output().await.map_err(|e| {
+        warn!(error = %e, "Failed to wait for gpg");
+    })?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(())
+    }
+}
+
+fn signer_fingerprint(status: &str) -> Result<String, String> {
+    status
+        .lines()
+        .filter_map(|line| line.strip_prefix("[GNUPG:] VALIDSIG "))
+        .find_map(|rest| {
+            let fields: Vec<&str> = rest.split_whitespace().collect();
+            fields
+                .get(9)
+                .or(fields.first())
+                .map(|f| f.to_ascii_uppercase())
+        })
+        .ok_or_else(|| "Signature is not valid".to_string())
+}
+
+struct GpgHome(PathBuf);
+
+impl GpgHome {
+    fn create() -> Result<Self, String> {
+        let path = std::env::temp_dir().join(format!("bird-lg-gpg-{}", random_string(16)));
+        DirBuilder::new()
+            .mode(0o700)
+            .create(&path)
+            .map_err(|e| format!("Failed to create gpg home: {}", e))?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for GpgHome {
+    fn drop(&mut self) {
+        if Path::new(&self.0).exists()
+            && let Err(e) = std::fs::remove_dir_all(&self.0)
+        {
+            warn!(error = %e, path = %self.0.display(), "Failed to remove gpg home");
+        }
+    }
+}
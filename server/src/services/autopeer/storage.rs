@@ -0,0 +1,226 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use common::auto_peer::PeeringSession;
+use sha2::{Digest, Sha256};
+
+pub use self::{memory::MemoryStorage, sqlite::SqliteStorage};
+
+mod memory;
+mod sqlite;
+
+/// Persistence for AutoPeer state that has to outlive a restart. Pending
+/// challenges are short-lived and stay in memory.
+pub trait Storage: Send + Sync {
+    fn sessions(&self, asn: u32) -> anyhow::Result<Vec<PeeringSession>>;
+
+    /// Replaces every session of `asn` in a single step.
+    fn save_sessions(&self, asn: u32, sessions: &[PeeringSession]) -> anyhow::Result<()>;
+
+    fn session_count(&self) -> anyhow::Result<usize>;
+
+    fn insert_credential(
+        &self,
+        token: &str,
+        asn: u32,
+        expires_at: DateTime<Utc>,
+    ) -> anyhow::Result<()>;
+
+    /// Returns the ASN of an unexpired credential.
+    fn credential(&self, token: &str) -> anyhow::Result<Option<u32>>;
+
+    fn purge_expired_credentials(&self) -> anyhow::Result<()>;
+
+    fn record_event(&self, event: &AuditEvent) -> anyhow::Result<()>;
+
+    /// Returns the audit events of `asn`, oldest first.
+    #[cfg(test)]
+    fn events(&self, asn: u32) -> anyhow::Result<Vec<AuditEvent>>;
+}
+
+#[derive(Clone, Debug)]
+pub struct AuditEvent {
+    pub timestamp: DateTime<Utc>,
+    pub asn: u32,
+    pub action: String,
+    pub detail: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn new(asn: u32, action: &str, detail: Option<String>) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            asn,
+            action: action.to_string(),
+            detail,
+        }
+    }
+}
+
+/// Imports the JSON sessions file of earlier versions and renames it, so it
+/// is only imported once. Returns the number of imported sessions.
+pub fn import_json(storage: &dyn Storage, path: &str) -> anyhow::Result<usize> {
+    if !Path::new(path).is_file() {
+        return Ok(0);
+    }
+
+    let raw = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read AutoPeer data '{}'", path))?;
+    let sessions: HashMap<u32, Vec<PeeringSession>> = serde_json::from_str(&raw)
+        .with_context(|| format!("Failed to parse AutoPeer data '{}'", path))?;
+    for (asn, sessions) in &sessions {
+        storage.save_sessions(*asn, sessions)?;
+    }
+
+    let migrated = format!("{}.migrated", path);
+    fs::rename(path, &migrated).with_context(|| format!("Failed to rename '{}'", path))?;
+    Ok(sessions.values().map(Vec::len).sum())
+}
+
+/// Credentials are bearer tokens, so only their hash is ever stored.
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use chrono::Duration;
+
+    use super::*;
+    use crate::services::autopeer::random_string;
+
+    /// A database file in the temp directory, removed on drop.
+    struct TempDb(PathBuf);
+
+    impl TempDb {
+        fn new() -> Self {
+            Self(std::env::temp_dir().join(format!("bird-lg-test-{}.db", random_string(16))))
+        }
+
+        fn path(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            for suffix in ["", "-wal", "-shm"] {
+                let _ = fs::remove_file(format!("{}{}", self.path(), suffix));
+            }
+        }
+    }
+
+    fn session(id: &str, node: &str) -> PeeringSession {
+        PeeringSession {
+            id: Some(id.to_string()),
+            node: node.to_string(),
+            name: format!("session {}", id),
+            ipv4: Some("172.20.0.1".to_string()),
+            ipv6: None,
+            endpoint: "peer.example.test:51820".to_string(),
+            wg_pubkey: None,
+            comment: None,
+        }
+    }
+
+    fn check_sessions(storage: &dyn Storage) {
+        assert!(storage.sessions(1).unwrap().is_empty());
+        assert_eq!(storage.session_count().unwrap(), 0);
+
+        let sessions = vec![session("a", "node1"), session("b", "node2")];
+        storage.save_sessions(1, &sessions).unwrap();
+        storage.save_sessions(2, &[session("c", "node1")]).unwrap();
+        assert_eq!(storage.sessions(1).unwrap(), sessions);
+        assert_eq!(storage.session_count().unwrap(), 3);
+
+        // Saving replaces every session of the ASN.
+        storage.save_sessions(1, &[session("b", "node2")]).unwrap();
+        assert_eq!(storage.sessions(1).unwrap(), [session("b", "node2")]);
+        storage.save_sessions(2, &[]).unwrap();
+        assert!(storage.sessions(2).unwrap().is_empty());
+        assert_eq!(storage.session_count().unwrap(), 1);
+    }
+
+    fn check_credentials(storage: &dyn Storage) {
+        let now = Utc::now();
+        storage
+            .insert_credential("valid", 1, now + Duration::hours(1))
+            .unwrap();
+        storage
+            .insert_credential("expired", 2, now - Duration::seconds(1))
+            .unwrap();
+
+        assert_eq!(storage.credential("valid").unwrap(), Some(1));
+        assert_eq!(storage.credential("expired").unwrap(), None);
+        assert_eq!(storage.credential("unknown").unwrap(), None);
+        // Lookups go by hash, never by the raw token.
+        assert_eq!(storage.credential(&hash_token("valid")).unwrap(), None);
+
+        storage.purge_expired_credentials().unwrap();
+        assert_eq!(storage.credential("valid").unwrap(), Some(1));
+        storage
+            .insert_credential("expired", 2, now + Duration::hours(1))
+            .unwrap();
+        assert_eq!(storage.credential("expired").unwrap(), Some(2));
+    }
+
+    fn check_events(storage: &dyn Storage) {
+        storage
+            .record_event(&AuditEvent::new(1, "verified", Some("pgp".to_string())))
+            .unwrap();
+        storage
+            .record_event(&AuditEvent::new(2, "verified", None))
+            .unwrap();
+        storage
+            .record_event(&AuditEvent::new(1, "session_created", None))
+            .unwrap();
+
+        let events = storage.events(1).unwrap();
+        let actions: Vec<_> = events.iter().map(|e| e.action.as_str()).collect();
+        assert_eq!(actions, ["verified", "session_created"]);
+        assert_eq!(events[0].detail.as_deref(), Some("pgp"));
+        assert_eq!(storage.events(2).unwrap().len(), 1);
+    }
+
+    fn check_storage(storage: &dyn Storage) {
+        check_sessions(storage);
+        check_credentials(storage);
+        check_events(storage);
+    }
+
+    #[test]
+    fn memory_storage() {
+        check_storage(&MemoryStorage::default());
+    }
+
+    #[test]
+    fn sqlite_storage() {
+        let db = TempDb::new();
+        check_storage(&SqliteStorage::open(db.path()).unwrap());
+
+        let reopened = SqliteStorage::open(db.path()).unwrap();
+        assert_eq!(reopened.session_count().unwrap(), 1);
+        assert_eq!(reopened.credential("valid").unwrap(), Some(1));
+        assert_eq!(reopened.events(1).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn import_legacy_json() {
+        let db = TempDb::new();
+        let legacy = format!("{}.json", db.path());
+        let sessions = HashMap::from([(1u32, vec![session("a", "node1")])]);
+        fs::write(&legacy, serde_json::to_string(&sessions).unwrap()).unwrap();
+
+        let storage = SqliteStorage::open(db.path()).unwrap();
+        assert_eq!(import_json(&storage, &legacy).unwrap(), 1);
+        assert_eq!(storage.sessions(1).unwrap(), [session("a", "node1")]);
+
+        // The file is renamed, so it is only imported once.
+        assert!(!Path::new(&legacy).exists());
+        assert_eq!(import_json(&storage, &legacy).unwrap(), 0);
+        fs::remove_file(format!("{}.migrated", legacy)).unwrap();
+    }
+}
@@ -0,0 +1,91 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, RwLock},
+};
+
+use chrono::{DateTime, Utc};
+use common::auto_peer::PeeringSession;
+
+use super::{AuditEvent, Storage, hash_token};
+
+#[derive(Default)]
+pub struct MemoryStorage {
+    sessions: RwLock<HashMap<u32, Vec<PeeringSession>>>,
+    credentials: Mutex<HashMap<String, (u32, DateTime<Utc>)>>,
+    events: Mutex<Vec<AuditEvent>>,
+}
+
+impl Storage for MemoryStorage {
+    fn sessions(&self, asn: u32) -> anyhow::Result<Vec<PeeringSession>> {
+        Ok(self
+            .sessions
+            .read()
+            .unwrap()
+            .get(&asn)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn save_sessions(&self, asn: u32, sessions: &[PeeringSession]) -> anyhow::Result<()> {
+        let mut all = self.sessions.write().unwrap();
+        if sessions.is_empty() {
+            all.remove(&asn);
+        } else {
+            all.insert(asn, sessions.to_vec());
+        }
+        Ok(())
+    }
+
+    fn session_count(&self) -> anyhow::Result<usize> {
+        Ok(self.sessions.read().unwrap().values().map(Vec::len).sum())
+    }
+
+    fn insert_credential(
+        &self,
+        token: &str,
+        asn: u32,
+        expires_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        self.credentials
+            .lock()
+            .unwrap()
+            .insert(hash_token(token), (asn, expires_at));
+        Ok(())
+    }
+
+    fn credential(&self, token: &str) -> anyhow::Result<Option<u32>> {
+        Ok(self
+            .credentials
+            .lock()
+            .unwrap()
+            .get(&hash_token(token))
+            .filter(|(_, expires_at)| *expires_at > Utc::now())
+            .map(|(asn, _)| *asn))
+    }
+
+    fn purge_expired_credentials(&self) -> anyhow::Result<()> {
+        let now = Utc::now();
+        self.credentials
+            .lock()
+            .unwrap()
+            .retain(|_, (_, expires_at)| *expires_at > now);
+        Ok(())
+    }
+
+    fn record_event(&self, event: &AuditEvent) -> anyhow::Result<()> {
+        self.events.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+
+    #[cfg(test)]
+    fn events(&self, asn: u32) -> anyhow::Result<Vec<AuditEvent>> {
+        Ok(self
+            .events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|e| e.asn == asn)
+            .cloned()
+            .collect())
+    }
+}
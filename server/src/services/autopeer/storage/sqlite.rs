@@ -0,0 +1,165 @@
+use std::{path::Path, sync::Mutex};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use common::auto_peer::PeeringSession;
+use rusqlite::{Connection, OptionalExtension, params};
+
+use super::{AuditEvent, Storage, hash_token};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS sessions (
+        id TEXT PRIMARY KEY,
+        asn INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS sessions_asn ON sessions (asn);
+    CREATE TABLE IF NOT EXISTS credentials (
+        token_hash TEXT PRIMARY KEY,
+        asn INTEGER NOT NULL,
+        expires_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS audit_events (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        timestamp INTEGER NOT NULL,
+        asn INTEGER NOT NULL,
+        action TEXT NOT NULL,
+        detail TEXT
+    );
+    CREATE INDEX IF NOT EXISTS audit_events_asn ON audit_events (asn);
+";
+
+pub struct SqliteStorage {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        if let Some(parent) = Path::new(path).parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create '{}'", parent.display()))?;
+        }
+
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open AutoPeer database '{}'", path))?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.execute_batch(SCHEMA)
+            .with_context(|| format!("Failed to initialize AutoPeer database '{}'", path))?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn sessions(&self, asn: u32) -> anyhow::Result<Vec<PeeringSession>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached("SELECT data FROM sessions WHERE asn = ?1")?;
+        let rows = stmt.query_map(params![asn], |row| row.get::<_, String>(0))?;
+
+        let mut sessions = Vec::new();
+        for data in rows {
+            sessions.push(serde_json::from_str(&data?).context("Corrupt session record")?);
+        }
+        Ok(sessions)
+    }
+
+    fn save_sessions(&self, asn: u32, sessions: &[PeeringSession]) -> anyhow::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM sessions WHERE asn = ?1", params![asn])?;
+        for session in sessions {
+            tx.execute(
+                "INSERT INTO sessions (id, asn, data) VALUES (?1, ?2, ?3)",
+                params![session.id, asn, serde_json::to_string(session)?],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn session_count(&self) -> anyhow::Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM sessions", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    fn insert_credential(
+        &self,
+        token: &str,
+        asn: u32,
+        expires_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO credentials (token_hash, asn, expires_at) VALUES (?1, ?2, ?3)",
+            params![hash_token(token), asn, expires_at.timestamp()],
+        )?;
+        Ok(())
+    }
+
+    fn credential(&self, token: &str) -> anyhow::Result<Option<u32>> {
+        let conn = self.conn.lock().unwrap();
+        let asn = conn
+            .query_row(
+                "SELECT asn FROM credentials WHERE token_hash = ?1 AND expires_at > ?2",
+                params![hash_token(token), Utc::now().timestamp()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(asn)
+    }
+
+    fn purge_expired_credentials(&self) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM credentials WHERE expires_at <= ?1",
+            params![Utc::now().timestamp()],
+        )?;
+        Ok(())
+    }
+
+    fn record_event(&self, event: &AuditEvent) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO audit_events (timestamp, asn, action, detail) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                event.timestamp.timestamp(),
+                event.asn,
+                event.action,
+                event.detail
+            ],
+        )?;
+        Ok(())
+    }
+
+    #[cfg(test)]
+    fn events(&self, asn: u32) -> anyhow::Result<Vec<AuditEvent>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached(
+            "SELECT timestamp, action, detail FROM audit_events WHERE asn = ?1 ORDER BY id",
+        )?;
+        let rows = stmt.query_map(params![asn], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })?;
+
+        let mut events = Vec::new();
+        for row in rows {
+            let (timestamp, action, detail) = row?;
+            events.push(AuditEvent {
+                timestamp: DateTime::from_timestamp(timestamp, 0).context("Corrupt audit event")?,
+                asn,
+                action,
+                detail,
+            });
+        }
+        Ok(events)
+    }
+}
@@ -1,59 +1,258 @@
 use std::{sync::Arc, time::Duration};
 
 use chrono::Utc;
-use common::models::NodeStatusDiff;
-use tokio::time::sleep;
+use common::{
+    command::{BirdCommand, BirdError},
+    diff::{calculate_diff, calculate_wireguard_diff},
+    models::{NodeStatusDiff, NodeWireGuard, Protocol},
+};
+use futures_util::future::join_all;
+use tokio::{
+    sync::mpsc,
+    time::{Instant, sleep, timeout_at},
+};
 use tracing::warn;
 
 use crate::{
     config::{Config, NodeConfig, PeeringInfo},
-    diff::calculate_diff,
-    services::request::{build_get, build_post},
+    services::{
+        alerts::AlertEngine,
+        api::fetch_wireguard,
+        request::{build_bird, build_get, error_message},
+        tunnels,
+    },
     state::{AppResponse, AppState, NodeProtocol},
     utils::parse_protocols,
 };
 
+const DEFAULT_POLL_INTERVAL: u64 = 10;
+const DEFAULT_TIMEOUT: u64 = 5;
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+const DEFAULT_WIREGUARD_POLL_INTERVAL: u64 = 30;
+const PEERING_POLL_INTERVAL: Duration = Duration::from_secs(30 * 60);
+/// How long to wait for other nodes after a poll result arrives, so nodes
+/// polled at the same time end up in a single broadcast.
+const BATCH_WINDOW: Duration = Duration::from_millis(250);
+
 pub fn spawn(state: AppState, config: Arc<Config>) {
-    tokio::spawn(run(state, config));
+    tokio::spawn(run(state.clone(), config.clone()));
+    tokio::spawn(run_wireguard(state, config));
 }
 
+/// Polls every node in its own task and merges the results. Broadcasts cover
+/// the full node list in config order; nodes that haven't reported yet are
+/// listed as pending, so a slow node doesn't hold back the others.
 async fn run(state: AppState, config: Arc<Config>) {
-    let client = create_client();
+    let client = reqwest::Client::new();
+    let mut alerts = config
+        .alerts
+        .clone()
+        .map(|c| AlertEngine::new(c, client.clone()));
+
+    let (tx, mut rx) = mpsc::channel(config.nodes.len().max(1));
+    for (index, node) in config.nodes.iter().enumerate() {
+        tokio::spawn(poll_node(
+            index,
+            node.clone(),
+            client.clone(),
+            state.clone(),
+            config.clone(),
+            tx.clone(),
+        ));
+    }
+    drop(tx);
+
+    let mut statuses: Vec<Option<NodeProtocol>> = vec![None; config.nodes.len()];
+    while next_batch(&mut rx, &mut statuses).await {
+        let new_statuses = merge_statuses(&statuses, &config.nodes);
+        let current_nodes = { state.nodes.read().unwrap().clone() };
+
+        let diffs = broadcast_updates(&state, new_statuses.clone(), &current_nodes);
+        tunnels::refresh(&state);
+        if let Some(alerts) = &mut alerts {
+            // Pending nodes haven't failed a poll, they mustn't count as
+            // unreachable.
+            let polled: Vec<NodeProtocol> = statuses.iter().flatten().cloned().collect();
+            alerts.evaluate(&polled, &diffs, &state.history);
+        }
+    }
+}
+
+/// Waits for the next poll result and stores it along with every other one
+/// that arrives within `BATCH_WINDOW`. Returns `false` once all pollers
+/// stopped.
+async fn next_batch(
+    rx: &mut mpsc::Receiver<(usize, NodeProtocol)>,
+    statuses: &mut [Option<NodeProtocol>],
+) -> bool {
+    let Some((index, status)) = rx.recv().await else {
+        return false;
+    };
+    statuses[index] = Some(status);
 
-    let mut poll_counter = 0u32;
-    const PEERING_POLL_INTERVAL: u32 = 180;
+    let deadline = Instant::now() + BATCH_WINDOW;
+    while let Ok(Some((index, status))) = timeout_at(deadline, rx.recv()).await {
+        statuses[index] = Some(status);
+    }
+    true
+}
+
+/// The latest status of every node in config order, pending for nodes that
+/// haven't reported yet.
+fn merge_statuses(statuses: &[Option<NodeProtocol>], nodes: &[NodeConfig]) -> Vec<NodeProtocol> {
+    statuses
+        .iter()
+        .zip(nodes)
+        .map(|(status, node)| status.clone().unwrap_or_else(|| pending(node)))
+        .collect()
+}
+
+/// Stands in for a node until its first poll finishes.
+fn pending(node: &NodeConfig) -> NodeProtocol {
+    NodeProtocol {
+        name: node.name.clone(),
+        protocols: Vec::new(),
+        last_updated: Utc::now(),
+        error: Some("Waiting for the first poll".to_string()),
+    }
+}
+
+async fn poll_node(
+    index: usize,
+    node: NodeConfig,
+    client: reqwest::Client,
+    state: AppState,
+    config: Arc<Config>,
+    tx: mpsc::Sender<(usize, NodeProtocol)>,
+) {
+    let interval = Duration::from_secs(node.poll_interval.unwrap_or(DEFAULT_POLL_INTERVAL));
+    let timeout = Duration::from_secs(node.timeout.unwrap_or(DEFAULT_TIMEOUT));
+    let mut last_peering_fetch: Option<Instant> = None;
+    let mut backoff = Backoff::new(interval);
 
     loop {
         if check_idle_timeout(&state, &config).await {
             continue;
         }
 
-        let mut new_statuses = Vec::new();
-        let current_nodes = { state.nodes.read().unwrap().clone() };
+        let should_fetch_peering =
+            last_peering_fetch.is_none_or(|t| t.elapsed() >= PEERING_POLL_INTERVAL);
+        if should_fetch_peering {
+            last_peering_fetch = Some(Instant::now());
+        }
 
-        let should_fetch_peering = poll_counter.is_multiple_of(PEERING_POLL_INTERVAL);
+        let status = process_node(&client, &node, timeout, &state, should_fetch_peering).await;
 
-        for node in &config.nodes {
-            let status =
-                process_node(&client, node, &state, &current_nodes, should_fetch_peering).await;
-            new_statuses.push(status);
+        let delay = backoff.next(status.error.is_some());
+
+        if tx.send((index, status)).await.is_err() {
+            return;
         }
+        sleep(delay).await;
+    }
+}
 
-        broadcast_updates(&state, new_statuses, &current_nodes);
+/// Unreachable nodes are retried with exponential backoff, starting from
+/// the regular interval and capped at `MAX_BACKOFF`.
+struct Backoff {
+    interval: Duration,
+    failures: u32,
+}
 
-        poll_counter = poll_counter.wrapping_add(1);
-        sleep(Duration::from_secs(10)).await;
+impl Backoff {
+    fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            failures: 0,
+        }
+    }
+
+    /// The delay before the next poll, after a poll that `failed` or not.
+    fn next(&mut self, failed: bool) -> Duration {
+        if !failed {
+            self.failures = 0;
+            return self.interval;
+        }
+        let delay = self
+            .interval
+            .saturating_mul(2u32.saturating_pow(self.failures))
+            .min(MAX_BACKOFF.max(self.interval));
+        self.failures = self.failures.saturating_add(1);
+        delay
     }
 }
 
-fn create_client() -> reqwest::Client {
-    reqwest::Client::builder()
-        .timeout(Duration::from_secs(5))
-        .build()
-        .unwrap_or_else(|e| {
-            warn!(error = ?e, "Failed to build reqwest client with timeout, falling back");
-            reqwest::Client::new()
-        })
+/// Polls WireGuard on all nodes and broadcasts the interfaces that changed.
+/// Nodes that can't be reached keep their last known interfaces.
+async fn run_wireguard(state: AppState, config: Arc<Config>) {
+    let interval = Duration::from_secs(
+        config
+            .wireguard_poll_interval
+            .unwrap_or(DEFAULT_WIREGUARD_POLL_INTERVAL),
+    );
+
+    loop {
+        if check_idle_timeout(&state, &config).await {
+            continue;
+        }
+
+        // Each node gets its own timeout, so a hung node doesn't hold back
+        // the update of the others.
+        let results = join_all(config.nodes.iter().map(|node| async {
+            let timeout = Duration::from_secs(node.timeout.unwrap_or(DEFAULT_TIMEOUT));
+            tokio::time::timeout(timeout, fetch_wireguard(&state.http_client, node))
+                .await
+                .unwrap_or_else(|_| {
+                    warn!(node = %node.name, "WireGuard request timed out");
+                    Err("Node is not reachable".to_string())
+                })
+        }))
+        .await;
+
+        let current = { state.wireguard.read().unwrap().clone() };
+        let new: Vec<NodeWireGuard> = config
+            .nodes
+            .iter()
+            .zip(results)
+            .map(|(node, result)| {
+                let (interfaces, error) = match result {
+                    Ok(interfaces) => (interfaces, None),
+                    Err(e) => (
+                        current
+                            .iter()
+                            .find(|n| n.name == node.name)
+                            .map(|n| n.interfaces.clone())
+                            .unwrap_or_default(),
+                        Some(e),
+                    ),
+                };
+                NodeWireGuard {
+                    name: node.name.clone(),
+                    interfaces,
+                    last_updated: Utc::now(),
+                    error,
+                }
+            })
+            .collect();
+
+        let diffs: Vec<_> = new
+            .iter()
+            .map(|node| {
+                let old = current.iter().find(|n| n.name == node.name);
+                (old, calculate_wireguard_diff(old, node))
+            })
+            .filter(|(old, diff)| !diff.is_empty() || old.is_none_or(|old| old.error != diff.e))
+            .map(|(_, diff)| diff)
+            .collect();
+
+        *state.wireguard.write().unwrap() = new;
+        if !diffs.is_empty() {
+            let _ = state.tx.send(AppResponse::WireGuardDiff { data: diffs });
+        }
+        tunnels::refresh(&state);
+
+        sleep(interval).await;
+    }
 }
 
 async fn check_idle_timeout(state: &AppState, config: &Config) -> bool {
@@ -74,7 +273,10 @@ async fn check_idle_timeout(state: &AppState, config: &Config) -> bool {
         return false;
     }
 
-    if let Some(idle_timeout_secs) = config.poll_idle_timeout {
+    // Alert rules need to see every poll, so polling never pauses with them.
+    if let Some(idle_timeout_secs) = config.poll_idle_timeout
+        && config.alerts.is_none()
+    {
         let should_pause = {
             let last_req = state.last_request_time.read().unwrap();
             last_req
@@ -109,16 +311,12 @@ async fn check_idle_timeout(state: &AppState, config: &Config) -> bool {
 async fn process_node(
     client: &reqwest::Client,
     node: &NodeConfig,
+    timeout: Duration,
     state: &AppState,
-    current_nodes: &[NodeProtocol],
     should_fetch_peering: bool,
 ) -> NodeProtocol {
-    let command = "show protocols";
-    let req = build_post(client, node, "/bird", command);
-    let resp = req.send().await;
-
     if (should_fetch_peering || !state.peering.read().unwrap().contains_key(&node.name))
-        && let Some(info) = fetch_peering_info(client, node).await
+        && let Some(info) = fetch_peering_info(client, node, timeout).await
     {
         state
             .peering
@@ -127,62 +325,85 @@ async fn process_node(
             .insert(node.name.clone(), info);
     }
 
-    match resp {
+    let req = build_bird(client, node, &BirdCommand::ShowProtocols).timeout(timeout);
+    let started = Instant::now();
+    let resp = req.send().await;
+
+    let status = match resp {
         Ok(r) => {
             if !r.status().is_success() {
                 warn!(node = %node.name, status = %r.status(), "Node returned error status");
-                let existing = current_nodes.iter().find(|n| n.name == node.name);
-                return NodeProtocol {
+                NodeProtocol {
                     name: node.name.clone(),
-                    protocols: existing.map(|n| n.protocols.clone()).unwrap_or_default(),
+                    protocols: cached(state, &node.name),
                     last_updated: Utc::now(),
-                    error: Some(format!("Node returned error: {}", r.status())),
-                };
-            }
-
-            match r.text().await {
-                Ok(text) => {
-                    let protocols = parse_protocols(&text);
-
-                    NodeProtocol {
-                        name: node.name.clone(),
-                        protocols,
-                        last_updated: Utc::now(),
-                        error: None,
-                    }
+                    error: Some(error_message(r).await),
                 }
-                Err(e) => {
-                    warn!(node = %node.name, error = ?e, "Failed to read BIRD response");
-                    let existing = current_nodes.iter().find(|n| n.name == node.name);
-                    NodeProtocol {
-                        name: node.name.clone(),
-                        protocols: existing.map(|n| n.protocols.clone()).unwrap_or_default(),
-                        last_updated: Utc::now(),
-                        error: Some(
-                            "Received invalid response from node. Showing cached data.".into(),
-                        ),
+            } else {
+                match r.text().await {
+                    Ok(text) => match text.lines().last().and_then(BirdError::from_trailer) {
+                        Some(error) => {
+                            warn!(
+                                node = %node.name,
+                                error = %error,
+                                "BIRD failed after sending output"
+                            );
+                            NodeProtocol {
+                                name: node.name.clone(),
+                                protocols: cached(state, &node.name),
+                                last_updated: Utc::now(),
+                                error: Some(error.to_string()),
+                            }
+                        }
+                        None => {
+                            let protocols = parse_protocols(&text);
+
+                            NodeProtocol {
+                                name: node.name.clone(),
+                                protocols,
+                                last_updated: Utc::now(),
+                                error: None,
+                            }
+                        }
+                    },
+                    Err(e) => {
+                        warn!(node = %node.name, error = ?e, "Failed to read BIRD response");
+                        NodeProtocol {
+                            name: node.name.clone(),
+                            protocols: cached(state, &node.name),
+                            last_updated: Utc::now(),
+                            error: Some(
+                                "Received invalid response from node. Showing cached data.".into(),
+                            ),
+                        }
                     }
                 }
             }
         }
         Err(e) => {
             warn!(node = %node.name, error = ?e, "Failed to contact node");
-            let existing = current_nodes.iter().find(|n| n.name == node.name);
             NodeProtocol {
                 name: node.name.clone(),
-                protocols: existing.map(|n| n.protocols.clone()).unwrap_or_default(),
+                protocols: cached(state, &node.name),
                 last_updated: Utc::now(),
                 error: Some("Unable to reach node. Showing cached data.".into()),
             }
         }
-    }
+    };
+
+    state
+        .metrics
+        .record_poll(&node.name, started.elapsed(), status.error.is_none());
+    status
 }
 
+/// Stores the new statuses, broadcasts what changed and returns the diffs
+/// (empty when nothing changed).
 fn broadcast_updates(
     state: &AppState,
     new_statuses: Vec<NodeProtocol>,
     current_nodes: &[NodeProtocol],
-) {
+) -> Vec<NodeStatusDiff> {
     let changed = if new_statuses.len() != current_nodes.len() {
         true
     } else {
@@ -192,26 +413,47 @@ fn broadcast_updates(
             .any(|(new, old)| {
                 new.name != old.name || new.protocols != old.protocols || new.error != old.error
             })
-            || new_statuses.iter().any(|n| n.error.is_some())
     };
 
+    for node in new_statuses.iter().filter(|n| n.error.is_none()) {
+        state
+            .history
+            .record(&node.name, &node.protocols, node.last_updated);
+    }
+
     {
         let mut w = state.nodes.write().unwrap();
         *w = new_statuses.clone();
     }
 
-    let resp = if changed {
-        let diffs: Vec<NodeStatusDiff> = new_statuses
+    let diffs: Vec<NodeStatusDiff> = if changed {
+        new_statuses
             .iter()
-            .zip(current_nodes.iter())
-            .map(|(new, old)| NodeStatusDiff {
-                n: new.name.clone(),
-                d: calculate_diff(&old.protocols, &new.protocols),
-                u: new.last_updated,
-                e: new.error.clone(),
+            .map(|new| {
+                let old = current_nodes
+                    .iter()
+                    .find(|n| n.name == new.name)
+                    .map(|n| n.protocols.as_slice())
+                    .unwrap_or_default();
+                NodeStatusDiff {
+                    n: new.name.clone(),
+                    d: calculate_diff(old, &new.protocols),
+                    u: new.last_updated,
+                    e: new.error.clone(),
+                }
             })
-            .collect();
-        AppResponse::ProtocolsDiff { data: diffs }
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    // Clients can only apply diffs to nodes they already know about.
+    let resp = if new_statuses.len() != current_nodes.len() {
+        AppResponse::Protocols { data: new_statuses }
+    } else if changed {
+        AppResponse::ProtocolsDiff {
+            data: diffs.clone(),
+        }
     } else {
         AppResponse::NoChange {
             last_updated: Utc::now(),
@@ -219,10 +461,27 @@ fn broadcast_updates(
     };
 
     let _ = state.tx.send(resp);
+    diffs
+}
+
+/// The last known protocols of a node, shown while it can't be reached.
+fn cached(state: &AppState, name: &str) -> Vec<Protocol> {
+    state
+        .nodes
+        .read()
+        .unwrap()
+        .iter()
+        .find(|n| n.name == name)
+        .map(|n| n.protocols.clone())
+        .unwrap_or_default()
 }
 
-async fn fetch_peering_info(client: &reqwest::Client, node: &NodeConfig) -> Option<PeeringInfo> {
-    let req = build_get(client, node, "/peering");
+async fn fetch_peering_info(
+    client: &reqwest::Client,
+    node: &NodeConfig,
+    timeout: Duration,
+) -> Option<PeeringInfo> {
+    let req = build_get(client, node, "/peering").timeout(timeout);
 
     match req.send().await {
         Ok(resp) if resp.status().is_success() => match resp.json::<Option<PeeringInfo>>().await {
@@ -242,3 +501,116 @@ async fn fetch_peering_info(client: &reqwest::Client, node: &NodeConfig) -> Opti
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str) -> NodeConfig {
+        NodeConfig {
+            name: name.to_string(),
+            url: format!("http://{}.example.com:8000", name),
+            shared_secret: None,
+            instance: None,
+            poll_interval: None,
+            timeout: None,
+        }
+    }
+
+    fn status(name: &str, error: Option<&str>) -> NodeProtocol {
+        NodeProtocol {
+            name: name.to_string(),
+            protocols: Vec::new(),
+            last_updated: Utc::now(),
+            error: error.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let mut backoff = Backoff::new(Duration::from_secs(10));
+        let delays: Vec<u64> = (0..8).map(|_| backoff.next(true).as_secs()).collect();
+        assert_eq!(delays, [10, 20, 40, 80, 160, 300, 300, 300]);
+
+        assert_eq!(backoff.next(false), Duration::from_secs(10));
+        assert_eq!(backoff.next(true), Duration::from_secs(10));
+        assert_eq!(backoff.next(true), Duration::from_secs(20));
+
+        // Intervals above the cap are never shortened.
+        let mut backoff = Backoff::new(Duration::from_secs(600));
+        assert_eq!(backoff.next(true), Duration::from_secs(600));
+        assert_eq!(backoff.next(true), Duration::from_secs(600));
+    }
+
+    #[test]
+    fn merges_in_config_order_with_pending_nodes() {
+        let nodes = [node("a"), node("b"), node("c")];
+        let statuses = [None, Some(status("b", None)), None];
+
+        let merged = merge_statuses(&statuses, &nodes);
+        let names: Vec<&str> = merged.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, ["a", "b", "c"]);
+        assert_eq!(
+            merged[0].error.as_deref(),
+            Some("Waiting for the first poll")
+        );
+        assert_eq!(merged[1].error, None);
+        assert_eq!(
+            merged[2].error.as_deref(),
+            Some("Waiting for the first poll")
+        );
+    }
+
+    #[tokio::test]
+    async fn batches_results_within_the_window() {
+        let (tx, mut rx) = mpsc::channel(4);
+        let mut statuses = vec![None; 3];
+
+        tx.send((0, status("a", None))).await.unwrap();
+        tx.send((2, status("c", None))).await.unwrap();
+        let late = tx.clone();
+        tokio::spawn(async move {
+            sleep(BATCH_WINDOW * 2).await;
+            late.send((1, status("b", None))).await.unwrap();
+        });
+
+        assert!(next_batch(&mut rx, &mut statuses).await);
+        assert!(statuses[0].is_some() && statuses[1].is_none() && statuses[2].is_some());
+
+        assert!(next_batch(&mut rx, &mut statuses).await);
+        assert!(statuses[1].is_some());
+
+        drop(tx);
+        assert!(!next_batch(&mut rx, &mut statuses).await);
+    }
+
+    #[tokio::test]
+    async fn unchanged_errors_are_no_change() {
+        let state = AppState::new();
+        let mut rx = state.tx.subscribe();
+        let current = vec![
+            status("a", None),
+            status("b", Some("Node is not reachable")),
+        ];
+        *state.nodes.write().unwrap() = current.clone();
+
+        let next = vec![
+            status("a", None),
+            status("b", Some("Node is not reachable")),
+        ];
+        assert!(broadcast_updates(&state, next, &current).is_empty());
+        assert!(matches!(
+            rx.recv().await.unwrap(),
+            AppResponse::NoChange { .. }
+        ));
+
+        let recovered = vec![status("a", None), status("b", None)];
+        let diffs = broadcast_updates(&state, recovered, &current);
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(diffs[1].e, None);
+        assert!(matches!(
+            rx.recv().await.unwrap(),
+            AppResponse::ProtocolsDiff { .. }
+        ));
+    }
+}
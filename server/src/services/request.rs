@@ -1,8 +1,9 @@
 use std::io;
 
 use axum::body::Bytes;
+use common::command::{BirdCommand, BirdError};
 use futures_util::StreamExt;
-use reqwest::{Body, Client, RequestBuilder};
+use reqwest::{Body, Client, RequestBuilder, Response};
 use tracing::info;
 
 use crate::config::NodeConfig;
@@ -32,6 +33,17 @@ pub fn build_post<B: Into<Body>>(
     }
 }
 
+/// A request running `command` through the node's `/bird` endpoint, on the
+/// node's BIRD instance if one is configured.
+pub fn build_bird(client: &Client, node: &NodeConfig, command: &BirdCommand) -> RequestBuilder {
+    let builder = build_post(client, node, "/bird", "").json(command);
+    if let Some(instance) = &node.instance {
+        builder.query(&[("instance", instance)])
+    } else {
+        builder
+    }
+}
+
 pub async fn fetch_stream(
     request: RequestBuilder,
 ) -> Result<impl futures_util::Stream<Item = Result<Bytes, io::Error>> + 'static, String> {
@@ -44,25 +56,20 @@ pub async fn fetch_stream(
                     .map(|chunk| chunk.map_err(io::Error::other));
                 Ok(stream)
             } else {
-                Err(resp
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "Node returned error".to_string()))
+                Err(error_message(resp).await)
             }
         }
         Err(_) => Err("Node is not reachable".to_string()),
     }
 }
 
-pub async fn post_stream<T: AsRef<str>>(
+pub async fn bird_stream(
     client: &Client,
     node: &NodeConfig,
-    url: T,
-    command: &str,
+    command: &BirdCommand,
 ) -> Result<impl futures_util::Stream<Item = Result<Bytes, io::Error>> + 'static, String> {
-    info!(node = %node.name, url = %url.as_ref(), command = %command, "POST");
-    let req = build_post(client, node, url, command.to_string());
-    fetch_stream(req).await
+    info!(node = %node.name, command = %command, "POST /bird");
+    fetch_stream(build_bird(client, node, command)).await
 }
 
 pub async fn get_stream<T: AsRef<str>>(
@@ -74,3 +81,51 @@ pub async fn get_stream<T: AsRef<str>>(
     let req = build_get(client, node, url);
     fetch_stream(req).await
 }
+
+/// The message of a failed response. Errors BIRD replied with arrive as a
+/// JSON [`BirdError`], anything else as plain text.
+pub async fn error_message(resp: Response) -> String {
+    let status = resp.status();
+    match resp.text().await {
+        Ok(text) => match serde_json::from_str::<BirdError>(&text) {
+            Ok(error) => error.to_string(),
+            Err(_) if text.trim().is_empty() => format!("Node returned error: {}", status),
+            Err(_) => text,
+        },
+        Err(_) => "Node returned error".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(instance: Option<&str>) -> NodeConfig {
+        NodeConfig {
+            name: "node1".to_string(),
+            url: "http://node1.example.com:8000/".to_string(),
+            shared_secret: Some("secret".to_string()),
+            instance: instance.map(str::to_string),
+            poll_interval: None,
+            timeout: None,
+        }
+    }
+
+    #[test]
+    fn bird_requests_name_the_instance() {
+        let client = Client::new();
+        let command: BirdCommand = "show protocols".parse().unwrap();
+
+        let request = build_bird(&client, &node(None), &command).build().unwrap();
+        assert_eq!(request.url().as_str(), "http://node1.example.com:8000/bird");
+        assert_eq!(request.headers()["x-shared-secret"], "secret");
+
+        let request = build_bird(&client, &node(Some("bird6")), &command)
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "http://node1.example.com:8000/bird?instance=bird6"
+        );
+    }
+}
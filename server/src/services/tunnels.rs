@@ -0,0 +1,225 @@
+use common::models::{NodeTunnels, ProtocolTunnel, WireGuardInterface};
+
+use crate::state::{AppResponse, AppState, NodeProtocol, NodeWireGuard};
+
+/// Recomputes which BGP sessions run over which WireGuard interface and
+/// broadcasts the result when it changed.
+pub fn refresh(state: &AppState) {
+    let tunnels = link(
+        &state.nodes.read().unwrap(),
+        &state.wireguard.read().unwrap(),
+    );
+
+    let mut current = state.tunnels.write().unwrap();
+    if *current != tunnels {
+        *current = tunnels.clone();
+        let _ = state.tx.send(AppResponse::Tunnels { data: tunnels });
+    }
+}
+
+/// Links every BGP protocol to its WireGuard interface. Proxies report the
+/// protocols of each interface; for older proxies that don't, a protocol is
+/// linked to the interface of the same name. Nodes without any linked
+/// protocol are left out.
+pub fn link(nodes: &[NodeProtocol], wireguard: &[NodeWireGuard]) -> Vec<NodeTunnels> {
+    nodes
+        .iter()
+        .filter_map(|node| {
+            let interfaces = &wireguard.iter().find(|n| n.name == node.name)?.interfaces;
+            let tunnels: Vec<ProtocolTunnel> = node
+                .protocols
+                .iter()
+                .filter(|p| p.proto == "BGP")
+                .filter_map(|p| {
+                    let interface = interfaces
+                        .iter()
+                        .find(|i| i.protocols.contains(&p.name))
+                        .or_else(|| interfaces.iter().find(|i| same_name(&i.name, &p.name)))?;
+                    Some(tunnel(&p.name, interface))
+                })
+                .collect();
+            (!tunnels.is_empty()).then(|| NodeTunnels {
+                name: node.name.clone(),
+                tunnels,
+            })
+        })
+        .collect()
+}
+
+fn tunnel(protocol: &str, interface: &WireGuardInterface) -> ProtocolTunnel {
+    let peer = interface.peers.iter().max_by_key(|p| p.latest_handshake);
+    ProtocolTunnel {
+        protocol: protocol.to_string(),
+        interface: interface.name.clone(),
+        endpoint: peer.and_then(|p| p.endpoint.clone()),
+        latest_handshake: peer.and_then(|p| p.latest_handshake),
+    }
+}
+
+/// BIRD doesn't allow `-` in protocol names, so `dn42_foo` matches an
+/// interface called `dn42-foo` as well.
+fn same_name(interface: &str, protocol: &str) -> bool {
+    interface.len() == protocol.len()
+        && interface
+            .chars()
+            .zip(protocol.chars())
+            .all(|(i, p)| i == p || (i == '-' && p == '_'))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use common::models::{Protocol, WireGuardPeer};
+
+    use super::*;
+
+    fn protocol(name: &str, proto: &str) -> Protocol {
+        Protocol {
+            name: name.to_string(),
+            proto: proto.to_string(),
+            table: "---".to_string(),
+            state: "up".to_string(),
+            since: "2025-01-01".to_string(),
+            info: "Established".to_string(),
+            reason: None,
+        }
+    }
+
+    fn node(name: &str, protocols: &[&str]) -> NodeProtocol {
+        NodeProtocol {
+            name: name.to_string(),
+            protocols: protocols.iter().map(|p| protocol(p, "BGP")).collect(),
+            last_updated: Utc::now(),
+            error: None,
+        }
+    }
+
+    fn interface(name: &str, protocols: &[&str], peers: &[(&str, i64)]) -> WireGuardInterface {
+        WireGuardInterface {
+            name: name.to_string(),
+            public_key: None,
+            listen_port: None,
+            peers: peers
+                .iter()
+                .map(|(endpoint, handshake)| WireGuardPeer {
+                    public_key: None,
+                    endpoint: Some(endpoint.to_string()),
+                    allowed_ips: Vec::new(),
+                    latest_handshake: Some(*handshake),
+                    rx_bytes: 0,
+                    tx_bytes: 0,
+                    persistent_keepalive: None,
+                })
+                .collect(),
+            protocols: protocols.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    fn wireguard(name: &str, interfaces: Vec<WireGuardInterface>) -> NodeWireGuard {
+        NodeWireGuard {
+            name: name.to_string(),
+            interfaces,
+            last_updated: Utc::now(),
+            error: None,
+        }
+    }
+
+    /// `(protocol, interface)` pairs of each node.
+    fn links(tunnels: &[NodeTunnels]) -> Vec<(&str, Vec<(&str, &str)>)> {
+        tunnels
+            .iter()
+            .map(|n| {
+                let pairs = n
+                    .tunnels
+                    .iter()
+                    .map(|t| (t.protocol.as_str(), t.interface.as_str()))
+                    .collect();
+                (n.name.as_str(), pairs)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn explicit_mapping_wins() {
+        let nodes = [node("node1", &["dn42_foo"])];
+        let wireguard = [wireguard(
+            "node1",
+            vec![
+                interface("dn42-foo", &[], &[]),
+                interface("wg-foo", &["dn42_foo"], &[]),
+            ],
+        )];
+        assert_eq!(
+            links(&link(&nodes, &wireguard)),
+            [("node1", vec![("dn42_foo", "wg-foo")])]
+        );
+    }
+
+    #[test]
+    fn naming_convention() {
+        let nodes = [node("node1", &["dn42_foo", "dn42_bar", "dn42_baz"])];
+        let wireguard = [wireguard(
+            "node1",
+            vec![
+                interface("dn42-foo", &[], &[]),
+                interface("dn42_bar", &[], &[]),
+                // Only whole names match.
+                interface("dn42_baz2", &[], &[]),
+            ],
+        )];
+        assert_eq!(
+            links(&link(&nodes, &wireguard)),
+            [(
+                "node1",
+                vec![("dn42_foo", "dn42-foo"), ("dn42_bar", "dn42_bar")]
+            )]
+        );
+        assert!(same_name("dn42-foo", "dn42_foo"));
+        assert!(!same_name("dn42_foo", "dn42-foo"));
+        assert!(!same_name("dn42-foo", "dn42_fo"));
+    }
+
+    #[test]
+    fn several_protocols_on_one_interface() {
+        let nodes = [node("node1", &["foo_v4", "foo_v6"])];
+        let wireguard = [wireguard(
+            "node1",
+            vec![interface(
+                "wg-foo",
+                &["foo_v4", "foo_v6"],
+                &[("192.0.2.1:51820", 100), ("192.0.2.2:51820", 200)],
+            )],
+        )];
+        let tunnels = link(&nodes, &wireguard);
+        assert_eq!(
+            links(&tunnels),
+            [("node1", vec![("foo_v4", "wg-foo"), ("foo_v6", "wg-foo")])]
+        );
+        // Both show the most recently active peer.
+        for tunnel in &tunnels[0].tunnels {
+            assert_eq!(tunnel.endpoint.as_deref(), Some("192.0.2.2:51820"));
+            assert_eq!(tunnel.latest_handshake, Some(200));
+        }
+    }
+
+    #[test]
+    fn unmatched_protocols_and_nodes() {
+        let mut node1 = node("node1", &["dn42_foo", "dn42_none"]);
+        node1.protocols.push(protocol("wg-foo", "Device"));
+        let nodes = [
+            node1,
+            node("node2", &["dn42_foo"]),
+            node("node3", &["dn42_none"]),
+        ];
+        let wireguard = [
+            wireguard("node1", vec![interface("wg-foo", &["dn42_foo"], &[])]),
+            wireguard("node3", vec![interface("wg-foo", &["dn42_foo"], &[])]),
+        ];
+        // node2 has no WireGuard data and nothing on node3 matches.
+        assert_eq!(
+            links(&link(&nodes, &wireguard)),
+            [("node1", vec![("dn42_foo", "wg-foo")])]
+        );
+        assert!(link(&nodes, &[]).is_empty());
+    }
+}
@@ -9,17 +9,21 @@ use std::{
 
 pub use common::{
     api::{AppRequest, AppResponse},
-    models::NodeProtocol,
+    models::{NodeProtocol, NodeTunnels, NodeWireGuard},
 };
 use tokio::sync::broadcast;
 use tracing::warn;
 
-use crate::config::PeeringInfo;
+use crate::{config::PeeringInfo, history::History, metrics::Metrics};
 
 #[derive(Clone)]
 pub struct AppState {
     pub nodes: Arc<RwLock<Vec<NodeProtocol>>>,
+    pub wireguard: Arc<RwLock<Vec<NodeWireGuard>>>,
+    pub tunnels: Arc<RwLock<Vec<NodeTunnels>>>,
     pub peering: Arc<RwLock<HashMap<String, PeeringInfo>>>,
+    pub history: History,
+    pub metrics: Metrics,
 
     pub http_client: reqwest::Client,
     pub tx: broadcast::Sender<AppResponse>,
@@ -44,7 +48,11 @@ impl AppState {
 
         Self {
             nodes: Arc::new(RwLock::new(Vec::new())),
+            wireguard: Arc::new(RwLock::new(Vec::new())),
+            tunnels: Arc::new(RwLock::new(Vec::new())),
             peering: Arc::new(RwLock::new(HashMap::new())),
+            history: History::default(),
+            metrics: Metrics::new(),
             http_client: client,
             tx,
             last_request_time: Arc::new(RwLock::new(None)),